@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sign_data_rust::{gpx, wire};
+
+// what `sign-batch` does with a GPX file before signing
+fuzz_target!(|data: &[u8]| {
+    let Ok(document) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(points) = gpx::read_gpx(document) {
+        for point in points {
+            let _ = wire::encode(&point.to_position(0));
+        }
+    }
+});
@@ -0,0 +1,31 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sign_data_rust::encoding;
+use sign_data_rust::scheme::{Scheme, Signer, VerifyingKey};
+
+// the first byte splits the input into the digest and the encoded signature or key
+fuzz_target!(|data: &[u8]| {
+    let Some((&split, rest)) = data.split_first() else {
+        return;
+    };
+    let (digest, text) = rest.split_at((split as usize).min(rest.len()));
+    let Ok(text) = std::str::from_utf8(text) else {
+        return;
+    };
+
+    let _ = encoding::decode(text, &[33, 64, 65]);
+    let _ = encoding::detect_public_key(text);
+    let _ = encoding::detect_signature(text);
+
+    let secret_key = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+    let signing_key = p256::ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+    for (scheme, public_key) in [
+        (Scheme::Secp256k1, Signer::public_key(&secret_key)),
+        (Scheme::P256, Signer::public_key(&signing_key)),
+    ] {
+        let _ = VerifyingKey::parse(scheme, text);
+        let key = VerifyingKey::parse(scheme, &public_key).unwrap();
+        let _ = key.verify(digest, text);
+    }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sign_data_rust::cosign::verify_threshold;
+use sign_data_rust::freshness::Freshness;
+use sign_data_rust::{gpx, kml, tsa, verify_signed_position, SignedPosition};
+
+// what `verify` does with each record of a signed positions file
+fuzz_target!(|data: &[u8]| {
+    for record in serde_json::Deserializer::from_slice(data).into_iter::<SignedPosition>() {
+        let Ok(record) = record else { break };
+        let result = verify_signed_position(&record);
+        let _ = verify_threshold(&record, &[], 1);
+        let _ = tsa::verify_position_timestamp(&record, 60);
+
+        let _ = gpx::write_gpx(&mut std::io::sink(), &[&record], 0);
+        let mut writer = kml::KmlWriter::new(std::io::sink(), "fuzz").unwrap();
+        let _ = writer.add(&record, &result, &Freshness::Fresh);
+        let _ = writer.finish();
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sign_data_rust::track;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(track) = track::unpack(data) {
+        let _ = track::verify_track(&track);
+    }
+});
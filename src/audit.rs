@@ -0,0 +1,299 @@
+//! Audit log of signing operations
+//!
+//! Every signature made, or refused, appends a JSON line to a dedicated log file: the time, the
+//! public key used, the digest signed, the client that asked, and the outcome. Each line carries
+//! in `prev_hash` the SHA-256 of the line before it, zeros for the first line, so that a line
+//! cannot be edited, removed or moved without breaking the links after it. Editing the last line
+//! is only detected against a head recorded elsewhere, see `verify_log`.
+//!
+//! Lines are buffered rather than written with every signature: they are written by the first
+//! signature made `FLUSH_INTERVAL` after the oldest of them, by a background thread when no
+//! signature follows within `FLUSH_INTERVAL`, or when the log is flushed or dropped. A process
+//! killed by a signal loses at most the lines of the last `FLUSH_INTERVAL`.
+//!
+//! A crash while writing may leave the last line cut short. Opening such a log leaves the partial
+//! line in place and appends a `recovered` entry holding its SHA-256, chained from the last
+//! complete line, which `verify_log` accepts in place of the partial line.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::clock::Clock;
+use crate::scheme::{KeyProvenance, Scheme, Signer};
+
+/// Previous hash of the first line of a log
+pub const GENESIS: [u8; 32] = [0; 32];
+
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// Milliseconds since the unix epoch
+    pub timestamp_ms: u64,
+    /// Hex encoded public key
+    pub key_id: String,
+    /// Hex encoded digest signed, unknown when signing failed before hashing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_hash: Option<String>,
+    /// Who asked for the signature, e.g. `cli:<user>`
+    pub client: String,
+    pub outcome: Outcome,
+    pub prev_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Signed,
+    /// Reason of the failure
+    Failed(String),
+    /// Hex encoded SHA-256 of the partial line left by a crash, which this entry follows
+    Recovered(String),
+}
+
+#[derive(Debug)]
+pub enum AuditError {
+    Io(io::Error),
+    /// Line number, counting from 1, that is not an entry
+    Malformed(usize),
+    /// Line number whose previous hash is not the hash of the line before
+    BrokenLink(usize),
+    /// The last line is not the expected head
+    HeadMismatch,
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditError::Io(err) => write!(f, "{}", err),
+            AuditError::Malformed(line) => write!(f, "line {} is not an audit entry", line),
+            AuditError::BrokenLink(line) => {
+                write!(f, "line {} does not follow the line before it", line)
+            }
+            AuditError::HeadMismatch => write!(f, "last line is not the expected head"),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+impl From<io::Error> for AuditError {
+    fn from(err: io::Error) -> Self {
+        AuditError::Io(err)
+    }
+}
+
+pub struct AuditLog {
+    clock: Box<dyn Clock>,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    out: BufWriter<File>,
+    head: [u8; 32],
+    /// When the oldest line not yet written was made
+    pending_since: Option<Instant>,
+}
+
+impl AuditLog {
+    /// Open a log for appending, chaining new lines after its last complete line
+    ///
+    /// The log is not checked otherwise, a broken link is reported by `verify_log`.
+    pub fn open(path: &Path, clock: Box<dyn Clock>) -> io::Result<Self> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        // a last line without its newline is complete when it is an entry, the crash having
+        // only cut the newline
+        let (complete, partial) = match content.strip_suffix(b"\n") {
+            Some(complete) => (complete, None),
+            None => match content.iter().rposition(|&byte| byte == b'\n') {
+                Some(end) => (&content[..end], Some(&content[end + 1..])),
+                None => (&content[..0], Some(&content[..])),
+            },
+        };
+        let (complete, partial) = match partial {
+            Some(last) if serde_json::from_slice::<AuditEntry>(last).is_ok() => {
+                (&content[..], None)
+            }
+            partial => (complete, partial.filter(|partial| !partial.is_empty())),
+        };
+        let head = match complete.rsplit(|&byte| byte == b'\n').next() {
+            Some(last) if !last.is_empty() => Sha256::digest(last).into(),
+            _ => GENESIS,
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if !content.is_empty() && !content.ends_with(b"\n") {
+            file.write_all(b"\n")?;
+        }
+        let state = Arc::new(Mutex::new(State {
+            out: BufWriter::new(file),
+            head,
+            pending_since: None,
+        }));
+        let flusher = Arc::downgrade(&state);
+        std::thread::spawn(move || flush_pending(flusher));
+        let log = AuditLog { clock, state };
+        if let Some(partial) = partial {
+            let digest = hex::encode(Sha256::digest(partial));
+            log.record("", None, "audit", Outcome::Recovered(digest))?;
+            log.flush()?;
+        }
+        Ok(log)
+    }
+
+    pub fn record(
+        &self,
+        key_id: &str,
+        payload_hash: Option<&[u8]>,
+        client: &str,
+        outcome: Outcome,
+    ) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let entry = AuditEntry {
+            timestamp_ms: self.clock.now_unix_ms(),
+            key_id: key_id.to_string(),
+            payload_hash: payload_hash.map(hex::encode),
+            client: client.to_string(),
+            outcome,
+            prev_hash: hex::encode(state.head),
+        };
+        let line = serde_json::to_string(&entry).expect("JSON serialization");
+        writeln!(state.out, "{}", line)?;
+        state.head = Sha256::digest(line.as_bytes()).into();
+        let pending_since = *state.pending_since.get_or_insert_with(Instant::now);
+        if pending_since.elapsed() >= FLUSH_INTERVAL {
+            state.out.flush()?;
+            state.pending_since = None;
+        }
+        Ok(())
+    }
+
+    /// Hash of the last line, to be recorded for checking the log later
+    pub fn head(&self) -> [u8; 32] {
+        self.state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .head
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.pending_since = None;
+        state.out.flush()
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Write the lines buffered for `FLUSH_INTERVAL`, until the log is dropped
+fn flush_pending(state: Weak<Mutex<State>>) {
+    loop {
+        std::thread::sleep(FLUSH_INTERVAL / 4);
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+        if state
+            .pending_since
+            .is_some_and(|since| since.elapsed() >= FLUSH_INTERVAL)
+        {
+            // a failure is reported by the next flush of the log
+            if state.out.flush().is_ok() {
+                state.pending_since = None;
+            }
+        }
+    }
+}
+
+/// Signer recording every signature it makes in a log
+pub struct AuditedSigner<'a> {
+    pub signer: &'a dyn Signer,
+    pub log: &'a AuditLog,
+    pub client: String,
+}
+
+impl Signer for AuditedSigner<'_> {
+    fn scheme(&self) -> Scheme {
+        self.signer.scheme()
+    }
+
+    fn provenance(&self) -> KeyProvenance {
+        self.signer.provenance()
+    }
+
+    fn public_key(&self) -> String {
+        self.signer.public_key()
+    }
+
+    /// Panics when the entry cannot be written: signing without an audit trail is not allowed
+    fn sign_digest(&self, digest: &[u8]) -> String {
+        let signature = self.signer.sign_digest(digest);
+        self.log
+            .record(
+                &self.signer.public_key(),
+                Some(digest),
+                &self.client,
+                Outcome::Signed,
+            )
+            .expect("audit log write");
+        signature
+    }
+}
+
+/// Check every link of a log, and that it ends with `head` when given, returning its head
+pub fn verify_log(path: &Path, head: Option<&[u8; 32]>) -> Result<[u8; 32], AuditError> {
+    verify_lines(&std::fs::read(path)?, head)
+}
+
+/// Hex encoded head given on the command line
+pub fn parse_head(text: &str) -> Option<[u8; 32]> {
+    <[u8; 32]>::from_hex(text.trim()).ok()
+}
+
+// bytes rather than text, a partial line possibly ending within a character
+fn verify_lines(content: &[u8], head: Option<&[u8; 32]>) -> Result<[u8; 32], AuditError> {
+    let entry = |line: &[u8]| serde_json::from_slice::<AuditEntry>(line).ok();
+    let mut previous = GENESIS;
+    let content = content.strip_suffix(b"\n").unwrap_or(content);
+    let mut lines = content
+        .split(|&byte| byte == b'\n')
+        .filter(|_| !content.is_empty())
+        .enumerate()
+        .peekable();
+    while let Some((index, line)) = lines.next() {
+        let Some(entry) = entry(line) else {
+            // a partial line is only accepted when followed by the entry recovering it
+            let digest = hex::encode(Sha256::digest(line));
+            match lines.peek().and_then(|(_, next)| entry(next)) {
+                Some(AuditEntry {
+                    outcome: Outcome::Recovered(recovered),
+                    ..
+                }) if recovered == digest => continue,
+                _ => return Err(AuditError::Malformed(index + 1)),
+            }
+        };
+        if entry.prev_hash != hex::encode(previous) {
+            return Err(AuditError::BrokenLink(index + 1));
+        }
+        previous = Sha256::digest(line).into();
+    }
+    match head {
+        Some(head) if *head != previous => Err(AuditError::HeadMismatch),
+        _ => Ok(previous),
+    }
+}
@@ -0,0 +1,132 @@
+//! One signature over a burst of positions
+//!
+//! Short bursts of fixes, a handful taken within seconds, are signed once over a combined digest
+//! rather than one by one. The positions are published with the signature so that the verifier
+//! recomputes the digest:
+//!
+//! ```text
+//! SHA-256("burst" || version (1 byte, 2) || count (4 bytes, big endian)
+//!         || for each position, in order: length (2 bytes, big endian) || wire encoding)
+//! ```
+//!
+//! The wire encoding is the one version 2 records sign, see `wire`. Length prefixes keep the
+//! boundaries between positions, and the count and order are covered, so that changing,
+//! dropping, adding or reordering a position changes the digest.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::scheme::{Scheme, Signer, VerifyingKey};
+use crate::wire::{self, WireError};
+use crate::{Position, VerifyError};
+
+/// Domain separating burst digests from record digests
+const DOMAIN: &[u8] = b"burst";
+
+/// Version of the combined digest, following the record version whose encoding it uses
+pub const BURST_VERSION: u8 = 2;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedBurst {
+    pub version: u8,
+    pub positions: Vec<Position>,
+    /// Hex encoded combined digest, recomputed by verifiers
+    pub digest: String,
+    pub signature: String,
+    pub public_key: String,
+    #[serde(default)]
+    pub scheme: Scheme,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BurstError {
+    /// Position that cannot be encoded, counting from 0
+    Position {
+        index: usize,
+        err: WireError,
+    },
+    EmptyBurst,
+    /// More positions than the count can tell
+    TooLarge,
+    UnsupportedVersion(u8),
+    /// The positions do not hash to the published digest
+    DigestMismatch,
+    Verify(VerifyError),
+}
+
+impl fmt::Display for BurstError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BurstError::Position { index, err } => write!(f, "position {}: {}", index, err),
+            BurstError::EmptyBurst => write!(f, "burst has no positions"),
+            BurstError::TooLarge => write!(f, "burst has too many positions"),
+            BurstError::UnsupportedVersion(version) => {
+                write!(f, "unsupported burst version {}", version)
+            }
+            BurstError::DigestMismatch => write!(f, "positions do not match the burst digest"),
+            BurstError::Verify(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for BurstError {}
+
+impl From<VerifyError> for BurstError {
+    fn from(err: VerifyError) -> Self {
+        BurstError::Verify(err)
+    }
+}
+
+pub fn combined_digest(positions: &[Position]) -> Result<[u8; 32], BurstError> {
+    if positions.is_empty() {
+        return Err(BurstError::EmptyBurst);
+    }
+    let count = u32::try_from(positions.len()).map_err(|_| BurstError::TooLarge)?;
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN);
+    hasher.update([BURST_VERSION]);
+    hasher.update(count.to_be_bytes());
+    for (index, position) in positions.iter().enumerate() {
+        let encoding = wire::encode(position).map_err(|err| BurstError::Position { index, err })?;
+        // encodings are at most `core::MAX_ENCODED_LENGTH` bytes long
+        hasher.update((encoding.len() as u16).to_be_bytes());
+        hasher.update(&encoding);
+    }
+    Ok(hasher.finalize().into())
+}
+
+pub fn sign_burst(
+    positions: Vec<Position>,
+    signer: &dyn Signer,
+) -> Result<SignedBurst, BurstError> {
+    let digest = combined_digest(&positions)?;
+    Ok(SignedBurst {
+        version: BURST_VERSION,
+        positions,
+        digest: hex::encode(digest),
+        signature: signer.sign_digest(&digest),
+        public_key: signer.public_key(),
+        scheme: signer.scheme(),
+    })
+}
+
+/// Recompute the combined digest of the published positions, then check the signature over it
+pub fn verify_burst(burst: &SignedBurst) -> Result<(), BurstError> {
+    if burst.version != BURST_VERSION {
+        return Err(BurstError::UnsupportedVersion(burst.version));
+    }
+    let digest = combined_digest(&burst.positions)?;
+    if hex::decode(&burst.digest).ok().as_deref() != Some(&digest[..]) {
+        return Err(BurstError::DigestMismatch);
+    }
+    let public_key = VerifyingKey::parse(burst.scheme, &burst.public_key)?;
+    if !public_key.verify(&digest, &burst.signature)? {
+        return Err(VerifyError::InvalidSignature {
+            public_key: burst.public_key.clone(),
+        }
+        .into());
+    }
+    Ok(())
+}
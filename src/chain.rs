@@ -0,0 +1,141 @@
+//! Hash chains of signed positions, making logs tamper-evident
+//!
+//! Each chained position carries in `prev_hash` the signed digest of the record before it, the
+//! first record of a chain carrying zeros. Since that digest covers the previous hash in turn, a
+//! record cannot be altered, removed or moved without breaking the links after it.
+//!
+//! The digest of the last signed record, the head of the chain, is kept in a state file so that
+//! signing can resume the chain after a restart.
+
+use std::fmt;
+use std::path::Path;
+
+use hex::FromHex;
+
+use crate::scheme::Signer;
+use crate::{sign_position, verify_signed_position, Position, SignedPosition};
+
+/// Previous hash of the first record of a chain
+pub const GENESIS: [u8; 32] = [0; 32];
+
+#[derive(Debug)]
+pub enum ChainError {
+    Io(std::io::Error),
+    MalformedHead,
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainError::Io(err) => write!(f, "{}", err),
+            ChainError::MalformedHead => write!(f, "malformed chain head, expected 32 hex bytes"),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+impl From<std::io::Error> for ChainError {
+    fn from(err: std::io::Error) -> Self {
+        ChainError::Io(err)
+    }
+}
+
+/// First broken link found when walking a chain
+#[derive(Debug, PartialEq)]
+pub enum ChainBreak {
+    /// The record does not verify, its content was changed after signing
+    Altered(usize),
+    /// The record links to a record that is not in the sequence
+    Removed(usize),
+    /// The record links to another record of the sequence than the one before it
+    Reordered(usize),
+    /// The record carries no previous hash
+    Unlinked(usize),
+}
+
+impl fmt::Display for ChainBreak {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainBreak::Altered(index) => write!(f, "record {} was altered", index),
+            ChainBreak::Removed(index) => write!(f, "records are missing before record {}", index),
+            ChainBreak::Reordered(index) => write!(f, "record {} is out of order", index),
+            ChainBreak::Unlinked(index) => write!(f, "record {} is not chained", index),
+        }
+    }
+}
+
+impl std::error::Error for ChainBreak {}
+
+/// Head stored in a state file, `GENESIS` when the file does not exist yet
+pub fn load_head(path: &Path) -> Result<[u8; 32], ChainError> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => <[u8; 32]>::from_hex(content.trim()).map_err(|_| ChainError::MalformedHead),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(GENESIS),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn save_head(path: &Path, head: &[u8; 32]) -> Result<(), ChainError> {
+    // write to a temporary file first, so that an interrupted write keeps the previous head
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, hex::encode(head) + "\n")?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Sign a position as the successor of `head`, moving `head` to the new record
+pub fn sign_linked(
+    mut position: Position,
+    head: &mut [u8; 32],
+    signer: &dyn Signer,
+) -> SignedPosition {
+    position.prev_hash = Some(hex::encode(*head));
+    let signed_position = sign_position(position, signer);
+    *head = link_hash(&signed_position).expect("record was just signed");
+    signed_position
+}
+
+/// Walk a sequence of records, checking every signature and link
+///
+/// With `head`, the first record must follow it, otherwise it is accepted as the start of the
+/// sequence whatever its previous hash.
+pub fn verify_chain(records: &[SignedPosition], head: Option<&[u8; 32]>) -> Result<(), ChainBreak> {
+    let mut hashes = Vec::with_capacity(records.len());
+    for (index, record) in records.iter().enumerate() {
+        if verify_signed_position(record).is_err() {
+            return Err(ChainBreak::Altered(index));
+        }
+        hashes.push(link_hash(record).ok_or(ChainBreak::Altered(index))?);
+    }
+
+    for (index, record) in records.iter().enumerate() {
+        let prev_hash = record
+            .position
+            .prev_hash
+            .as_deref()
+            .and_then(|prev_hash| <[u8; 32]>::from_hex(prev_hash).ok())
+            .ok_or(ChainBreak::Unlinked(index))?;
+        let expected = match index {
+            0 => match head {
+                Some(head) => head,
+                None => continue,
+            },
+            _ => &hashes[index - 1],
+        };
+        if prev_hash != *expected {
+            return Err(if hashes.contains(&prev_hash) {
+                ChainBreak::Reordered(index)
+            } else {
+                ChainBreak::Removed(index)
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Value the successor of a record carries as its previous hash
+pub fn link_hash(record: &SignedPosition) -> Option<[u8; 32]> {
+    record.digest().ok()?.as_ref().try_into().ok()
+}
@@ -0,0 +1,87 @@
+//! Sources of the current time, so that timestamps can be injected
+//!
+//! Positions are stamped with the time of a `Clock` rather than reading the system time directly:
+//! `SystemClock` is the default, `MockClock` gives deterministic timestamps for tests and
+//! replays, and `GpsClock` follows the time of the fixes of the receiver, for devices whose
+//! system clock is not to be trusted.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime};
+
+use crate::Position;
+
+pub trait Clock {
+    /// Milliseconds since the unix epoch
+    fn now_unix_ms(&self) -> u64;
+
+    fn now_unix_secs(&self) -> u64 {
+        self.now_unix_ms() / 1000
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_unix_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("system time after the unix epoch")
+            .as_millis() as u64
+    }
+}
+
+/// Clock that only moves when told to
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now_ms: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new(now_ms: u64) -> Self {
+        MockClock {
+            now_ms: AtomicU64::new(now_ms),
+        }
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now_ms.store(now_ms, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now_ms.fetch_add(ms, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_unix_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::Relaxed)
+    }
+}
+
+/// Time of the last fix observed, advanced by the monotonic time elapsed since
+///
+/// Before the first fix, the time is read from the system clock.
+#[derive(Debug, Default)]
+pub struct GpsClock {
+    last_fix: Option<(u64, Instant)>,
+}
+
+impl GpsClock {
+    pub fn new() -> Self {
+        GpsClock::default()
+    }
+
+    pub fn observe(&mut self, position: &Position) {
+        self.last_fix = Some((position.timestamp.saturating_mul(1000), Instant::now()));
+    }
+}
+
+impl Clock for GpsClock {
+    fn now_unix_ms(&self) -> u64 {
+        match self.last_fix {
+            Some((fix_ms, at)) => fix_ms.saturating_add(at.elapsed().as_millis() as u64),
+            None => SystemClock.now_unix_ms(),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Transparent zstd compression of artifacts on disk
+//!
+//! Track containers and proofs can be written as a single zstd frame. Readers detect the frame
+//! magic, so that uncompressed files keep working, and stream through the decoder rather than
+//! holding both forms in memory. Integrity checks, such as the section checksums of `track`, are
+//! computed over the uncompressed content, so recompressing an artifact does not invalidate it.
+//!
+//! Compression needs the `zstd` feature. Without it compressed files are recognized but cannot
+//! be read.
+
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+/// Magic number starting every zstd frame
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(&ZSTD_MAGIC)
+}
+
+/// Content of an artifact, decompressed when it is a zstd frame
+pub fn read_artifact(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = io::BufReader::new(std::fs::File::open(path)?);
+    if !is_compressed(file.fill_buf()?) {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        return Ok(data);
+    }
+    decompress(file)
+}
+
+/// Write an artifact, compressed at `level` when given
+pub fn write_artifact(path: &Path, data: &[u8], level: Option<i32>) -> io::Result<()> {
+    if let Some(level) = level {
+        check_level(level)?;
+    }
+    let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+    match level {
+        Some(level) => compress(data, level, &mut file)?,
+        None => file.write_all(data)?,
+    }
+    file.flush()
+}
+
+#[cfg(feature = "zstd")]
+fn decompress<R: Read>(reader: R) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    zstd::stream::read::Decoder::new(reader)?
+        .read_to_end(&mut data)
+        .map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("corrupted compressed data: {}", err),
+            )
+        })?;
+    Ok(data)
+}
+
+#[cfg(feature = "zstd")]
+fn check_level(level: i32) -> io::Result<()> {
+    let range = zstd::compression_level_range();
+    if !range.contains(&level) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "compression level must be in {}..={}",
+                range.start(),
+                range.end()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+fn compress<W: Write>(data: &[u8], level: i32, out: &mut W) -> io::Result<()> {
+    let mut encoder = zstd::stream::write::Encoder::new(out, level)?;
+    // catch corruption while decompressing, not only once the content is parsed
+    encoder.include_checksum(true)?;
+    encoder.write_all(data)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress<R: Read>(_reader: R) -> io::Result<Vec<u8>> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn check_level(_level: i32) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress<W: Write>(_data: &[u8], _level: i32, _out: &mut W) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "zstd compression needs the zstd feature",
+    )
+}
@@ -0,0 +1,173 @@
+//! Plausibility of a submitted track as a whole
+//!
+//! Valid signatures prove every fix was signed by the device, not that the fixes make a track: a
+//! device may have been switched off for an hour, or its receiver spoofed to jump across a city.
+//! `check_track` walks an ordered batch of records and reports every consecutive pair breaking a
+//! rule, with the value measured, rather than a single verdict. Signatures are checked apart,
+//! the report telling cryptographic failures from plausibility findings.
+//!
+//! Speeds are great circle distances over the time between fixes, see `geo`. Sequence numbers
+//! are the `key_use` counts written by `--include-key-use`, which follow each other for the
+//! records signed by a key in a row. `key_use` is not covered by the signatures, anyone handling
+//! the records can rewrite it, so sequence findings are only advisory: they are reported apart
+//! and do not make a track unclean.
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::geo::distance_m;
+use crate::{verify_signed_position, SignedPosition};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ContinuityPolicy {
+    /// Longest time between consecutive fixes, any when `None`
+    pub max_gap: Option<Duration>,
+    /// Fastest speed implied between consecutive fixes, in meters per second, any when `None`
+    pub max_speed_mps: Option<f64>,
+    /// Whether to report consecutive records of a key not carrying consecutive sequence numbers,
+    /// as advisory findings
+    pub check_sequence: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// The timestamp is not after the previous one, measured in seconds since it
+    IncreasingTimestamp,
+    /// Measured in seconds since the previous fix
+    MaxGap,
+    /// Measured in meters per second since the previous fix
+    MaxSpeed,
+    /// Measured as the difference to the previous sequence number of the key, absent when the
+    /// record carries none
+    Sequence,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// Index of the record in the batch, the rule applying to it and the record before
+    pub index: usize,
+    pub rule: Rule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub measured: Option<f64>,
+}
+
+/// Record whose signatures do not verify
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct InvalidRecord {
+    pub index: usize,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TrackReport {
+    pub records: usize,
+    /// Records failing `verify_signed_position`
+    pub invalid: Vec<InvalidRecord>,
+    /// Plausibility findings, made over every record whether valid or not
+    pub findings: Vec<Finding>,
+    /// Findings over the unsigned sequence numbers, not counted by `is_clean`
+    pub advisory: Vec<Finding>,
+}
+
+impl TrackReport {
+    /// Whether every record is valid and no signed field breaks a rule
+    pub fn is_clean(&self) -> bool {
+        self.invalid.is_empty() && self.findings.is_empty()
+    }
+}
+
+/// Verify every record of an ordered batch, then check the continuity of the track
+pub fn verify_track(records: &[SignedPosition], policy: &ContinuityPolicy) -> TrackReport {
+    let invalid = records
+        .iter()
+        .enumerate()
+        .filter_map(|(index, record)| {
+            verify_signed_position(record)
+                .err()
+                .map(|err| InvalidRecord {
+                    index,
+                    error: err.to_string(),
+                })
+        })
+        .collect();
+    TrackReport {
+        records: records.len(),
+        invalid,
+        findings: check_track(records, policy),
+        advisory: if policy.check_sequence {
+            check_sequence(records)
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+/// Findings of the rules of `policy` over the signed fields of consecutive records, in record
+/// order, `check_sequence` being left to `check_sequence`
+pub fn check_track(records: &[SignedPosition], policy: &ContinuityPolicy) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (index, pair) in records.windows(2).enumerate() {
+        let (previous, record) = (&pair[0].position, &pair[1].position);
+        let index = index + 1;
+        let mut finding = |rule, measured| {
+            findings.push(Finding {
+                index,
+                rule,
+                measured,
+            })
+        };
+        let elapsed = record.timestamp as f64 - previous.timestamp as f64;
+        if record.timestamp <= previous.timestamp {
+            finding(Rule::IncreasingTimestamp, Some(elapsed));
+            continue;
+        }
+        if policy
+            .max_gap
+            .is_some_and(|max_gap| elapsed > max_gap.as_secs_f64())
+        {
+            finding(Rule::MaxGap, Some(elapsed));
+        }
+        let speed = distance_m(previous, record) / elapsed;
+        if policy
+            .max_speed_mps
+            .is_some_and(|max_speed| speed > max_speed)
+        {
+            finding(Rule::MaxSpeed, Some(speed));
+        }
+    }
+    findings
+}
+
+/// Records whose sequence number does not follow the previous one of their key, in record order
+///
+/// Advisory only, the sequence numbers not being signed.
+pub fn check_sequence(records: &[SignedPosition]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut last: Vec<(&str, u64)> = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        let Some(sequence) = record.key_use else {
+            findings.push(Finding {
+                index,
+                rule: Rule::Sequence,
+                measured: None,
+            });
+            continue;
+        };
+        match last.iter_mut().find(|(key, _)| *key == record.public_key) {
+            Some((_, previous)) => {
+                if sequence != previous.wrapping_add(1) {
+                    findings.push(Finding {
+                        index,
+                        rule: Rule::Sequence,
+                        measured: Some(sequence as f64 - *previous as f64),
+                    });
+                }
+                *previous = sequence;
+            }
+            None => last.push((&record.public_key, sequence)),
+        }
+    }
+    findings
+}
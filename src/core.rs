@@ -0,0 +1,181 @@
+//! Signing of positions without the standard library
+//!
+//! This module is all that is left with `default-features = false`, for trackers running without
+//! an operating system. It encodes positions as in `wire`, hashes them and signs the digest with
+//! secp256k1, using fixed buffers only: nothing is allocated and the clock is never read,
+//! timestamps come from the caller. `wire` encodes through this module, so that both produce the
+//! same bytes and signatures.
+
+use core::fmt;
+
+use secp256k1::ffi::types::AlignedType;
+use secp256k1::{Message, Secp256k1, SecretKey};
+use sha2::Digest;
+
+pub const WIRE_VERSION: u8 = 2;
+/// Length of an encoding with an altitude, a previous hash, an expiry and a dwell
+pub const MAX_ENCODED_LENGTH: usize = 86;
+
+pub(crate) const FLAG_ALTITUDE: u8 = 1;
+pub(crate) const FLAG_PREV_HASH: u8 = 2;
+pub(crate) const FLAG_EXPIRY: u8 = 4;
+pub(crate) const FLAG_DWELL: u8 = 8;
+pub(crate) const DEGREE_SCALE: f64 = 1e9;
+pub(crate) const METER_SCALE: f64 = 1e3;
+pub(crate) const MAX_ALTITUDE: f64 = 1e9;
+
+// signing contexts hold no precomputed tables, they take 13 words on x86_64
+const CONTEXT_WORDS: usize = 32;
+
+/// Field of a fix that cannot be encoded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutOfRange(pub &'static str);
+
+impl fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is out of range", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    OutOfRange(OutOfRange),
+    InvalidSecretKey,
+    /// The signing context does not fit its buffer
+    ContextTooLarge,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::OutOfRange(err) => write!(f, "{}", err),
+            Error::InvalidSecretKey => write!(f, "invalid secret key"),
+            Error::ContextTooLarge => write!(f, "signing context does not fit its buffer"),
+        }
+    }
+}
+
+impl From<OutOfRange> for Error {
+    fn from(err: OutOfRange) -> Self {
+        Error::OutOfRange(err)
+    }
+}
+
+/// Position as read from the receiver, with its previous hash as raw bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fix {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub timestamp: u64,
+    pub altitude: Option<f64>,
+    pub prev_hash: Option<[u8; 32]>,
+    /// Unix seconds after which the record is no longer to be honored
+    pub expires_at: Option<u64>,
+    /// Number of fixes skipped since the previous record and the time of the last of them
+    pub dwell: Option<(u32, u64)>,
+}
+
+/// Encoded position, in a buffer long enough for any of them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Encoded {
+    buffer: [u8; MAX_ENCODED_LENGTH],
+    length: usize,
+}
+
+impl Encoded {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.length]
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.buffer[self.length..self.length + bytes.len()].copy_from_slice(bytes);
+        self.length += bytes.len();
+    }
+}
+
+/// Encode a fix in the layout of `wire`
+pub fn encode(fix: &Fix) -> Result<Encoded, OutOfRange> {
+    let latitude = to_fixed(fix.latitude, 90.0, DEGREE_SCALE, "latitude")?;
+    let longitude = to_fixed(fix.longitude, 180.0, DEGREE_SCALE, "longitude")?;
+    let altitude = match fix.altitude {
+        Some(altitude) => Some(to_fixed(altitude, MAX_ALTITUDE, METER_SCALE, "altitude")?),
+        None => None,
+    };
+
+    let mut flags = 0;
+    if altitude.is_some() {
+        flags |= FLAG_ALTITUDE;
+    }
+    if fix.prev_hash.is_some() {
+        flags |= FLAG_PREV_HASH;
+    }
+    if fix.expires_at.is_some() {
+        flags |= FLAG_EXPIRY;
+    }
+    if fix.dwell.is_some() {
+        flags |= FLAG_DWELL;
+    }
+    let mut out = Encoded {
+        buffer: [0; MAX_ENCODED_LENGTH],
+        length: 0,
+    };
+    out.push(&[WIRE_VERSION, flags]);
+    out.push(&latitude.to_be_bytes());
+    out.push(&longitude.to_be_bytes());
+    out.push(&fix.timestamp.to_be_bytes());
+    if let Some(altitude) = altitude {
+        out.push(&altitude.to_be_bytes());
+    }
+    if let Some(prev_hash) = &fix.prev_hash {
+        out.push(prev_hash);
+    }
+    if let Some(expires_at) = fix.expires_at {
+        out.push(&expires_at.to_be_bytes());
+    }
+    if let Some((count, last_seen)) = fix.dwell {
+        out.push(&count.to_be_bytes());
+        out.push(&last_seen.to_be_bytes());
+    }
+    Ok(out)
+}
+
+/// SHA-256 of the encoded fix, the message signed in version 2
+pub fn digest(fix: &Fix) -> Result<[u8; 32], OutOfRange> {
+    Ok(sha2::Sha256::digest(encode(fix)?.as_bytes()).into())
+}
+
+/// Sign a fix under version 2, returning the compact signature
+pub fn sign(fix: &Fix, secret_key: &[u8; 32]) -> Result<[u8; 64], Error> {
+    sign_digest(&digest(fix)?, secret_key)
+}
+
+/// Sign a digest with secp256k1, nonces are derived from the key and digest (RFC 6979)
+pub fn sign_digest(digest: &[u8; 32], secret_key: &[u8; 32]) -> Result<[u8; 64], Error> {
+    let mut buffer = [AlignedType::ZERO; CONTEXT_WORDS];
+    let secp =
+        Secp256k1::preallocated_signing_only(&mut buffer).map_err(|_| Error::ContextTooLarge)?;
+    let secret_key = SecretKey::from_slice(secret_key).map_err(|_| Error::InvalidSecretKey)?;
+    let signature = secp.sign_ecdsa(&Message::from_digest(*digest), &secret_key);
+    Ok(signature.serialize_compact())
+}
+
+fn to_fixed(value: f64, bound: f64, scale: f64, field: &'static str) -> Result<i64, OutOfRange> {
+    if !value.is_finite() || value.abs() > bound {
+        return Err(OutOfRange(field));
+    }
+    Ok(round(value * scale))
+}
+
+// f64::round is not in core. Scaled values stay far below 2^52, where the fraction left by
+// truncation is exact, so this rounds half away from zero just the same
+fn round(value: f64) -> i64 {
+    let truncated = value as i64;
+    let fraction = value - truncated as f64;
+    if fraction >= 0.5 {
+        truncated + 1
+    } else if fraction <= -0.5 {
+        truncated - 1
+    } else {
+        truncated
+    }
+}
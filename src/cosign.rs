@@ -0,0 +1,111 @@
+//! Co-signing of a position by several independent keys
+//!
+//! Every signer signs the same position hash, the position itself is never altered. A signed
+//! position is then accepted either when every carried signature verifies
+//! (`verify_signed_position`), or when at least `k` keys out of a trusted set produced a valid
+//! signature (`verify_threshold`).
+
+use std::fmt;
+
+use crate::scheme::{Signer, VerifyingKey};
+use crate::session;
+use crate::{CoSignature, SignedPosition, VerifyError};
+
+#[derive(Debug, PartialEq)]
+pub enum CoSignError {
+    /// The key already signed this position, or endorsed the session key that did
+    AlreadySigned(String),
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for CoSignError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoSignError::AlreadySigned(key) => {
+                write!(f, "public key {} already signed this position", key)
+            }
+            CoSignError::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CoSignError {}
+
+/// Append the signature of `signer` over the position hash
+pub fn co_sign(
+    signed_position: &mut SignedPosition,
+    signer: &dyn Signer,
+) -> Result<(), CoSignError> {
+    let serialized_public_key = signer.public_key();
+    let public_key = VerifyingKey::parse(signer.scheme(), &serialized_public_key).ok();
+
+    let endorsing = signed_position
+        .endorsement
+        .iter()
+        .map(|endorsement| (endorsement.device_scheme, endorsement.device_key.as_str()));
+    let already_signed = signed_position
+        .signatures()
+        .map(|(scheme, key, _)| (scheme, key))
+        .chain(endorsing)
+        .any(|(scheme, key)| VerifyingKey::parse(scheme, key).ok() == public_key);
+    if already_signed {
+        return Err(CoSignError::AlreadySigned(serialized_public_key));
+    }
+
+    let hash = signed_position
+        .digest()
+        .map_err(|_| CoSignError::UnsupportedVersion(signed_position.version))?;
+    signed_position.co_signatures.push(CoSignature {
+        signature: signer.sign_digest(&hash),
+        public_key: serialized_public_key,
+        scheme: signer.scheme(),
+    });
+    Ok(())
+}
+
+/// Check that at least `required` of the `trusted` keys produced a valid signature
+///
+/// Signatures from keys outside the trusted set, or that do not verify, are not counted. A key
+/// appearing twice in the signed position is rejected outright, the device key endorsing the
+/// session key of a record counting as appearing, see `session`. Returns the number of trusted
+/// keys with a valid signature.
+pub fn verify_threshold(
+    signed_position: &SignedPosition,
+    trusted: &[VerifyingKey],
+    required: usize,
+) -> Result<usize, VerifyError> {
+    let hash = signed_position.digest()?;
+    let mut seen = Vec::new();
+    let mut valid = 0;
+    for (index, (scheme, public_key_str, signature_str)) in signed_position.signatures().enumerate()
+    {
+        let public_key = VerifyingKey::parse(scheme, public_key_str)?;
+        // a session key is trusted as the device key endorsing it, which may not sign again
+        let identity = match index {
+            0 => session::device_key(signed_position)?,
+            _ => public_key,
+        };
+        if seen.contains(&public_key) {
+            return Err(VerifyError::DuplicateKey(public_key_str.to_string()));
+        }
+        if seen.contains(&identity) {
+            return Err(VerifyError::DuplicateKey(identity.to_hex()));
+        }
+        seen.push(public_key);
+        if identity != public_key {
+            seen.push(identity);
+        }
+        if !trusted.contains(&identity) {
+            continue;
+        }
+        if public_key.verify(&hash, signature_str).unwrap_or(false) {
+            valid += 1;
+        }
+    }
+    if valid < required {
+        return Err(VerifyError::ThresholdNotMet { valid, required });
+    }
+    Ok(valid)
+}
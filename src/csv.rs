@@ -0,0 +1,114 @@
+//! CSV import and export of routes
+//!
+//! One point per line, `latitude,longitude[,altitude[,time]]`, the time being either unix seconds
+//! or ISO 8601. A first line that does not start with a number is a header naming the columns,
+//! in any order: `latitude` (or `lat`), `longitude` (`lon`, `lng`), `altitude` (`ele`) and
+//! `time` (`timestamp`). Empty fields are missing values, blank lines and lines starting with `#`
+//! are skipped. Exported files have a header, and times in unix seconds.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::gpx::GpxPoint;
+use crate::time::parse_iso8601;
+use crate::Position;
+
+#[derive(Debug, PartialEq)]
+pub enum CsvError {
+    MissingColumn(&'static str),
+    /// Line number, counting from 1, and the field that could not be read
+    Malformed {
+        line: usize,
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::MissingColumn(column) => write!(f, "no {} column", column),
+            CsvError::Malformed { line, field } => write!(f, "line {}: invalid {}", line, field),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+pub fn read_csv(text: &str) -> Result<Vec<GpxPoint>, CsvError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    // column of latitude, longitude, altitude and time
+    let mut columns = [Some(0), Some(1), Some(2), Some(3)];
+    if let Some((_, header)) = lines.peek() {
+        let first = header.split(',').next().unwrap_or_default().trim();
+        if first.parse::<f64>().is_err() {
+            let names: Vec<String> = header
+                .split(',')
+                .map(|name| name.trim().to_ascii_lowercase())
+                .collect();
+            let find = |aliases: &[&str]| names.iter().position(|name| aliases.contains(&&**name));
+            columns = [
+                find(&["latitude", "lat"]),
+                find(&["longitude", "lon", "lng"]),
+                find(&["altitude", "ele"]),
+                find(&["time", "timestamp"]),
+            ];
+            columns[0].ok_or(CsvError::MissingColumn("latitude"))?;
+            columns[1].ok_or(CsvError::MissingColumn("longitude"))?;
+            lines.next();
+        }
+    }
+
+    let mut points = Vec::new();
+    for (line, content) in lines {
+        let fields: Vec<&str> = content.split(',').map(str::trim).collect();
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| fields.get(column))
+                .copied()
+                .filter(|field| !field.is_empty())
+        };
+        let malformed = |field| CsvError::Malformed { line, field };
+        let number = |column, name| match field(column) {
+            Some(value) => value.parse::<f64>().map(Some).map_err(|_| malformed(name)),
+            None => Ok(None),
+        };
+        let time = match field(columns[3]) {
+            Some(time) => Some(
+                time.parse::<u64>()
+                    .ok()
+                    .or_else(|| parse_iso8601(time))
+                    .ok_or_else(|| malformed("time"))?,
+            ),
+            None => None,
+        };
+        points.push(GpxPoint {
+            latitude: number(columns[0], "latitude")?.ok_or_else(|| malformed("latitude"))?,
+            longitude: number(columns[1], "longitude")?.ok_or_else(|| malformed("longitude"))?,
+            altitude: number(columns[2], "altitude")?,
+            time,
+        });
+    }
+    Ok(points)
+}
+
+pub fn write_csv<W: Write>(out: &mut W, positions: &[&Position]) -> io::Result<()> {
+    writeln!(out, "latitude,longitude,altitude,time")?;
+    for position in positions {
+        let altitude = position.altitude.map(|altitude| altitude.to_string());
+        writeln!(
+            out,
+            "{},{},{},{}",
+            position.latitude,
+            position.longitude,
+            altitude.unwrap_or_default(),
+            position.timestamp
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,159 @@
+//! Positions with coordinates carried as decimal strings
+//!
+//! Floating point coordinates do not always survive a JSON round trip through other languages
+//! (`2.3522` may come back as `2.3522000000000001`), which breaks the hash of the serialized
+//! position. In this mode latitude and longitude are serialized as strings with a fixed number of
+//! decimals, e.g. `"48.8566000"`, and the signed hash covers those exact strings.
+//!
+//! A coordinate string is an optional `-`, an integer part without leading zeros, a `.` and
+//! exactly 6 or 7 decimals; negative zero is rejected so that every value has one spelling.
+//! Latitudes must lie in [-90, 90] and longitudes in [-180, 180]. Altitudes and chain links are
+//! not carried.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::scheme::{Scheme, Signer, VerifyingKey};
+use crate::{hash_message, Position, VerifyError};
+
+#[derive(Debug, PartialEq)]
+pub enum DecimalError {
+    UnsupportedPrecision(u8),
+    Malformed(String),
+    OutOfRange(String),
+}
+
+impl fmt::Display for DecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecimalError::UnsupportedPrecision(decimals) => {
+                write!(
+                    f,
+                    "unsupported precision of {} decimals, use 6 or 7",
+                    decimals
+                )
+            }
+            DecimalError::Malformed(value) => write!(f, "malformed decimal coordinate {}", value),
+            DecimalError::OutOfRange(value) => write!(f, "coordinate {} is out of range", value),
+        }
+    }
+}
+
+impl std::error::Error for DecimalError {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DecimalPosition {
+    pub latitude: String,
+    pub longitude: String,
+    pub timestamp: u64,
+}
+
+impl DecimalPosition {
+    /// Round the coordinates of a position to `decimals` (6 or 7) decimals
+    pub fn from_position(position: &Position, decimals: u8) -> Result<Self, DecimalError> {
+        Ok(DecimalPosition {
+            latitude: format_coordinate(position.latitude, decimals, 90.0)?,
+            longitude: format_coordinate(position.longitude, decimals, 180.0)?,
+            timestamp: position.timestamp,
+        })
+    }
+
+    pub fn to_position(&self) -> Result<Position, DecimalError> {
+        Ok(Position {
+            latitude: parse_coordinate(&self.latitude, 90.0)?,
+            longitude: parse_coordinate(&self.longitude, 180.0)?,
+            timestamp: self.timestamp,
+            altitude: None,
+            prev_hash: None,
+            expires_at: None,
+            dwell_count: None,
+            last_seen: None,
+        })
+    }
+
+    /// Hash of the serialized position, with the coordinate strings exactly as carried
+    pub fn digest(&self) -> Result<Box<[u8]>, DecimalError> {
+        self.to_position()?;
+        let payload = serde_json::to_string(self).expect("JSON serialization");
+        Ok(hash_message(&payload))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedDecimalPosition {
+    pub position: DecimalPosition,
+    pub signature: String,
+    pub public_key: String,
+    #[serde(default)]
+    pub scheme: Scheme,
+}
+
+pub fn sign_decimal_position(
+    position: DecimalPosition,
+    signer: &dyn Signer,
+) -> Result<SignedDecimalPosition, DecimalError> {
+    let hash = position.digest()?;
+    Ok(SignedDecimalPosition {
+        position,
+        signature: signer.sign_digest(&hash),
+        public_key: signer.public_key(),
+        scheme: signer.scheme(),
+    })
+}
+
+pub fn verify_decimal_position(record: &SignedDecimalPosition) -> Result<(), VerifyError> {
+    let hash = record
+        .position
+        .digest()
+        .map_err(|err| VerifyError::MalformedCoordinate(err.to_string()))?;
+    let public_key = VerifyingKey::parse(record.scheme, &record.public_key)?;
+    if !public_key.verify(&hash, &record.signature)? {
+        return Err(VerifyError::InvalidSignature {
+            public_key: record.public_key.clone(),
+        });
+    }
+    Ok(())
+}
+
+pub fn format_coordinate(value: f64, decimals: u8, bound: f64) -> Result<String, DecimalError> {
+    if decimals != 6 && decimals != 7 {
+        return Err(DecimalError::UnsupportedPrecision(decimals));
+    }
+    if !value.is_finite() || value.abs() > bound {
+        return Err(DecimalError::OutOfRange(value.to_string()));
+    }
+    let formatted = format!("{:.*}", decimals as usize, value);
+    // values rounding to zero from below would otherwise be spelled "-0.000000"
+    match formatted.strip_prefix('-') {
+        Some(digits) if digits.bytes().all(|b| b == b'0' || b == b'.') => Ok(digits.to_string()),
+        _ => Ok(formatted),
+    }
+}
+
+pub fn parse_coordinate(value: &str, bound: f64) -> Result<f64, DecimalError> {
+    let malformed = || DecimalError::Malformed(value.to_string());
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let (integer, fraction) = digits.split_once('.').ok_or_else(malformed)?;
+
+    let only_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !only_digits(integer) || !only_digits(fraction) {
+        return Err(malformed());
+    }
+    if integer.len() > 3 || (integer.len() > 1 && integer.starts_with('0')) {
+        return Err(malformed());
+    }
+    if fraction.len() != 6 && fraction.len() != 7 {
+        return Err(malformed());
+    }
+    let is_zero = integer == "0" && fraction.bytes().all(|b| b == b'0');
+    if value.starts_with('-') && is_zero {
+        return Err(malformed());
+    }
+
+    let parsed: f64 = value.parse().map_err(|_| malformed())?;
+    if parsed.abs() > bound {
+        return Err(DecimalError::OutOfRange(value.to_string()));
+    }
+    Ok(parsed)
+}
@@ -0,0 +1,148 @@
+//! Deduplication of stationary fixes before signing
+//!
+//! A parked tracker keeps producing fixes a few meters apart, each one a record to store and
+//! prove. `Dedup` skips a fix within `radius_m` and `max_gap_secs` of the last fix it handed out,
+//! unless the motion changed: the speed over the last leg differing from the one the last handed
+//! out fix arrived at by more than `max_speed_change_mps`, or the heading turning by more than
+//! `max_turn_deg` while moving faster than `max_speed_change_mps`, slower legs being mostly
+//! receiver noise. Skipped fixes are not lost, the next fix handed out carries their
+//! number as `dwell_count` and the time of the last of them as `last_seen`, both signed. Once the
+//! source is exhausted, the last skipped fix is handed out with the others folded in.
+//!
+//! Fixes carry no speed or heading, both are derived from consecutive fixes.
+
+use crate::geo::{bearing_deg, distance_m};
+use crate::source::PositionSource;
+use crate::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DedupPolicy {
+    pub radius_m: f64,
+    pub max_gap_secs: u64,
+    pub max_speed_change_mps: f64,
+    pub max_turn_deg: f64,
+}
+
+impl Default for DedupPolicy {
+    fn default() -> Self {
+        DedupPolicy {
+            radius_m: 10.0,
+            max_gap_secs: 60,
+            max_speed_change_mps: 2.0,
+            max_turn_deg: 30.0,
+        }
+    }
+}
+
+/// Source handing out the fixes of another one, stationary ones folded into the next
+pub struct Dedup<'a> {
+    source: &'a mut dyn PositionSource,
+    policy: DedupPolicy,
+    /// Last fix handed out, with the motion it arrived at
+    last: Option<(Position, Option<Motion>)>,
+    /// Last fix read, skipped or not
+    previous: Option<Position>,
+    /// Last skipped fix
+    held: Option<Position>,
+    /// Count and last time of the fixes skipped before the held one
+    dwell: Option<(u32, u64)>,
+    skipped: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Motion {
+    speed_mps: f64,
+    bearing_deg: f64,
+}
+
+impl<'a> Dedup<'a> {
+    pub fn new(source: &'a mut dyn PositionSource, policy: DedupPolicy) -> Self {
+        Dedup {
+            source,
+            policy,
+            last: None,
+            previous: None,
+            held: None,
+            dwell: None,
+            skipped: 0,
+        }
+    }
+
+    /// Number of fixes skipped so far
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    fn is_stationary(&self, fix: &Position, motion: Option<Motion>) -> bool {
+        let Some((last, last_motion)) = &self.last else {
+            return false;
+        };
+        if distance_m(last, fix) > self.policy.radius_m
+            || fix.timestamp.saturating_sub(last.timestamp) > self.policy.max_gap_secs
+        {
+            return false;
+        }
+        let speed = |motion: Option<Motion>| motion.map_or(0.0, |motion| motion.speed_mps);
+        if (speed(motion) - speed(*last_motion)).abs() > self.policy.max_speed_change_mps {
+            return false;
+        }
+        match (motion, last_motion) {
+            (Some(motion), Some(last_motion))
+                if motion.speed_mps > self.policy.max_speed_change_mps
+                    && last_motion.speed_mps > self.policy.max_speed_change_mps =>
+            {
+                let turn = (motion.bearing_deg - last_motion.bearing_deg).rem_euclid(360.0);
+                turn.min(360.0 - turn) <= self.policy.max_turn_deg
+            }
+            _ => true,
+        }
+    }
+
+    /// Hand out `fix` with the skipped fixes folded in
+    fn hand_out(&mut self, mut fix: Position, motion: Option<Motion>) -> Position {
+        self.last = Some((fix.clone(), motion));
+        let dwell = match (self.dwell.take(), self.held.take()) {
+            (dwell, Some(held)) => Some((dwell.map_or(0, |(count, _)| count) + 1, held.timestamp)),
+            (dwell, None) => dwell,
+        };
+        if let Some((count, last_seen)) = dwell {
+            fix.dwell_count = Some(count);
+            fix.last_seen = Some(last_seen);
+        }
+        fix
+    }
+}
+
+impl PositionSource for Dedup<'_> {
+    fn next_fix(&mut self) -> Option<Position> {
+        loop {
+            let Some(fix) = self.source.next_fix() else {
+                let held = self.held.take()?;
+                return Some(self.hand_out(held, None));
+            };
+            let motion = self
+                .previous
+                .replace(fix.clone())
+                .and_then(|previous| motion(&previous, &fix));
+            if !self.is_stationary(&fix, motion) {
+                return Some(self.hand_out(fix, motion));
+            }
+            self.skipped += 1;
+            if let Some(held) = self.held.replace(fix) {
+                let count = self.dwell.map_or(0, |(count, _)| count);
+                self.dwell = Some((count + 1, held.timestamp));
+            }
+        }
+    }
+}
+
+fn motion(from: &Position, to: &Position) -> Option<Motion> {
+    let seconds = to.timestamp.checked_sub(from.timestamp)?;
+    if seconds == 0 {
+        return None;
+    }
+    Some(Motion {
+        speed_mps: distance_m(from, to) / seconds as f64,
+        bearing_deg: bearing_deg(from, to),
+    })
+}
@@ -0,0 +1,176 @@
+//! Minimal DER reader / writer, just enough for the ASN.1 structures used by `tsa`
+
+use std::fmt;
+
+use crate::time::days_from_civil;
+
+pub const TAG_BOOLEAN: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_UTF8_STRING: u8 = 0x0c;
+pub const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+
+/// Context specific, constructed tag `[n]`
+pub const fn context(n: u8) -> u8 {
+    0xa0 | n
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DerError {
+    Truncated,
+    BadLength,
+    UnexpectedTag { expected: u8, found: u8 },
+    BadTime,
+}
+
+impl fmt::Display for DerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DerError::Truncated => write!(f, "truncated DER input"),
+            DerError::BadLength => write!(f, "unsupported DER length encoding"),
+            DerError::UnexpectedTag { expected, found } => write!(
+                f,
+                "unexpected DER tag 0x{:02x}, expected 0x{:02x}",
+                found, expected
+            ),
+            DerError::BadTime => write!(f, "malformed GeneralizedTime"),
+        }
+    }
+}
+
+impl std::error::Error for DerError {}
+
+/// Cursor over a sequence of DER encoded TLVs
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// Read the next TLV, returning its tag, its content and the whole encoded element
+    pub fn read_any(&mut self) -> Result<(u8, &'a [u8], &'a [u8]), DerError> {
+        let tag = *self.data.first().ok_or(DerError::Truncated)?;
+        let first = *self.data.get(1).ok_or(DerError::Truncated)?;
+        let (len, header) = if first < 0x80 {
+            (first as usize, 2)
+        } else {
+            let n = (first & 0x7f) as usize;
+            if n == 0 || n > 4 {
+                return Err(DerError::BadLength);
+            }
+            let bytes = self.data.get(2..2 + n).ok_or(DerError::Truncated)?;
+            let len = bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+            (len, 2 + n)
+        };
+        let end = header.checked_add(len).ok_or(DerError::BadLength)?;
+        if self.data.len() < end {
+            return Err(DerError::Truncated);
+        }
+        let element = &self.data[..end];
+        let content = &self.data[header..end];
+        self.data = &self.data[end..];
+        Ok((tag, content, element))
+    }
+
+    /// Read the next TLV and check its tag, returning its content
+    pub fn read(&mut self, expected: u8) -> Result<&'a [u8], DerError> {
+        let (tag, content, _) = self.read_any()?;
+        if tag != expected {
+            return Err(DerError::UnexpectedTag {
+                expected,
+                found: tag,
+            });
+        }
+        Ok(content)
+    }
+
+    /// Read the next TLV only if it carries the given tag
+    pub fn read_optional(&mut self, expected: u8) -> Result<Option<&'a [u8]>, DerError> {
+        if self.peek_tag() == Some(expected) {
+            self.read(expected).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+pub fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+pub fn sequence(elements: &[&[u8]]) -> Vec<u8> {
+    encode(TAG_SEQUENCE, &elements.concat())
+}
+
+/// Encode an unsigned integer, adding a leading zero byte when the high bit is set
+pub fn unsigned_integer(value: &[u8]) -> Vec<u8> {
+    let skip = value.iter().take_while(|b| **b == 0).count();
+    let mut content = value[skip.min(value.len().saturating_sub(1))..].to_vec();
+    if content.is_empty() {
+        content.push(0);
+    }
+    if content[0] & 0x80 != 0 {
+        content.insert(0, 0);
+    }
+    encode(TAG_INTEGER, &content)
+}
+
+/// Strip the sign padding of a non negative DER integer
+pub fn integer_bytes(content: &[u8]) -> &[u8] {
+    match content {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        _ => content,
+    }
+}
+
+/// Parse a GeneralizedTime (`YYYYMMDDHHMMSS[.fff]Z`) into unix seconds, fractions are dropped
+pub fn parse_generalized_time(content: &[u8]) -> Result<u64, DerError> {
+    let text = std::str::from_utf8(content).map_err(|_| DerError::BadTime)?;
+    let text = text.strip_suffix('Z').ok_or(DerError::BadTime)?;
+    let whole = text.split('.').next().unwrap_or_default();
+    if whole.len() != 14 || !whole.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(DerError::BadTime);
+    }
+    let field = |range: std::ops::Range<usize>| whole[range].parse::<u64>().unwrap();
+    let (year, month, day) = (field(0..4), field(4..6), field(6..8));
+    let (hour, minute, second) = (field(8..10), field(10..12), field(12..14));
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(DerError::BadTime);
+    }
+    let days = days_from_civil(year as i64, month as u32, day as u32);
+    if days < 0 {
+        return Err(DerError::BadTime);
+    }
+    Ok(days as u64 * 86400 + hour * 3600 + minute * 60 + second)
+}
@@ -0,0 +1,183 @@
+//! Signers whose key lives in secure hardware
+//!
+//! Android Keystore and the Apple Secure Enclave never hand out private keys: they sign a digest
+//! given to them and return a DER encoded ECDSA signature, whose `s` may be high, and public keys
+//! uncompressed. The platform layer implements `KeyBackend` over its native API, and
+//! `EnclaveSigner` turns what it returns into the compressed keys and low-S compact signatures
+//! records carry. Both platforms only keep P-256 keys in hardware.
+//!
+//! The backends over Android Keystore and the Secure Enclave are not part of this crate: they
+//! call into JNI and the Security framework, which the platform layer binds. `SoftwareBackend`
+//! answers as those APIs do from a key held in memory, for tests and machines without the
+//! hardware. Its key is a software key whatever provenance it is told to claim.
+
+use std::fmt;
+
+use hex::ToHex;
+use p256::ecdsa::signature::hazmat::PrehashSigner;
+
+use crate::scheme::{KeyProvenance, Scheme, Signer, VerifyingKey};
+
+#[derive(Debug, PartialEq)]
+pub enum EnclaveError {
+    /// The platform refused to sign, e.g. the key was invalidated or user authentication failed
+    Backend(String),
+    InvalidPublicKey,
+    MalformedSignature,
+    /// The signature returned does not verify under the key of the backend
+    BadSignature,
+}
+
+impl fmt::Display for EnclaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnclaveError::Backend(err) => write!(f, "key backend failed: {}", err),
+            EnclaveError::InvalidPublicKey => write!(f, "invalid public key from the key backend"),
+            EnclaveError::MalformedSignature => {
+                write!(f, "malformed DER signature from the key backend")
+            }
+            EnclaveError::BadSignature => {
+                write!(f, "signature from the key backend does not verify")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EnclaveError {}
+
+/// Key kept by the platform, used through its native API
+pub trait KeyBackend {
+    fn scheme(&self) -> Scheme;
+
+    fn provenance(&self) -> KeyProvenance;
+
+    /// SEC1 encoded public key, compressed or not
+    fn public_key(&self) -> Vec<u8>;
+
+    /// DER encoded ECDSA signature over a 32 bytes digest, signed as is without hashing it again
+    fn sign_prehash(&self, digest: &[u8; 32]) -> Result<Vec<u8>, EnclaveError>;
+}
+
+pub struct EnclaveSigner<B> {
+    backend: B,
+    public_key: VerifyingKey,
+}
+
+impl<B: KeyBackend> EnclaveSigner<B> {
+    pub fn new(backend: B) -> Result<Self, EnclaveError> {
+        let public_key = VerifyingKey::parse(backend.scheme(), &hex::encode(backend.public_key()))
+            .map_err(|_| EnclaveError::InvalidPublicKey)?;
+        Ok(EnclaveSigner {
+            backend,
+            public_key,
+        })
+    }
+
+    /// Sign a digest, returning the normalized compact signature
+    ///
+    /// The signature is verified before being returned, so that a faulty backend cannot produce
+    /// records that would only fail later at verification.
+    pub fn try_sign_digest(&self, digest: &[u8; 32]) -> Result<[u8; 64], EnclaveError> {
+        let der = self.backend.sign_prehash(digest)?;
+        let signature = normalize(self.backend.scheme(), &der)?;
+        match self.public_key.verify(digest, &hex::encode(signature)) {
+            Ok(true) => Ok(signature),
+            _ => Err(EnclaveError::BadSignature),
+        }
+    }
+}
+
+impl<B: KeyBackend> Signer for EnclaveSigner<B> {
+    fn scheme(&self) -> Scheme {
+        self.backend.scheme()
+    }
+
+    fn provenance(&self) -> KeyProvenance {
+        self.backend.provenance()
+    }
+
+    fn public_key(&self) -> String {
+        self.public_key.to_hex()
+    }
+
+    /// # Panics
+    ///
+    /// If the backend fails to sign, use `try_sign_digest` to handle it.
+    fn sign_digest(&self, digest: &[u8]) -> String {
+        let digest = digest.try_into().expect("32 bytes");
+        match self.try_sign_digest(digest) {
+            Ok(signature) => signature.encode_hex(),
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
+/// Compact low-S form of a DER encoded signature
+pub fn normalize(scheme: Scheme, der: &[u8]) -> Result<[u8; 64], EnclaveError> {
+    match scheme {
+        Scheme::Secp256k1 => {
+            let mut signature = secp256k1::ecdsa::Signature::from_der(der)
+                .map_err(|_| EnclaveError::MalformedSignature)?;
+            signature.normalize_s();
+            Ok(signature.serialize_compact())
+        }
+        Scheme::P256 => {
+            let signature = p256::ecdsa::Signature::from_der(der)
+                .map_err(|_| EnclaveError::MalformedSignature)?;
+            let signature = signature.normalize_s().unwrap_or(signature);
+            Ok(signature.to_bytes().into())
+        }
+    }
+}
+
+pub enum SoftwareKey {
+    Secp256k1(secp256k1::SecretKey),
+    P256(p256::ecdsa::SigningKey),
+}
+
+/// Backend over a key held in memory, claiming whatever provenance it is given
+pub struct SoftwareBackend {
+    pub key: SoftwareKey,
+    pub provenance: KeyProvenance,
+}
+
+impl KeyBackend for SoftwareBackend {
+    fn scheme(&self) -> Scheme {
+        match self.key {
+            SoftwareKey::Secp256k1(_) => Scheme::Secp256k1,
+            SoftwareKey::P256(_) => Scheme::P256,
+        }
+    }
+
+    fn provenance(&self) -> KeyProvenance {
+        self.provenance
+    }
+
+    fn public_key(&self) -> Vec<u8> {
+        match &self.key {
+            SoftwareKey::Secp256k1(key) => {
+                let secp = secp256k1::Secp256k1::new();
+                key.public_key(&secp).serialize_uncompressed().to_vec()
+            }
+            SoftwareKey::P256(key) => key
+                .verifying_key()
+                .to_encoded_point(false)
+                .as_bytes()
+                .to_vec(),
+        }
+    }
+
+    fn sign_prehash(&self, digest: &[u8; 32]) -> Result<Vec<u8>, EnclaveError> {
+        match &self.key {
+            SoftwareKey::Secp256k1(key) => {
+                Ok(crate::sign_hash_slice(key, digest).serialize_der().to_vec())
+            }
+            SoftwareKey::P256(key) => {
+                let signature: p256::ecdsa::Signature = key
+                    .sign_prehash(digest)
+                    .map_err(|err| EnclaveError::Backend(err.to_string()))?;
+                Ok(signature.to_der().as_bytes().to_vec())
+            }
+        }
+    }
+}
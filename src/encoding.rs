@@ -0,0 +1,75 @@
+//! Decoding of binary fields whose text encoding is not known in advance
+//!
+//! Public keys and signatures are written as hex, but records produced by other stacks may carry
+//! them as base64. Encodings are tried in a fixed order, hex first, and the first one decoding to
+//! an expected length wins, so that a string valid under several encodings always reads the same.
+
+use std::fmt;
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Hex,
+    Base64,
+    Base64Unpadded,
+    Base64Url,
+    Base64UrlUnpadded,
+}
+
+/// Order in which encodings are tried
+const ENCODINGS: [Encoding; 5] = [
+    Encoding::Hex,
+    Encoding::Base64,
+    Encoding::Base64Unpadded,
+    Encoding::Base64Url,
+    Encoding::Base64UrlUnpadded,
+];
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Encoding::Hex => write!(f, "hex"),
+            Encoding::Base64 => write!(f, "base64"),
+            Encoding::Base64Unpadded => write!(f, "base64 without padding"),
+            Encoding::Base64Url => write!(f, "url-safe base64"),
+            Encoding::Base64UrlUnpadded => write!(f, "url-safe base64 without padding"),
+        }
+    }
+}
+
+impl Encoding {
+    pub fn decode(&self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            Encoding::Hex => hex::decode(text).ok(),
+            Encoding::Base64 => STANDARD.decode(text).ok(),
+            Encoding::Base64Unpadded => STANDARD_NO_PAD.decode(text).ok(),
+            Encoding::Base64Url => URL_SAFE.decode(text).ok(),
+            Encoding::Base64UrlUnpadded => URL_SAFE_NO_PAD.decode(text).ok(),
+        }
+    }
+}
+
+/// Decode `text` with the first encoding giving one of the `expected` lengths
+pub fn decode(text: &str, expected: &[usize]) -> Option<(Vec<u8>, Encoding)> {
+    ENCODINGS.iter().find_map(|encoding| {
+        encoding
+            .decode(text)
+            .filter(|bytes| expected.contains(&bytes.len()))
+            .map(|bytes| (bytes, *encoding))
+    })
+}
+
+/// Encoding `decode` would pick for a public key, compressed or not
+pub fn detect_public_key(text: &str) -> Option<Encoding> {
+    decode(text, &PUBLIC_KEY_LENGTHS).map(|(_, encoding)| encoding)
+}
+
+/// Encoding `decode` would pick for a compact signature
+pub fn detect_signature(text: &str) -> Option<Encoding> {
+    decode(text, &[SIGNATURE_LENGTH]).map(|(_, encoding)| encoding)
+}
+
+pub(crate) const PUBLIC_KEY_LENGTHS: [usize; 2] = [33, 65];
+pub(crate) const SIGNATURE_LENGTH: usize = 64;
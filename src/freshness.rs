@@ -0,0 +1,86 @@
+//! Staleness checks of verified records
+//!
+//! A valid signature only proves where a device was when it signed, not that it is still there.
+//! Callers accepting a record as the current location compare its timestamp to their clock,
+//! allowing for some skew between the device and the verifier, and pick their own policy for
+//! records that are too old or too far ahead.
+//!
+//! Records may also carry an expiry signed by the device, after which they are not to be honored
+//! whatever the policy: a record is current only when it is neither expired nor too old.
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::Position;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FreshnessPolicy {
+    /// Oldest accepted fix, any age being accepted when `None`
+    pub max_age: Option<Duration>,
+    /// How far ahead of the verifier clock a fix may be, any when `None`
+    pub max_future: Option<Duration>,
+}
+
+/// Age of a fix against a policy, fixes exactly at a bound being fresh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Freshness {
+    Fresh,
+    Stale {
+        age: Duration,
+    },
+    FromTheFuture {
+        ahead: Duration,
+    },
+    /// Past the expiry of the record, records being honored up to their expiry included
+    Expired {
+        since: Duration,
+    },
+}
+
+impl Freshness {
+    pub fn is_fresh(&self) -> bool {
+        matches!(self, Freshness::Fresh)
+    }
+}
+
+impl fmt::Display for Freshness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Freshness::Fresh => write!(f, "fresh"),
+            Freshness::Stale { age } => write!(f, "stale, taken {}s ago", age.as_secs()),
+            Freshness::FromTheFuture { ahead } => {
+                write!(f, "from the future, {}s ahead", ahead.as_secs())
+            }
+            Freshness::Expired { since } => write!(f, "expired {}s ago", since.as_secs()),
+        }
+    }
+}
+
+impl FreshnessPolicy {
+    pub fn check(&self, position: &Position, clock: &dyn Clock) -> Freshness {
+        let now_ms = clock.now_unix_ms();
+        if let Some(expires_at) = position.expires_at {
+            let expires_ms = expires_at.saturating_mul(1000);
+            if now_ms > expires_ms {
+                return Freshness::Expired {
+                    since: Duration::from_millis(now_ms - expires_ms),
+                };
+            }
+        }
+        let taken_ms = position.timestamp.saturating_mul(1000);
+        if taken_ms <= now_ms {
+            let age = Duration::from_millis(now_ms - taken_ms);
+            match self.max_age {
+                Some(max_age) if age > max_age => Freshness::Stale { age },
+                _ => Freshness::Fresh,
+            }
+        } else {
+            let ahead = Duration::from_millis(taken_ms - now_ms);
+            match self.max_future {
+                Some(max_future) if ahead > max_future => Freshness::FromTheFuture { ahead },
+                _ => Freshness::Fresh,
+            }
+        }
+    }
+}
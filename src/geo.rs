@@ -0,0 +1,99 @@
+//! Great-circle computations between positions
+//!
+//! Positions are treated as points on a sphere of the WGS-84 mean radius, which keeps errors
+//! below 0.5% compared to the ellipsoid. Longitudes of results are normalized to [-180, 180),
+//! so that paths crossing the antimeridian need no special care from callers.
+
+use crate::Position;
+
+/// Mean radius of the WGS-84 ellipsoid, in meters
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Haversine distance between two positions, in meters
+pub fn distance_m(a: &Position, b: &Position) -> f64 {
+    let (lat1, lat2) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.longitude - a.longitude).to_radians();
+
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    // rounding may push h slightly above 1 for antipodal points
+    2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()
+}
+
+/// Initial bearing of the great circle from `a` to `b`, in degrees clockwise from north in
+/// [0, 360)
+///
+/// The bearing between identical points is undefined, 0 is returned by convention. At the poles
+/// every direction is south (resp. north), the result is the bearing along the meridian of `b`.
+pub fn bearing_deg(a: &Position, b: &Position) -> f64 {
+    let (lat1, lat2) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let dlon = (b.longitude - a.longitude).to_radians();
+
+    let y = dlon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+    if x == 0.0 && y == 0.0 {
+        return 0.0;
+    }
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Position reached from `a` travelling `distance` meters along the great circle starting at
+/// `bearing` degrees, keeping the timestamp and altitude of `a`
+pub fn destination(a: &Position, bearing: f64, distance: f64) -> Position {
+    let lat1 = a.latitude.to_radians();
+    let lon1 = a.longitude.to_radians();
+    let bearing = bearing.to_radians();
+    let angular = distance / EARTH_RADIUS_M;
+
+    let lat2 = (lat1.sin() * angular.cos() + lat1.cos() * angular.sin() * bearing.cos())
+        .clamp(-1.0, 1.0)
+        .asin();
+    let lon2 = lon1
+        + (bearing.sin() * angular.sin() * lat1.cos())
+            .atan2(angular.cos() - lat1.sin() * lat2.sin());
+
+    Position {
+        latitude: lat2.to_degrees(),
+        longitude: normalize_longitude(lon2.to_degrees()),
+        timestamp: a.timestamp,
+        altitude: a.altitude,
+        prev_hash: None,
+        expires_at: None,
+        dwell_count: None,
+        last_seen: None,
+    }
+}
+
+/// Bring a longitude in degrees back to [-180, 180)
+pub fn normalize_longitude(longitude: f64) -> f64 {
+    (longitude + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Geohash of a position, `precision` characters long
+///
+/// Every character halves the longitude and latitude ranges 5 times, alternately, starting with
+/// the longitude. 9 characters give a cell of about 5 by 5 meters.
+pub fn geohash(position: &Position, precision: usize) -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+    let mut ranges = [(-180.0, 180.0), (-90.0, 90.0)];
+    let values = [position.longitude, position.latitude];
+    let mut hash = String::with_capacity(precision);
+    let mut bit = 0;
+    while hash.len() < precision {
+        let mut index = 0;
+        for _ in 0..5 {
+            let (low, high) = &mut ranges[bit % 2];
+            let middle = (*low + *high) / 2.0;
+            index <<= 1;
+            if values[bit % 2] >= middle {
+                index |= 1;
+                *low = middle;
+            } else {
+                *high = middle;
+            }
+            bit += 1;
+        }
+        hash.push(ALPHABET[index] as char);
+    }
+    hash
+}
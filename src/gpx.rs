@@ -0,0 +1,263 @@
+//! GPX import and export of tracks
+//!
+//! Exported tracks hold a single `trk` with one `trkpt` per verified position, in timestamp order.
+//! Coordinates are written with the shortest representation that reads back to the same `f64`,
+//! so a track can go through GPX without losing precision. The signing keys and the number of
+//! verified and excluded records go in the metadata extensions.
+//!
+//! The reader only understands what is needed to get points out of a GPX file: `trkpt`, `rtept`
+//! and `wpt` elements, with their `ele` and `time` children.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::time::{format_iso8601, parse_iso8601};
+use crate::{Position, SignedPosition};
+
+const EXTENSIONS_NAMESPACE: &str = "https://github.com/AntoineF4C5/sign_GPS_coords";
+
+#[derive(Debug, PartialEq)]
+pub enum GpxError {
+    /// A point element misses its `lat` or `lon` attribute, or they are not numbers
+    BadCoordinates(usize),
+    BadElevation(usize),
+    BadTime(usize),
+    Unterminated(usize),
+}
+
+impl fmt::Display for GpxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GpxError::BadCoordinates(index) => write!(f, "point {}: invalid lat/lon", index),
+            GpxError::BadElevation(index) => write!(f, "point {}: invalid elevation", index),
+            GpxError::BadTime(index) => write!(f, "point {}: invalid time", index),
+            GpxError::Unterminated(index) => write!(f, "point {}: unterminated element", index),
+        }
+    }
+}
+
+impl std::error::Error for GpxError {}
+
+/// Point read from a GPX file, whose time may be missing (e.g. in planned routes)
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpxPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+    pub time: Option<u64>,
+}
+
+impl GpxPoint {
+    /// Position of the point, using `default_time` when it has no time
+    pub fn to_position(&self, default_time: u64) -> Position {
+        Position {
+            latitude: self.latitude,
+            longitude: self.longitude,
+            timestamp: self.time.unwrap_or(default_time),
+            altitude: self.altitude,
+            prev_hash: None,
+            expires_at: None,
+            dwell_count: None,
+            last_seen: None,
+        }
+    }
+}
+
+/// Write verified positions as a GPX track, `excluded` being the number of records that failed
+/// verification
+pub fn write_gpx<W: Write>(
+    out: &mut W,
+    verified: &[&SignedPosition],
+    excluded: usize,
+) -> io::Result<()> {
+    let mut records = verified.to_vec();
+    records.sort_by_key(|record| record.position.timestamp);
+
+    let mut keys: Vec<(String, &str)> = Vec::new();
+    for record in &records {
+        for (scheme, public_key, _) in record.signatures() {
+            if !keys.iter().any(|(_, key)| *key == public_key) {
+                keys.push((scheme.to_string(), public_key));
+            }
+        }
+    }
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<gpx version="1.1" creator="signDataRust" xmlns="http://www.topografix.com/GPX/1/1" xmlns:sgc="{}">"#,
+        EXTENSIONS_NAMESPACE
+    )?;
+    writeln!(out, "  <metadata>")?;
+    writeln!(
+        out,
+        "    <desc>{} verified signed positions, {} excluded</desc>",
+        records.len(),
+        excluded
+    )?;
+    writeln!(out, "    <extensions>")?;
+    writeln!(
+        out,
+        r#"      <sgc:verification verified="{}" excluded="{}"/>"#,
+        records.len(),
+        excluded
+    )?;
+    for (scheme, public_key) in &keys {
+        writeln!(
+            out,
+            r#"      <sgc:public_key scheme="{}">{}</sgc:public_key>"#,
+            scheme,
+            escape(public_key)
+        )?;
+    }
+    writeln!(out, "    </extensions>")?;
+    writeln!(out, "  </metadata>")?;
+    let positions: Vec<&Position> = records.iter().map(|record| &record.position).collect();
+    write_track(out, &positions)?;
+    writeln!(out, "</gpx>")?;
+    Ok(())
+}
+
+/// Write positions as a GPX track, in the given order and without metadata
+pub fn write_gpx_positions<W: Write>(out: &mut W, positions: &[&Position]) -> io::Result<()> {
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<gpx version="1.1" creator="signDataRust" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+    write_track(out, positions)?;
+    writeln!(out, "</gpx>")?;
+    Ok(())
+}
+
+fn write_track<W: Write>(out: &mut W, positions: &[&Position]) -> io::Result<()> {
+    writeln!(out, "  <trk>")?;
+    writeln!(out, "    <trkseg>")?;
+    for position in positions {
+        writeln!(
+            out,
+            r#"      <trkpt lat="{}" lon="{}">"#,
+            position.latitude, position.longitude
+        )?;
+        if let Some(altitude) = position.altitude {
+            writeln!(out, "        <ele>{}</ele>", altitude)?;
+        }
+        writeln!(
+            out,
+            "        <time>{}</time>",
+            format_iso8601(position.timestamp)
+        )?;
+        writeln!(out, "      </trkpt>")?;
+    }
+    writeln!(out, "    </trkseg>")?;
+    writeln!(out, "  </trk>")?;
+    Ok(())
+}
+
+/// Points of every track, route and waypoint of a GPX document, in document order
+pub fn read_gpx(document: &str) -> Result<Vec<GpxPoint>, GpxError> {
+    let mut points = Vec::new();
+    let mut rest = document;
+    while let Some((name, start)) = next_point(rest) {
+        let index = points.len();
+        let element = &rest[start..];
+        let tag_end = element.find('>').ok_or(GpxError::Unterminated(index))?;
+        let tag = &element[..tag_end];
+
+        let coordinate = |name: &str| {
+            attribute(tag, name)
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .ok_or(GpxError::BadCoordinates(index))
+        };
+        let latitude = coordinate("lat")?;
+        let longitude = coordinate("lon")?;
+
+        let (body, consumed) = if tag.ends_with('/') {
+            ("", tag_end + 1)
+        } else {
+            let closing = format!("</{}>", name);
+            let body_end = element[tag_end + 1..]
+                .find(&closing)
+                .ok_or(GpxError::Unterminated(index))?
+                + tag_end
+                + 1;
+            (&element[tag_end + 1..body_end], body_end + closing.len())
+        };
+
+        let altitude = match child_text(body, "ele") {
+            Some(text) => Some(
+                text.trim()
+                    .parse::<f64>()
+                    .map_err(|_| GpxError::BadElevation(index))?,
+            ),
+            None => None,
+        };
+        let time = match child_text(body, "time") {
+            Some(text) => Some(parse_iso8601(text).ok_or(GpxError::BadTime(index))?),
+            None => None,
+        };
+
+        points.push(GpxPoint {
+            latitude,
+            longitude,
+            altitude,
+            time,
+        });
+        rest = &element[consumed..];
+    }
+    Ok(points)
+}
+
+// position of the next point element, skipping elements that merely share the prefix
+fn next_point(document: &str) -> Option<(&'static str, usize)> {
+    ["trkpt", "rtept", "wpt"]
+        .iter()
+        .filter_map(|name| {
+            let open = format!("<{}", name);
+            let mut offset = 0;
+            while let Some(found) = document[offset..].find(&open) {
+                let start = offset + found;
+                let after = document[start + open.len()..].chars().next();
+                if matches!(after, Some(c) if c.is_whitespace() || c == '>' || c == '/') {
+                    return Some((*name, start));
+                }
+                offset = start + open.len();
+            }
+            None
+        })
+        .min_by_key(|(_, start)| *start)
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    for quote in ['"', '\''] {
+        let pattern = format!("{}={}", name, quote);
+        let mut offset = 0;
+        while let Some(found) = tag[offset..].find(&pattern) {
+            let start = offset + found;
+            // make sure we matched `lat=` and not the end of another attribute name
+            let preceded_by_space = tag[..start].ends_with(char::is_whitespace);
+            let value_start = start + pattern.len();
+            if preceded_by_space {
+                let value_end = tag[value_start..].find(quote)? + value_start;
+                return Some(&tag[value_start..value_end]);
+            }
+            offset = value_start;
+        }
+    }
+    None
+}
+
+fn child_text<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = body.find(&open)? + open.len();
+    let end = body[start..].find(&close)? + start;
+    Some(&body[start..end])
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
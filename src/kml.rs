@@ -0,0 +1,156 @@
+//! KML export of signed positions, e.g. for Google Earth
+//!
+//! The document holds one `Placemark` per record, whose description balloon shows its signatures
+//! and verification status, followed by a `LineString` joining the verified positions in the
+//! order they were written. Records that failed verification are kept with the `invalid` style,
+//! so that gaps in the track stay visible. Valid records that are not current, see `freshness`,
+//! are kept with the `stale`, `future` or `expired` style and left out of the track as well.
+//!
+//! Placemarks are written as records come, only the coordinates of the track are kept in memory
+//! until `KmlWriter::finish`.
+
+use std::io::{self, Write};
+
+use crate::freshness::Freshness;
+use crate::gpx::escape;
+use crate::time::format_iso8601;
+use crate::{SignedPosition, VerifyError};
+
+const STYLES: &str = r#"    <Style id="valid">
+      <IconStyle><color>ff00c000</color><Icon><href>http://maps.google.com/mapfiles/kml/shapes/placemark_circle.png</href></Icon></IconStyle>
+    </Style>
+    <Style id="invalid">
+      <IconStyle><color>ff0000ff</color><scale>1.2</scale><Icon><href>http://maps.google.com/mapfiles/kml/shapes/forbidden.png</href></Icon></IconStyle>
+    </Style>
+    <Style id="stale">
+      <IconStyle><color>ff00c0ff</color><Icon><href>http://maps.google.com/mapfiles/kml/shapes/placemark_circle.png</href></Icon></IconStyle>
+    </Style>
+    <Style id="future">
+      <IconStyle><color>ffff00ff</color><Icon><href>http://maps.google.com/mapfiles/kml/shapes/placemark_circle.png</href></Icon></IconStyle>
+    </Style>
+    <Style id="expired">
+      <IconStyle><color>ff808080</color><Icon><href>http://maps.google.com/mapfiles/kml/shapes/placemark_circle.png</href></Icon></IconStyle>
+    </Style>
+    <Style id="track">
+      <LineStyle><color>ffff8000</color><width>3</width></LineStyle>
+    </Style>
+"#;
+
+pub struct KmlWriter<W: Write> {
+    out: W,
+    track: Vec<(f64, f64, Option<f64>)>,
+    records: usize,
+}
+
+impl<W: Write> KmlWriter<W> {
+    /// Start a document named `name`
+    pub fn new(mut out: W, name: &str) -> io::Result<Self> {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(out, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+        writeln!(out, "  <Document>")?;
+        writeln!(out, "    <name>{}</name>", escape(name))?;
+        out.write_all(STYLES.as_bytes())?;
+        Ok(KmlWriter {
+            out,
+            track: Vec::new(),
+            records: 0,
+        })
+    }
+
+    /// Write the placemark of a record, `status` being the outcome of its verification and
+    /// `freshness` the age of a valid record against the policy of the verifier
+    pub fn add(
+        &mut self,
+        record: &SignedPosition,
+        status: &Result<(), VerifyError>,
+        freshness: &Freshness,
+    ) -> io::Result<()> {
+        let position = &record.position;
+        let (style, status) = match (status, freshness) {
+            (Err(err), _) => ("invalid", format!("invalid, {}", err)),
+            (Ok(()), Freshness::Fresh) => ("valid", "valid".to_string()),
+            (Ok(()), Freshness::Stale { .. }) => ("stale", format!("valid but {}", freshness)),
+            (Ok(()), Freshness::FromTheFuture { .. }) => {
+                ("future", format!("valid but {}", freshness))
+            }
+            (Ok(()), Freshness::Expired { .. }) => ("expired", format!("valid but {}", freshness)),
+        };
+
+        let mut description = format!("<p><b>Status:</b> {}</p>", escape(&status));
+        for (scheme, public_key, signature) in record.signatures() {
+            description.push_str(&format!(
+                "<p><b>Key ({}):</b> {}<br/><b>Signature:</b> {}</p>",
+                scheme,
+                escape(public_key),
+                escape(signature)
+            ));
+        }
+
+        let out = &mut self.out;
+        writeln!(out, "    <Placemark>")?;
+        writeln!(out, "      <name>{}</name>", self.records)?;
+        writeln!(out, "      <styleUrl>#{}</styleUrl>", style)?;
+        writeln!(
+            out,
+            "      <TimeStamp><when>{}</when></TimeStamp>",
+            format_iso8601(position.timestamp)
+        )?;
+        // the description is HTML, escaped once more as the text of the element
+        writeln!(
+            out,
+            "      <description>{}</description>",
+            escape(&description)
+        )?;
+        writeln!(out, "      <Point>")?;
+        if position.altitude.is_some() {
+            writeln!(out, "        <altitudeMode>absolute</altitudeMode>")?;
+        }
+        writeln!(
+            out,
+            "        <coordinates>{}</coordinates>",
+            coordinates(position.latitude, position.longitude, position.altitude)
+        )?;
+        writeln!(out, "      </Point>")?;
+        writeln!(out, "    </Placemark>")?;
+
+        if style == "valid" {
+            self.track
+                .push((position.latitude, position.longitude, position.altitude));
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Write the track of the verified positions and close the document
+    pub fn finish(mut self) -> io::Result<W> {
+        let out = &mut self.out;
+        writeln!(out, "    <Placemark>")?;
+        writeln!(out, "      <name>Track</name>")?;
+        writeln!(out, "      <styleUrl>#track</styleUrl>")?;
+        writeln!(out, "      <LineString>")?;
+        writeln!(out, "        <tessellate>1</tessellate>")?;
+        writeln!(out, "        <coordinates>")?;
+        for (latitude, longitude, altitude) in &self.track {
+            writeln!(
+                out,
+                "          {}",
+                coordinates(*latitude, *longitude, *altitude)
+            )?;
+        }
+        writeln!(out, "        </coordinates>")?;
+        writeln!(out, "      </LineString>")?;
+        writeln!(out, "    </Placemark>")?;
+        writeln!(out, "  </Document>")?;
+        writeln!(out, "</kml>")?;
+        out.flush()?;
+        Ok(self.out)
+    }
+}
+
+// KML puts the longitude first
+fn coordinates(latitude: f64, longitude: f64, altitude: Option<f64>) -> String {
+    match altitude {
+        Some(altitude) => format!("{},{},{}", longitude, latitude, altitude),
+        None => format!("{},{}", longitude, latitude),
+    }
+}
@@ -0,0 +1,443 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+use hex::FromHex;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::fmt;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+#[cfg(feature = "std")]
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+#[cfg(feature = "std")]
+use sha2::Digest;
+
+#[cfg(feature = "std")]
+use scheme::{KeyProvenance, Scheme, Signer, VerifyingKey};
+
+#[cfg(feature = "std")]
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod burst;
+#[cfg(feature = "std")]
+pub mod chain;
+#[cfg(feature = "std")]
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod compress;
+#[cfg(feature = "std")]
+pub mod continuity;
+pub mod core;
+#[cfg(feature = "std")]
+pub mod cosign;
+#[cfg(feature = "std")]
+pub mod csv;
+#[cfg(feature = "std")]
+pub mod decimal;
+#[cfg(feature = "std")]
+pub mod dedup;
+#[cfg(feature = "std")]
+mod der;
+#[cfg(feature = "std")]
+pub mod enclave;
+#[cfg(feature = "std")]
+pub mod encoding;
+#[cfg(feature = "std")]
+pub mod freshness;
+#[cfg(feature = "std")]
+pub mod geo;
+#[cfg(feature = "std")]
+pub mod gpx;
+#[cfg(feature = "std")]
+pub mod kml;
+#[cfg(feature = "std")]
+pub mod lora;
+#[cfg(feature = "std")]
+pub mod multiformats;
+#[cfg(feature = "std")]
+pub mod nostr;
+#[cfg(feature = "std")]
+pub mod ots;
+#[cfg(feature = "std")]
+pub mod outbox;
+#[cfg(feature = "std")]
+pub mod payload;
+#[cfg(feature = "std")]
+pub mod revocation;
+#[cfg(feature = "std")]
+pub mod scheme;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "std")]
+pub mod source;
+#[cfg(feature = "std")]
+pub mod synthetic;
+#[cfg(feature = "std")]
+pub mod takeout;
+#[cfg(feature = "std")]
+pub mod time;
+#[cfg(feature = "std")]
+pub mod track;
+#[cfg(feature = "std")]
+pub mod transport;
+#[cfg(feature = "std")]
+pub mod tsa;
+#[cfg(feature = "std")]
+pub mod usage;
+#[cfg(feature = "std")]
+pub mod vectors;
+#[cfg(feature = "std")]
+pub mod wire;
+
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub timestamp: u64,
+    /// Meters above the WGS-84 ellipsoid, left out of the serialization when unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    /// Hex encoded digest of the previous record of a hash chain, see `chain`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_hash: Option<String>,
+    /// Unix seconds after which the record is no longer to be honored, see `freshness`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Fixes skipped by deduplication since the previous record, see `dedup`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dwell_count: Option<u32>,
+    /// Unix seconds of the last fix skipped, set together with `dwell_count`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<u64>,
+}
+
+#[cfg(feature = "std")]
+/// Version of the signing rules used for new records
+pub const CURRENT_VERSION: u8 = 2;
+
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedPosition {
+    /// Signing rules of the record, records predating this field are version 1
+    #[serde(default = "legacy_version")]
+    pub version: u8,
+    pub position: Position,
+    pub signature: String,
+    pub public_key: String,
+    #[serde(default)]
+    pub scheme: Scheme,
+    /// Signatures of other keys over the same position hash, see `cosign`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub co_signatures: Vec<CoSignature>,
+    /// DER encoded RFC 3161 TimeStampToken over the position hash, see `tsa`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_token: Option<String>,
+    /// Where the device key is kept, as claimed by the signer and not signed
+    #[serde(default, skip_serializing_if = "KeyProvenance::is_software")]
+    pub provenance: KeyProvenance,
+    /// Signatures made with the device key up to this one, as counted by the signer and not
+    /// signed, see `usage`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_use: Option<u64>,
+    /// Endorsement of the key that signed the record by the long-term device key, see `session`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endorsement: Option<session::Endorsement>,
+}
+
+#[cfg(feature = "std")]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CoSignature {
+    pub public_key: String,
+    pub signature: String,
+    #[serde(default)]
+    pub scheme: Scheme,
+}
+
+#[cfg(feature = "std")]
+fn legacy_version() -> u8 {
+    1
+}
+
+#[cfg(feature = "std")]
+impl SignedPosition {
+    /// Hash signed by every signer of the record, according to its version
+    pub fn digest(&self) -> Result<Box<[u8]>, VerifyError> {
+        position_digest(&self.position, self.version)
+    }
+
+    /// All (scheme, public key, signature) triples, the original signer first
+    pub fn signatures(&self) -> impl Iterator<Item = (Scheme, &str, &str)> {
+        std::iter::once((
+            self.scheme,
+            self.public_key.as_str(),
+            self.signature.as_str(),
+        ))
+        .chain(
+            self.co_signatures
+                .iter()
+                .map(|co| (co.scheme, co.public_key.as_str(), co.signature.as_str())),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+    MalformedPublicKey(String),
+    MalformedSignature(String),
+    InvalidSignature {
+        public_key: String,
+    },
+    DuplicateKey(String),
+    /// Fewer than `required` trusted keys produced a valid signature
+    ThresholdNotMet {
+        valid: usize,
+        required: usize,
+    },
+    /// The payload does not match the hash recorded in a `SignedPayload`
+    PayloadMismatch,
+    UnsupportedVersion(u8),
+    MalformedCoordinate(String),
+    /// The record carries an endorsement of another key than the one that signed it
+    NotEndorsed {
+        public_key: String,
+    },
+    /// The endorsement is not signed by the device key it names
+    InvalidEndorsement {
+        device_key: String,
+    },
+    /// The record was taken outside the window of its session key
+    OutsideSession {
+        timestamp: u64,
+        not_before: u64,
+        not_after: u64,
+    },
+    /// A key that signed the record was revoked at or before the time of the record
+    Revoked {
+        public_key: String,
+        revoked_at: u64,
+    },
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyError::MalformedPublicKey(key) => write!(f, "malformed public key {}", key),
+            VerifyError::MalformedSignature(sig) => write!(f, "malformed signature {}", sig),
+            VerifyError::InvalidSignature { public_key } => {
+                write!(f, "invalid signature for public key {}", public_key)
+            }
+            VerifyError::DuplicateKey(key) => write!(f, "public key {} signed twice", key),
+            VerifyError::ThresholdNotMet { valid, required } => write!(
+                f,
+                "{} valid signatures from trusted keys, {} required",
+                valid, required
+            ),
+            VerifyError::PayloadMismatch => write!(f, "payload does not match the signed hash"),
+            VerifyError::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {}", version)
+            }
+            VerifyError::MalformedCoordinate(reason) => write!(f, "{}", reason),
+            VerifyError::NotEndorsed { public_key } => {
+                write!(
+                    f,
+                    "public key {} is not the endorsed session key",
+                    public_key
+                )
+            }
+            VerifyError::InvalidEndorsement { device_key } => {
+                write!(
+                    f,
+                    "invalid session endorsement for device key {}",
+                    device_key
+                )
+            }
+            VerifyError::OutsideSession {
+                timestamp,
+                not_before,
+                not_after,
+            } => write!(
+                f,
+                "timestamp {} outside the session from {} to {}",
+                timestamp, not_before, not_after
+            ),
+            VerifyError::Revoked {
+                public_key,
+                revoked_at,
+            } => write!(f, "key {} revoked at {}", public_key, revoked_at),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifyError {}
+
+// secret key used by the wasm export, so that the execution can be proved without extra inputs
+#[cfg(feature = "std")]
+const SECRET_KEY_HEX: &str = "3132333435363738393031323334353637383930313233343536373839303131";
+
+#[cfg(feature = "std")]
+#[no_mangle]
+pub fn sign_coordinates(latitude: f64, longitude: f64, timestamp: u64) -> SignedPosition {
+    // convert hex encoded secret key to bytes
+    let secret_key_bytes = hex::decode(SECRET_KEY_HEX).expect("Invalid hex");
+    let (secret_key, _) = create_key_pair_from_bytes(secret_key_bytes.as_slice());
+
+    let position = Position {
+        latitude,
+        longitude,
+        timestamp,
+        altitude: None,
+        prev_hash: None,
+        expires_at: None,
+        dwell_count: None,
+        last_seen: None,
+    };
+    sign_position(position, &secret_key)
+}
+
+#[cfg(feature = "std")]
+/// Sign a position under the current version
+///
+/// # Panics
+///
+/// If the coordinates cannot be encoded, see `wire::encode`.
+pub fn sign_position(position: Position, signer: &dyn Signer) -> SignedPosition {
+    try_sign_position(position, CURRENT_VERSION, signer).expect("coordinates in range")
+}
+
+#[cfg(feature = "std")]
+/// Sign a position under the rules of `version`, failing on unknown versions and positions
+/// that version cannot hash
+pub fn try_sign_position(
+    position: Position,
+    version: u8,
+    signer: &dyn Signer,
+) -> Result<SignedPosition, VerifyError> {
+    // hash payload
+    let result = position_digest(&position, version)?;
+    let hash = result.as_ref();
+
+    // sign hash, signature and public key come hex serialized
+    Ok(SignedPosition {
+        version,
+        position,
+        signature: signer.sign_digest(hash),
+        public_key: signer.public_key(),
+        scheme: signer.scheme(),
+        co_signatures: Vec::new(),
+        timestamp_token: None,
+        provenance: signer.provenance(),
+        key_use: None,
+        endorsement: None,
+    })
+}
+
+#[cfg(feature = "std")]
+/// Hash signed for a position under the rules of `version`
+///
+/// - version 1: SHA-256 of the JSON serialized position
+/// - version 2: SHA-256 of the binary encoding of `wire`
+pub fn position_digest(position: &Position, version: u8) -> Result<Box<[u8]>, VerifyError> {
+    match version {
+        1 => Ok(hash_position(position)),
+        2 => wire::digest(position)
+            .map(|digest| digest.as_slice().into())
+            .map_err(|err| VerifyError::MalformedCoordinate(err.to_string())),
+        _ => Err(VerifyError::UnsupportedVersion(version)),
+    }
+}
+
+#[cfg(feature = "std")]
+/// Hash of the serialized position, this is the message that gets signed in version 1
+pub fn hash_position(position: &Position) -> Box<[u8]> {
+    let payload = serde_json::to_string(position).expect("JSON serialization");
+    hash_message(&payload)
+}
+
+#[cfg(feature = "std")]
+pub fn create_key_pair_from_bytes(secret_bytes: &[u8]) -> (SecretKey, PublicKey) {
+    let secp = Secp256k1::new();
+    let secret_key = SecretKey::from_slice(secret_bytes).expect("32 bytes");
+    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+    (secret_key, public_key)
+}
+
+#[cfg(feature = "std")]
+pub fn hash_message(message: &str) -> Box<[u8]> {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(message.as_bytes());
+    hasher.finalize().to_vec().into_boxed_slice()
+}
+
+#[cfg(feature = "std")]
+pub fn sign_hash_slice(secret_key: &SecretKey, hash: &[u8]) -> secp256k1::ecdsa::Signature {
+    let message = Message::from_digest_slice(hash).expect("32 bytes");
+    let secp = Secp256k1::new();
+    secp.sign_ecdsa(&message, secret_key)
+}
+
+#[cfg(feature = "std")]
+/// Check a signature over a 32 bytes hash, hashes of any other length never verify
+pub fn verify_signature(
+    public_key: &PublicKey,
+    sig: &secp256k1::ecdsa::Signature,
+    hash: &[u8],
+) -> bool {
+    let secp = Secp256k1::new();
+    match Message::from_digest_slice(hash) {
+        Ok(message) => secp.verify_ecdsa(&message, sig, public_key).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(feature = "std")]
+/// Verify every signature carried by a signed position, rejecting keys that sign twice
+pub fn verify_signed_position(signed_position: &SignedPosition) -> Result<(), VerifyError> {
+    if let Some(endorsement) = &signed_position.endorsement {
+        endorsement.check(signed_position)?;
+    }
+    let hash = signed_position.digest()?;
+    let mut seen = Vec::new();
+    for (scheme, public_key_str, signature_str) in signed_position.signatures() {
+        let public_key = VerifyingKey::parse(scheme, public_key_str)?;
+        if seen.contains(&public_key) {
+            return Err(VerifyError::DuplicateKey(public_key_str.to_string()));
+        }
+        if !public_key.verify(&hash, signature_str)? {
+            return Err(VerifyError::InvalidSignature {
+                public_key: public_key_str.to_string(),
+            });
+        }
+        seen.push(public_key);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+pub fn deser_pubkey(pubkey_str: &str) -> PublicKey {
+    PublicKey::from_slice(<[u8; 33]>::from_hex(pubkey_str).unwrap().as_ref()).expect("33 bytes")
+}
+
+#[cfg(feature = "std")]
+pub fn deser_signature(signature_str: &str) -> secp256k1::ecdsa::Signature {
+    secp256k1::ecdsa::Signature::from_compact(<[u8; 64]>::from_hex(signature_str).unwrap().as_ref())
+        .expect("64 bytes")
+}
+
+// nonces only need to be unpredictable enough to pair answers with requests
+#[cfg(feature = "std")]
+pub(crate) fn fresh_entropy(seed: &[u8]) -> [u8; 32] {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(seed);
+    hasher.update(now.to_be_bytes());
+    hasher.finalize().into()
+}
@@ -0,0 +1,277 @@
+//! Packed LoRaWAN uplinks for signed fixes
+//!
+//! A fix and its 64 bytes signature do not fit one 51 bytes payload, so every fix is sent as two
+//! frames sharing a 16 bits sequence number, the order they arrive in being irrelevant:
+//!
+//! ```text
+//! fix frame, 46 bytes                       signature frame, 35 bytes
+//! 0       header 0x10                       0       header 0x11
+//! 1..3    sequence number, big endian       1..3    sequence number, big endian
+//! 3..6    latitude                          3..35   signature bytes 32..64
+//! 6..9    longitude
+//! 9..13   seconds since the session epoch, big endian
+//! 13      flags, bit 0 set for a P-256 signature, other bits zero
+//! 14..46  signature bytes 0..32
+//! ```
+//!
+//! The high nibble of the header is the format version, the low one the frame type.
+//! Coordinates are unsigned 24 bits fixed point over their range, `(latitude + 90) / 180` and
+//! `(longitude + 180) / 360` scaled to 2^24 - 1, which is about 1.2 and 2.4 meters at the equator.
+//! The session epoch is agreed on when the device joins, like the device key.
+//!
+//! The device does not sign the packed bytes but the fix they decode to, as a version 2 record:
+//! the server rebuilds a `SignedPosition` that verifies like any other, and is kept as such.
+//!
+//! Frames of fixes that never complete are held until `MAX_PENDING` newer fixes are pending,
+//! then dropped.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::scheme::{KeyProvenance, Scheme, Signer, VerifyingKey};
+use crate::wire::{self, WireError};
+use crate::{sign_position, Position, SignedPosition, CURRENT_VERSION};
+
+pub const FORMAT_VERSION: u8 = 1;
+pub const FIX_FRAME_LENGTH: usize = 46;
+pub const SIGNATURE_FRAME_LENGTH: usize = 35;
+/// Incomplete fixes held by a `Reassembler`
+pub const MAX_PENDING: usize = 16;
+
+const FIX_FRAME: u8 = 0;
+const SIGNATURE_FRAME: u8 = 1;
+const FLAG_P256: u8 = 1;
+const COORDINATE_MAX: f64 = ((1 << 24) - 1) as f64;
+
+#[derive(Debug, PartialEq)]
+pub enum LoraError {
+    Wire(WireError),
+    /// The fix is before the session epoch, or more than 2^32 seconds after it
+    OutsideSession,
+    UnknownFormat(u8),
+    BadLength {
+        frame: u8,
+        length: usize,
+    },
+    /// Flags other than the scheme bit are set
+    UnknownFlags(u8),
+    /// The scheme of the flags is not the one of the session key
+    SchemeMismatch,
+}
+
+impl fmt::Display for LoraError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoraError::Wire(err) => write!(f, "{}", err),
+            LoraError::OutsideSession => write!(f, "timestamp is outside the session"),
+            LoraError::UnknownFormat(header) => write!(f, "unknown frame header {:#04x}", header),
+            LoraError::BadLength { frame, length } => {
+                write!(f, "bad length {} for frame type {}", length, frame)
+            }
+            LoraError::UnknownFlags(flags) => write!(f, "unknown flags {:#04x}", flags),
+            LoraError::SchemeMismatch => write!(f, "frame scheme does not match the session key"),
+        }
+    }
+}
+
+impl std::error::Error for LoraError {}
+
+impl From<WireError> for LoraError {
+    fn from(err: WireError) -> Self {
+        LoraError::Wire(err)
+    }
+}
+
+/// Fix and signature frames of a position, the position being rounded to what the frames carry
+pub fn pack(
+    position: &Position,
+    epoch: u64,
+    sequence: u16,
+    signer: &dyn Signer,
+) -> Result<[Vec<u8>; 2], LoraError> {
+    wire::encode(position)?;
+    let offset = position
+        .timestamp
+        .checked_sub(epoch)
+        .and_then(|offset| u32::try_from(offset).ok())
+        .ok_or(LoraError::OutsideSession)?;
+    let latitude = quantize(position.latitude, 90.0);
+    let longitude = quantize(position.longitude, 180.0);
+    let signed_position = sign_position(
+        unpacked_position(latitude, longitude, epoch + u64::from(offset)),
+        signer,
+    );
+    let signature = hex::decode(&signed_position.signature).expect("hex encoded signature");
+    let flags = match signer.scheme() {
+        Scheme::Secp256k1 => 0,
+        Scheme::P256 => FLAG_P256,
+    };
+
+    let mut fix_frame = Vec::with_capacity(FIX_FRAME_LENGTH);
+    fix_frame.push(header(FIX_FRAME));
+    fix_frame.extend_from_slice(&sequence.to_be_bytes());
+    fix_frame.extend_from_slice(&latitude.to_be_bytes()[1..]);
+    fix_frame.extend_from_slice(&longitude.to_be_bytes()[1..]);
+    fix_frame.extend_from_slice(&offset.to_be_bytes());
+    fix_frame.push(flags);
+    fix_frame.extend_from_slice(&signature[..32]);
+
+    let mut signature_frame = Vec::with_capacity(SIGNATURE_FRAME_LENGTH);
+    signature_frame.push(header(SIGNATURE_FRAME));
+    signature_frame.extend_from_slice(&sequence.to_be_bytes());
+    signature_frame.extend_from_slice(&signature[32..]);
+    Ok([fix_frame, signature_frame])
+}
+
+/// Server side of a device session, pairing frames back into signed positions
+pub struct Reassembler {
+    epoch: u64,
+    public_key: VerifyingKey,
+    /// Incomplete fixes, oldest first
+    pending: VecDeque<Pending>,
+    dropped: usize,
+}
+
+struct Pending {
+    sequence: u16,
+    fix: Option<[u8; FIX_FRAME_LENGTH]>,
+    signature: Option<[u8; SIGNATURE_FRAME_LENGTH]>,
+}
+
+impl Reassembler {
+    pub fn new(epoch: u64, public_key: VerifyingKey) -> Self {
+        Reassembler {
+            epoch,
+            public_key,
+            pending: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Take in a frame, returning the record once both frames of its fix arrived
+    ///
+    /// The record still has to be checked with `verify_signed_position`. A frame arriving again
+    /// replaces the pending one.
+    pub fn push(&mut self, frame: &[u8]) -> Result<Option<SignedPosition>, LoraError> {
+        let (&header, rest) = frame.split_first().ok_or(LoraError::BadLength {
+            frame: 0,
+            length: 0,
+        })?;
+        if header >> 4 != FORMAT_VERSION {
+            return Err(LoraError::UnknownFormat(header));
+        }
+        let kind = header & 0x0f;
+        let expected_length = match kind {
+            FIX_FRAME => FIX_FRAME_LENGTH,
+            SIGNATURE_FRAME => SIGNATURE_FRAME_LENGTH,
+            _ => return Err(LoraError::UnknownFormat(header)),
+        };
+        if frame.len() != expected_length {
+            return Err(LoraError::BadLength {
+                frame: kind,
+                length: frame.len(),
+            });
+        }
+        let sequence = u16::from_be_bytes([rest[0], rest[1]]);
+        if kind == FIX_FRAME {
+            let flags = frame[13];
+            let scheme = match flags {
+                0 => Scheme::Secp256k1,
+                FLAG_P256 => Scheme::P256,
+                _ => return Err(LoraError::UnknownFlags(flags)),
+            };
+            if scheme != self.public_key.scheme() {
+                return Err(LoraError::SchemeMismatch);
+            }
+        }
+
+        let index = match self
+            .pending
+            .iter()
+            .position(|pending| pending.sequence == sequence)
+        {
+            Some(index) => index,
+            None => {
+                if self.pending.len() == MAX_PENDING {
+                    self.pending.pop_front();
+                    self.dropped += 1;
+                }
+                self.pending.push_back(Pending {
+                    sequence,
+                    fix: None,
+                    signature: None,
+                });
+                self.pending.len() - 1
+            }
+        };
+        let pending = &mut self.pending[index];
+        match kind {
+            FIX_FRAME => pending.fix = frame.try_into().ok(),
+            _ => pending.signature = frame.try_into().ok(),
+        }
+        let (Some(fix), Some(signature)) = (pending.fix, pending.signature) else {
+            return Ok(None);
+        };
+        self.pending.remove(index);
+        Ok(Some(self.unpack(&fix, &signature)))
+    }
+
+    /// Number of incomplete fixes dropped so far
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    fn unpack(
+        &self,
+        fix: &[u8; FIX_FRAME_LENGTH],
+        signature: &[u8; SIGNATURE_FRAME_LENGTH],
+    ) -> SignedPosition {
+        let latitude = u32::from_be_bytes([0, fix[3], fix[4], fix[5]]);
+        let longitude = u32::from_be_bytes([0, fix[6], fix[7], fix[8]]);
+        let offset = u32::from_be_bytes([fix[9], fix[10], fix[11], fix[12]]);
+        let mut bytes = fix[14..].to_vec();
+        bytes.extend_from_slice(&signature[3..]);
+        SignedPosition {
+            version: CURRENT_VERSION,
+            position: unpacked_position(
+                latitude,
+                longitude,
+                self.epoch.saturating_add(u64::from(offset)),
+            ),
+            signature: hex::encode(bytes),
+            public_key: self.public_key.to_hex(),
+            scheme: self.public_key.scheme(),
+            co_signatures: Vec::new(),
+            timestamp_token: None,
+            provenance: KeyProvenance::Software,
+            key_use: None,
+            endorsement: None,
+        }
+    }
+}
+
+fn header(kind: u8) -> u8 {
+    FORMAT_VERSION << 4 | kind
+}
+
+/// Fixed point of a coordinate in [-bound, bound]
+fn quantize(value: f64, bound: f64) -> u32 {
+    ((value + bound) / (2.0 * bound) * COORDINATE_MAX).round() as u32
+}
+
+fn dequantize(value: u32, bound: f64) -> f64 {
+    value as f64 * (2.0 * bound) / COORDINATE_MAX - bound
+}
+
+fn unpacked_position(latitude: u32, longitude: u32, timestamp: u64) -> Position {
+    Position {
+        latitude: dequantize(latitude, 90.0),
+        longitude: dequantize(longitude, 180.0),
+        timestamp,
+        altitude: None,
+        prev_hash: None,
+        expires_at: None,
+        dwell_count: None,
+        last_seen: None,
+    }
+}
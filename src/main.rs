@@ -1,35 +1,1643 @@
-use hex::{FromHex, ToHex}; // to pretty print hash
-use serde::{Deserialize, Serialize};
-use serde_json;
-use std::time::SystemTime;
+use hex::FromHex;
+use std::cell::Cell;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process::exit;
+use std::time::Duration;
 
-use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
-use sha2::{self, Digest};
+use secp256k1::SecretKey;
+use sign_data_rust::audit::{self, AuditLog, AuditedSigner, Outcome};
+use sign_data_rust::burst;
+use sign_data_rust::chain;
+use sign_data_rust::clock::{Clock, MockClock, SystemClock};
+use sign_data_rust::compress;
+use sign_data_rust::continuity::{self, ContinuityPolicy};
+use sign_data_rust::cosign::{co_sign, verify_threshold};
+use sign_data_rust::csv;
+use sign_data_rust::dedup::{Dedup, DedupPolicy};
+use sign_data_rust::encoding::{self, Encoding};
+use sign_data_rust::freshness::{Freshness, FreshnessPolicy};
+use sign_data_rust::gpx;
+use sign_data_rust::kml::KmlWriter;
+use sign_data_rust::lora;
+use sign_data_rust::multiformats::MultiformatRecord;
+use sign_data_rust::nostr;
+use sign_data_rust::ots::{self, Attestation};
+use sign_data_rust::outbox::{self, Backoff, OutboxError, Overflow, Spool};
+use sign_data_rust::revocation::{Revocation, RevocationList};
+use sign_data_rust::scheme::{load_p256_key, Scheme, Signer, VerifyingKey};
+use sign_data_rust::session::{Endorsement, Session};
+use sign_data_rust::source::{
+    self, JsonLinesSource, PositionSource, RoutePlayback, SourceError, Speed,
+};
+use sign_data_rust::synthetic::{self, Route, TrackOptions};
+use sign_data_rust::takeout::{self, TakeoutFilter};
+use sign_data_rust::time;
+use sign_data_rust::track;
+use sign_data_rust::transport::HttpTransport;
+use sign_data_rust::usage::{UsageError, UsageState};
+use sign_data_rust::vectors;
+use sign_data_rust::wire;
+use sign_data_rust::{
+    deser_pubkey, deser_signature, position_digest, sign_coordinates, sign_position,
+    verify_signature, verify_signed_position, Position, SignedPosition, VerifyError,
+};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Position {
-    latitude: f64,
-    longitude: f64,
-    timestamp: u64,
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        None => {
+            demo();
+            Ok(())
+        }
+        Some("sign") => sign_command(&args[1..]),
+        Some("sign-batch") => sign_batch_command(&args[1..]),
+        Some("watch") => watch_command(&args[1..]),
+        Some("send") => send_command(&args[1..]),
+        Some("sync") => sync_command(&args[1..]),
+        Some("sos") => sos_command(&args[1..]),
+        Some("generate") => generate_command(&args[1..]),
+        Some("audit") => audit_command(&args[1..]),
+        Some("burst") => burst_command(&args[1..]),
+        Some("key") => key_command(&args[1..]),
+        Some("verify") => verify_command(&args[1..]),
+        Some("verify-track") => verify_track_command(&args[1..]),
+        Some("lora") => lora_command(&args[1..]),
+        Some("nostr") => nostr_command(&args[1..]),
+        Some("ots") => ots_command(&args[1..]),
+        Some("revoke") => revoke_command(&args[1..]),
+        Some("track") => track_command(&args[1..]),
+        Some("vectors") => vectors_command(&args[1..]),
+        Some(other) => Err(format!("unknown command: {}", other)),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct SignedPosition {
-    position: Position,
-    signature: String,
-    public_key: String,
+/// `sign <latitude> <longitude> <private key> [--additional-key <private key>]...
+/// [--chain <state file>] [--now <unix seconds>] [--multiformats] [--audit-log <file>
+/// [--client <name>]] [--key-state <file> [--force] [--include-key-use]] [--valid-for <duration>]`
+///
+/// Keys are hex encoded secp256k1 keys, or `p256:<file>` for a SEC1 / PKCS#8 P-256 key file.
+/// With `--chain`, the position is chained to the head kept in the state file, see `chain`.
+/// `--now` stamps the position with a given time instead of the system time, e.g. for replays.
+/// `--multiformats` prints the record in the multiformats profile, see `multiformats`.
+/// `--audit-log` records every signature, or refusal to sign, in a log, see `audit`, made on
+/// behalf of `--client`, `cli:<user>` by default. `--key-state` counts the signatures of every
+/// key in a state file, see `usage`, refusing to sign past the limit of a key unless `--force`
+/// is given, and `--include-key-use` writes the count of the device key in the record.
+/// `--valid-for`, e.g. `600` or `10m`, signs an expiry that long after the position timestamp.
+fn sign_command(args: &[String]) -> Result<(), String> {
+    let (latitude, longitude, key) = match args {
+        [latitude, longitude, key, ..] => (latitude, longitude, key),
+        _ => return Err("usage: sign <latitude> <longitude> <private key hex>".to_string()),
+    };
+    let clock = parse_clock(&args[3..])?;
+    let timestamp = clock.now_unix_secs();
+    let position = Position {
+        latitude: latitude.parse().map_err(|_| "invalid latitude")?,
+        longitude: longitude.parse().map_err(|_| "invalid longitude")?,
+        timestamp,
+        altitude: None,
+        prev_hash: None,
+        expires_at: expiry(&args[3..], timestamp)?,
+        dwell_count: None,
+        last_seen: None,
+    };
+    let audit = open_audit_log(&args[3..])?;
+    let mut counter = open_key_counter(&args[3..])?;
+    let device = parse_signer(key)?;
+    let signer = audited(device.as_ref(), &audit);
+    if let Err(err) = wire::encode(&position) {
+        audit_failure(signer.as_ref(), &audit, &err)?;
+        return Err(err.to_string());
+    }
+    let key_use = count_signature(counter.as_mut(), signer.as_ref(), &audit)?;
+
+    let chain_state = flag_values(&args[3..], "--chain")
+        .first()
+        .copied()
+        .map(Path::new);
+    let mut head = match chain_state {
+        Some(path) => Some(chain::load_head(path).map_err(|err| err.to_string())?),
+        None => None,
+    };
+    let mut signed_position = match head.as_mut() {
+        Some(head) => chain::sign_linked(position, head, signer.as_ref()),
+        None => sign_position(position, signer.as_ref()),
+    };
+    signed_position.key_use = key_use;
+    for additional_key in flag_values(&args[3..], "--additional-key") {
+        let additional_signer = parse_signer(additional_key)?;
+        let additional_signer = audited(additional_signer.as_ref(), &audit);
+        count_signature(counter.as_mut(), additional_signer.as_ref(), &audit)?;
+        if let Err(err) = co_sign(&mut signed_position, additional_signer.as_ref()) {
+            audit_failure(additional_signer.as_ref(), &audit, &err)?;
+            return Err(err.to_string());
+        }
+    }
+    println!(
+        "{}",
+        record_json(
+            &signed_position,
+            args.iter().any(|arg| arg == "--multiformats")
+        )?
+    );
+    if let (Some(path), Some(head)) = (chain_state, head) {
+        chain::save_head(path, &head).map_err(|err| err.to_string())?;
+    }
+    flush_audit_log(&audit)
 }
 
-fn main() {
-    // build SignedPosition object, to be sent
-    let signed_position = sign_coordinates(
-        37.7749,
-        -122.4194,
-        SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
+/// `sign-batch <gpx or takeout file> <private key> [--additional-key <private key>]...
+/// [--chain <state file>] [--now <unix seconds>] [--multiformats] [--audit-log <file>
+/// [--client <name>]] [--key-state <file> [--force] [--include-key-use]] [--valid-for <duration>]
+/// [--since <time>] [--until <time>] [--min-accuracy <meters>]`
+///
+/// Signs every point of the file, printing one signed position per line. Points without a time
+/// are stamped with the current time, or the time given by `--now`. `--valid-for` signs an
+/// expiry that long after the time of every point. A `.json` file is a Google Takeout location
+/// history, read as a stream, see `takeout`: `--since` and `--until`, in unix seconds or
+/// ISO 8601, keep the entries between those times included, and `--min-accuracy` those at least
+/// that accurate.
+fn sign_batch_command(args: &[String]) -> Result<(), String> {
+    let (input, key) = match args {
+        [input, key, ..] => (input, key),
+        _ => return Err("usage: sign-batch <gpx file> <private key hex>".to_string()),
+    };
+
+    let audit = open_audit_log(&args[2..])?;
+    let mut counter = open_key_counter(&args[2..])?;
+    let device = parse_signer(key)?;
+    let signer = audited(device.as_ref(), &audit);
+    let additional_devices = flag_values(&args[2..], "--additional-key")
+        .into_iter()
+        .map(parse_signer)
+        .collect::<Result<Vec<_>, _>>()?;
+    let additional_signers: Vec<_> = additional_devices
+        .iter()
+        .map(|device| audited(device.as_ref(), &audit))
+        .collect();
+    let chain_state = flag_values(&args[2..], "--chain")
+        .first()
+        .copied()
+        .map(Path::new);
+    let mut head = match chain_state {
+        Some(path) => Some(chain::load_head(path).map_err(|err| err.to_string())?),
+        None => None,
+    };
+    let default_time = parse_clock(&args[2..])?.now_unix_secs();
+    let multiformats = args.iter().any(|arg| arg == "--multiformats");
+    let mut sign_point = |index: usize, mut position: Position| -> Result<(), String> {
+        position.expires_at = expiry(&args[2..], position.timestamp)?;
+        if let Err(err) = wire::encode(&position) {
+            audit_failure(signer.as_ref(), &audit, &err)?;
+            return Err(format!("point {}: {}", index, err));
+        }
+        let key_use = count_signature(counter.as_mut(), signer.as_ref(), &audit)
+            .map_err(|err| format!("point {}: {}", index, err))?;
+        let mut signed_position = match head.as_mut() {
+            Some(head) => chain::sign_linked(position, head, signer.as_ref()),
+            None => sign_position(position, signer.as_ref()),
+        };
+        signed_position.key_use = key_use;
+        for additional_signer in &additional_signers {
+            count_signature(counter.as_mut(), additional_signer.as_ref(), &audit)
+                .map_err(|err| format!("point {}: {}", index, err))?;
+            if let Err(err) = co_sign(&mut signed_position, additional_signer.as_ref()) {
+                audit_failure(additional_signer.as_ref(), &audit, &err)?;
+                return Err(err.to_string());
+            }
+        }
+        println!("{}", record_json(&signed_position, multiformats)?);
+        Ok(())
+    };
+    let result = if input.ends_with(".json") {
+        let filter = takeout_filter(&args[2..])?;
+        let file = std::fs::File::open(input).map_err(|err| format!("{}: {}", input, err))?;
+        let mut index = 0;
+        takeout::read_takeout(file, &filter, |position| {
+            sign_point(index, position).map_err(std::io::Error::other)?;
+            index += 1;
+            Ok(())
+        })
+        .map(|stats| {
+            eprintln!(
+                "Signed {} entries, {} filtered out, {} unreadable",
+                stats.kept, stats.filtered, stats.malformed
+            )
+        })
+        .map_err(|err| format!("{}: {}", input, err))
+    } else {
+        let document =
+            std::fs::read_to_string(input).map_err(|err| format!("{}: {}", input, err))?;
+        let points = gpx::read_gpx(&document).map_err(|err| err.to_string())?;
+        points
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, point)| sign_point(index, point.to_position(default_time)))
+    };
+    // records signed before an error were printed, keep the head they moved
+    if let (Some(path), Some(head)) = (chain_state, head) {
+        chain::save_head(path, &head).map_err(|err| err.to_string())?;
+    }
+    result?;
+    flush_audit_log(&audit)
+}
+
+/// Filter of `--since`, `--until` and `--min-accuracy`
+fn takeout_filter(args: &[String]) -> Result<TakeoutFilter, String> {
+    let time = |flag| match flag_values(args, flag).first() {
+        Some(time) => time
+            .parse()
+            .ok()
+            .or_else(|| time::parse_iso8601(time))
+            .map(Some)
+            .ok_or_else(|| format!("invalid {} {}", flag, time)),
+        None => Ok(None),
+    };
+    let min_accuracy = match flag_values(args, "--min-accuracy").first() {
+        Some(meters) => Some(
+            meters
+                .parse()
+                .map_err(|_| format!("invalid --min-accuracy {}", meters))?,
+        ),
+        None => None,
+    };
+    Ok(TakeoutFilter {
+        since: time("--since")?,
+        until: time("--until")?,
+        min_accuracy,
+    })
+}
+
+/// `watch <private key> [--source stdin|gpsd[:<host:port>]|playback:<route file>] [--speed <factor>|max]
+/// [--chain <state file>] [--now <unix seconds>] [--audit-log <file> [--client <name>]]
+/// [--key-state <file> [--force] [--include-key-use]]
+/// [--outbox <spool file> [--outbox-limit <n> [--drop-oldest]]]
+/// [--session <lifetime> [--device-id <id>]]
+/// [--dedup <meters> [--dedup-window <duration>] [--dedup-speed <m/s>] [--dedup-turn <degrees>]]`
+///
+/// Signs fixes as the source hands them out, printing one signed position per line. The default
+/// source reads JSON positions from stdin, `gpsd` the fixes of a gpsd daemon, on localhost by
+/// default, `playback:` replays a GPX or CSV route at `--speed` times real time (1 by default),
+/// or as fast as possible with `max`. Route points without a time are interpolated, starting
+/// from the current time, or `--now`, if none has one. Lines of stdin or gpsd that are not
+/// fixes are skipped and counted on stderr, and watching fails if reading them fails.
+/// `--outbox` also appends every record to a spool for `send`, see `outbox`. A spool holding
+/// `--outbox-limit` unsent records refuses new ones, or drops the oldest with `--drop-oldest`.
+/// `--session` signs with a fresh session key instead, endorsed by the device key for the
+/// lifetime, e.g. 8h, from the current time, see `session`. The device key is dropped once it
+/// signed the endorsement, which is the only signature counted by `--key-state`, and watching
+/// ends when a fix falls outside the session. `--device-id` defaults to the device public key.
+/// `--dedup` skips fixes within that many meters and `--dedup-window` of the last record, 60s
+/// by default, unless the speed changed by more than `--dedup-speed`, 2 by default, or the
+/// heading by more than `--dedup-turn`, 30 by default, see `dedup`.
+fn watch_command(args: &[String]) -> Result<(), String> {
+    let key = args
+        .first()
+        .ok_or("usage: watch <private key hex> [--source <source>]")?;
+    let audit = open_audit_log(&args[1..])?;
+    let mut counter = open_key_counter(&args[1..])?;
+    let device = parse_signer(key)?;
+    let clock = parse_clock(&args[1..])?;
+    let (device, endorsement): (Box<dyn Signer>, _) =
+        match flag_values(&args[1..], "--session").first() {
+            Some(lifetime) => {
+                let lifetime = time::parse_duration(lifetime)
+                    .ok_or_else(|| format!("invalid --session {}, expected e.g. 8h", lifetime))?;
+                let endorser = audited(device.as_ref(), &audit);
+                count_signature(counter.take().as_mut(), endorser.as_ref(), &audit)?;
+                let device_id = match flag_values(&args[1..], "--device-id").first() {
+                    Some(device_id) => device_id.to_string(),
+                    None => endorser.public_key(),
+                };
+                let not_before = clock.now_unix_secs();
+                let session = Session::start(
+                    endorser.as_ref(),
+                    &device_id,
+                    not_before,
+                    not_before.saturating_add(lifetime.as_secs()),
+                )
+                .map_err(|err| err.to_string())?;
+                drop(endorser);
+                drop(device);
+                let endorsement = session.endorsement().clone();
+                (Box::new(session), Some(endorsement))
+            }
+            None => (device, None),
+        };
+    let signer = audited(device.as_ref(), &audit);
+    let speed = match flag_values(&args[1..], "--speed").first() {
+        Some(speed) => speed.parse::<Speed>()?,
+        None => Speed::Factor(1.0),
+    };
+    let mut lines: Option<JsonLinesSource<Box<dyn BufRead>>> = None;
+    let mut route = None;
+    let source: &mut dyn PositionSource = match flag_values(&args[1..], "--source").first() {
+        None | Some(&"stdin") => {
+            lines.insert(JsonLinesSource::new(Box::new(std::io::stdin().lock())))
+        }
+        Some(source) if *source == "gpsd" || source.starts_with("gpsd:") => {
+            let address = match source.strip_prefix("gpsd:") {
+                Some(address) => address.to_string(),
+                None => format!("localhost:{}", source::GPSD_PORT),
+            };
+            let reader = source::connect_gpsd(&address)
+                .map_err(|err| format!("gpsd at {}: {}", address, err))?;
+            lines.insert(JsonLinesSource::gpsd(Box::new(reader)))
+        }
+        Some(source) => match source.strip_prefix("playback:") {
+            Some(path) => route.insert(
+                RoutePlayback::open(Path::new(path), speed, clock.as_ref())
+                    .map_err(|err| format!("{}: {}", path, err))?,
+            ),
+            None => return Err(format!("unknown source {}", source)),
+        },
+    };
+    let mut dedup = match flag_values(&args[1..], "--dedup").is_empty() {
+        true => None,
+        false => Some(Dedup::new(source, dedup_policy(&args[1..])?)),
+    };
+    let source: &mut dyn PositionSource = match dedup.as_mut() {
+        Some(dedup) => dedup,
+        None => source,
+    };
+
+    let chain_state = flag_values(&args[1..], "--chain")
+        .first()
+        .copied()
+        .map(Path::new);
+    let mut head = match chain_state {
+        Some(path) => Some(chain::load_head(path).map_err(|err| err.to_string())?),
+        None => None,
+    };
+    let key_use = Cell::new(None);
+    let mut exhausted = None;
+    let mut ended = None;
+    let mut source = SessionSource {
+        source,
+        endorsement: endorsement.as_ref(),
+        ended: &mut ended,
+    };
+    let mut source = CountedSource {
+        source: &mut source,
+        counter: counter.as_mut(),
+        public_key: signer.public_key(),
+        key_use: &key_use,
+        exhausted: &mut exhausted,
+    };
+    let mut spool = match flag_values(&args[1..], "--outbox").first() {
+        Some(path) => {
+            let limit = match flag_values(&args[1..], "--outbox-limit").first() {
+                Some(limit) => Some(limit.parse().map_err(|_| "invalid --outbox-limit")?),
+                None => None,
+            };
+            let overflow = match args.iter().any(|arg| arg == "--drop-oldest") {
+                true => Overflow::DropOldest,
+                false => Overflow::RejectNewest,
+            };
+            Some(
+                Spool::open(Path::new(path), limit, overflow)
+                    .map_err(|err| format!("{}: {}", path, err))?,
+            )
+        }
+        None => None,
+    };
+    let emit = |signed_position: &SignedPosition, head: Option<&[u8; 32]>| {
+        let line = match (key_use.get(), &endorsement) {
+            (None, None) => serde_json::to_string(signed_position)?,
+            (count, endorsement) => {
+                let mut signed_position = signed_position.clone();
+                signed_position.key_use = count;
+                signed_position.endorsement = endorsement.clone();
+                serde_json::to_string(&signed_position)?
+            }
+        };
+        // spool the record before anything else, so that it is not lost if the process dies
+        if let Some(spool) = spool.as_mut() {
+            let dropped = spool.dropped();
+            match spool.push(&line) {
+                Ok(()) if spool.dropped() > dropped => {
+                    eprintln!("warning: spool is full, dropped the oldest unsent record")
+                }
+                Ok(()) => {}
+                Err(err @ OutboxError::Full { .. }) => {
+                    eprintln!("warning: record not spooled, {}", err)
+                }
+                Err(err) => return Err(std::io::Error::other(err.to_string())),
+            }
+        }
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", line)?;
+        stdout.flush()?;
+        // save the head with every record, so that the chain survives an interruption
+        if let (Some(path), Some(head)) = (chain_state, head) {
+            chain::save_head(path, head).map_err(std::io::Error::other)?;
+        }
+        Ok(())
+    };
+    let result = source::sign_stream(&mut source, signer.as_ref(), head.as_mut(), emit);
+    if let Err(err @ SourceError::Fix { .. }) = &result {
+        audit_failure(signer.as_ref(), &audit, err)?;
+    }
+    result.map_err(|err| err.to_string())?;
+    if let Some(err) = exhausted {
+        audit_failure(signer.as_ref(), &audit, &err)?;
+        return Err(err.to_string());
+    }
+    flush_audit_log(&audit)?;
+    if let Some(lines) = lines.as_mut() {
+        if lines.skipped() > 0 {
+            eprintln!("{} lines skipped, not fixes", lines.skipped());
+        }
+        if let Some(err) = lines.take_error() {
+            return Err(format!("reading fixes: {}", err));
+        }
+    }
+    match ended {
+        Some(err) => Err(format!("{}, start watching again for a new session", err)),
+        None => Ok(()),
+    }
+}
+
+/// Thresholds of `--dedup` and the flags refining it
+fn dedup_policy(args: &[String]) -> Result<DedupPolicy, String> {
+    let default = DedupPolicy::default();
+    let number = |flag: &str, default: f64| match flag_values(args, flag).first() {
+        Some(value) => value
+            .parse::<f64>()
+            .ok()
+            .filter(|value| *value >= 0.0)
+            .ok_or_else(|| format!("invalid {} {}", flag, value)),
+        None => Ok(default),
+    };
+    let max_gap_secs = match flag_values(args, "--dedup-window").first() {
+        Some(window) => time::parse_duration(window)
+            .ok_or_else(|| format!("invalid --dedup-window {}, expected e.g. 60s", window))?
             .as_secs(),
+        None => default.max_gap_secs,
+    };
+    Ok(DedupPolicy {
+        radius_m: number("--dedup", default.radius_m)?,
+        max_gap_secs,
+        max_speed_change_mps: number("--dedup-speed", default.max_speed_change_mps)?,
+        max_turn_deg: number("--dedup-turn", default.max_turn_deg)?,
+    })
+}
+
+/// Source ending at the first fix outside the session, which its key cannot sign
+struct SessionSource<'a> {
+    source: &'a mut dyn PositionSource,
+    endorsement: Option<&'a Endorsement>,
+    ended: &'a mut Option<VerifyError>,
+}
+
+impl PositionSource for SessionSource<'_> {
+    fn next_fix(&mut self) -> Option<Position> {
+        if self.ended.is_some() {
+            return None;
+        }
+        let fix = self.source.next_fix()?;
+        match self.endorsement {
+            Some(endorsement)
+                if fix.timestamp < endorsement.not_before
+                    || fix.timestamp > endorsement.not_after =>
+            {
+                *self.ended = Some(VerifyError::OutsideSession {
+                    timestamp: fix.timestamp,
+                    not_before: endorsement.not_before,
+                    not_after: endorsement.not_after,
+                });
+                None
+            }
+            _ => Some(fix),
+        }
+    }
+}
+
+/// Source counting a signature of the device key for every fix it hands out, and ending once
+/// the key is exhausted
+struct CountedSource<'a> {
+    source: &'a mut dyn PositionSource,
+    counter: Option<&'a mut KeyCounter>,
+    public_key: String,
+    /// Count of the last fix handed out, when included in records
+    key_use: &'a Cell<Option<u64>>,
+    exhausted: &'a mut Option<UsageError>,
+}
+
+impl PositionSource for CountedSource<'_> {
+    fn next_fix(&mut self) -> Option<Position> {
+        if self.exhausted.is_some() {
+            return None;
+        }
+        let fix = self.source.next_fix()?;
+        let Some(counter) = self.counter.as_mut() else {
+            return Some(fix);
+        };
+        match counter.state.reserve(&self.public_key, counter.force) {
+            Ok(count) => {
+                self.key_use.set(counter.include.then_some(count));
+                Some(fix)
+            }
+            Err(err) => {
+                *self.exhausted = Some(err);
+                None
+            }
+        }
+    }
+}
+
+/// `send <spool file> <http url> [--follow] [--attempts <n>]`
+///
+/// Posts the records spooled by `watch --outbox` to a server, in order, marking each one sent
+/// once the server answered with a 2xx status, see `outbox`. Failed deliveries are retried with
+/// exponential backoff, giving up after `--attempts` failures in a row, 5 by default. With
+/// `--follow`, keeps sending records as they are spooled and never gives up.
+fn send_command(args: &[String]) -> Result<(), String> {
+    let (path, url) = match args {
+        [path, url, ..] => (Path::new(path), url),
+        _ => return Err("usage: send <spool file> <http url> [--follow]".to_string()),
+    };
+    let follow = args.iter().any(|arg| arg == "--follow");
+    let delivered = with_retries(&args[2..], follow, || {
+        outbox::deliver(path, &HttpTransport, url)
+    })?;
+    println!("Delivered {} records", delivered);
+    Ok(())
+}
+
+/// `sync <spool file> <http url> [--batch <n>] [--attempts <n>]`
+///
+/// Uploads the records spooled by `watch --outbox` as track containers of up to `--batch`
+/// records, 1000 by default, marking them sent once the server answered with a 2xx status, see
+/// `outbox`. Containers carry no proof. Failed uploads are retried as for `send`, and running
+/// `sync` again resumes with the first container that was not accepted.
+fn sync_command(args: &[String]) -> Result<(), String> {
+    let (path, url) = match args {
+        [path, url, ..] => (Path::new(path), url),
+        _ => return Err("usage: sync <spool file> <http url> [--batch <n>]".to_string()),
+    };
+    let batch = match flag_values(&args[2..], "--batch").first() {
+        Some(batch) => batch.parse().map_err(|_| "invalid --batch")?,
+        None => 1000,
+    };
+    let synced = with_retries(&args[2..], false, || {
+        outbox::sync(path, &HttpTransport, url, batch)
+    })?;
+    println!("Synced {} records", synced);
+    Ok(())
+}
+
+/// `sos <latitude> <longitude> <private key> --sink <http url>... [--outbox <spool file>]
+/// [--now <unix seconds>] [--audit-log <file> [--client <name>]]
+/// [--key-state <file> [--force] [--include-key-use]]`
+///
+/// Signs the position and posts it at once to every sink in parallel, then prints it. Sinks
+/// are tried once only, and when one of them did not accept the record it is spooled in
+/// `--outbox` for `send`, or the command fails without one. `--audit-log` and `--key-state`
+/// work as for `sign`, the signature being counted before it is made, and the audit log being
+/// flushed once the record was posted.
+fn sos_command(args: &[String]) -> Result<(), String> {
+    let usage = "usage: sos <latitude> <longitude> <private key hex> --sink <http url>...";
+    let (latitude, longitude, key) = match args {
+        [latitude, longitude, key, ..] => (latitude, longitude, key),
+        _ => return Err(usage.to_string()),
+    };
+    let sinks = flag_values(&args[3..], "--sink");
+    if sinks.is_empty() {
+        return Err(usage.to_string());
+    }
+    let position = Position {
+        latitude: latitude.parse().map_err(|_| "invalid latitude")?,
+        longitude: longitude.parse().map_err(|_| "invalid longitude")?,
+        timestamp: parse_clock(&args[3..])?.now_unix_secs(),
+        altitude: None,
+        prev_hash: None,
+        expires_at: None,
+        dwell_count: None,
+        last_seen: None,
+    };
+    let audit = open_audit_log(&args[3..])?;
+    let mut counter = open_key_counter(&args[3..])?;
+    let device = parse_signer(key)?;
+    let signer = audited(device.as_ref(), &audit);
+    let key_use = count_signature(counter.as_mut(), signer.as_ref(), &audit)?;
+    let mut signed_position = sign_position(position, signer.as_ref());
+    signed_position.key_use = key_use;
+    let record = serde_json::to_string(&signed_position).map_err(|err| err.to_string())?;
+    let failed = outbox::broadcast(&record, &HttpTransport, &sinks);
+    println!("{}", record);
+    for (sink, err) in &failed {
+        eprintln!("warning: {}: {}", sink, err);
+    }
+    flush_audit_log(&audit)?;
+    if failed.is_empty() {
+        return Ok(());
+    }
+    match flag_values(&args[3..], "--outbox").first() {
+        Some(path) => {
+            Spool::open(Path::new(path), None, Overflow::RejectNewest)
+                .and_then(|mut spool| spool.push(&record))
+                .map_err(|err| format!("{}: {}", path, err))?;
+            eprintln!("Spooled the record in {}", path);
+            Ok(())
+        }
+        None => Err(format!(
+            "{} of {} sinks did not accept the record",
+            failed.len(),
+            sinks.len()
+        )),
+    }
+}
+
+/// Run a delivery until it succeeds, backing off after failures, returning the records delivered
+///
+/// Gives up after `--attempts` failures in a row, unless `follow`, which also runs the delivery
+/// again every second once it succeeded. A failure that retrying cannot fix, e.g. a 4xx status
+/// telling the record is refused, is returned at once, `follow` or not, the record staying
+/// spooled until the server or the spool is fixed.
+fn with_retries(
+    args: &[String],
+    follow: bool,
+    mut deliver: impl FnMut() -> Result<usize, OutboxError>,
+) -> Result<usize, String> {
+    let attempts: u32 = match flag_values(args, "--attempts").first() {
+        Some(attempts) => attempts.parse().map_err(|_| "invalid --attempts")?,
+        None => 5,
+    };
+    let seed = SystemClock.now_unix_ms() ^ u64::from(std::process::id());
+    let mut backoff = Backoff::new(Duration::from_millis(500), Duration::from_secs(60), seed);
+    let mut delivered = 0;
+    loop {
+        match deliver() {
+            Ok(sent) => {
+                delivered += sent;
+                backoff.reset();
+                if !follow {
+                    return Ok(delivered);
+                }
+                std::thread::sleep(Duration::from_secs(1));
+            }
+            Err(OutboxError::Delivery { sent, err }) if err.is_permanent() => {
+                return Err(format!(
+                    "{} records delivered, not retrying: {}",
+                    delivered + sent,
+                    err
+                ));
+            }
+            Err(OutboxError::Delivery { sent, err }) => {
+                delivered += sent;
+                if sent > 0 {
+                    backoff.reset();
+                }
+                if !follow && backoff.failures() + 1 >= attempts {
+                    return Err(format!(
+                        "{} records delivered, giving up after {} attempts: {}",
+                        delivered, attempts, err
+                    ));
+                }
+                let delay = backoff.next_delay();
+                eprintln!(
+                    "delivery failed, retrying in {:.1}s: {}",
+                    delay.as_secs_f64(),
+                    err
+                );
+                std::thread::sleep(delay);
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+}
+
+/// `generate <count> [--start <lat>,<lon>] [--to <lat>,<lon>] [--speed <m/s>]
+/// [--interval <seconds>] [--noise <meters>] [--dropout <probability>] [--seed <n>] [--sign]
+/// [--format jsonl|gpx|csv] [--now <unix seconds>]`
+///
+/// Prints a synthetic track of `count` positions, a random walk from `--start` at `--speed`, or
+/// the great circle from `--start` to `--to`. The same seed always gives the same track, and
+/// `--now`, the time of the first fix, makes the timestamps reproducible too. `--sign` signs the
+/// positions with a key drawn from the seed, only as JSON lines.
+fn generate_command(args: &[String]) -> Result<(), String> {
+    let count = args
+        .first()
+        .and_then(|count| count.parse::<usize>().ok())
+        .ok_or("usage: generate <count> [--to <lat>,<lon>]")?;
+    let flags = &args[1..];
+    let point = |flag: &str| -> Result<Option<(f64, f64)>, String> {
+        match flag_values(flags, flag).first() {
+            Some(value) => value
+                .split_once(',')
+                .and_then(|(latitude, longitude)| {
+                    Some((
+                        latitude.trim().parse().ok()?,
+                        longitude.trim().parse().ok()?,
+                    ))
+                })
+                .map(Some)
+                .ok_or_else(|| format!("invalid {}, expected <latitude>,<longitude>", flag)),
+            None => Ok(None),
+        }
+    };
+    let number = |flag: &str| -> Result<Option<f64>, String> {
+        match flag_values(flags, flag).first() {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid {}", flag)),
+            None => Ok(None),
+        }
+    };
+    let integer = |flag: &str| -> Result<Option<u64>, String> {
+        match flag_values(flags, flag).first() {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid {}", flag)),
+            None => Ok(None),
+        }
+    };
+
+    let defaults = TrackOptions::default();
+    let start = point("--start")?.unwrap_or((48.8566, 2.3522));
+    let route = match point("--to")? {
+        Some(to) => Route::GreatCircle { from: start, to },
+        None => Route::RandomWalk {
+            latitude: start.0,
+            longitude: start.1,
+            speed_mps: number("--speed")?.unwrap_or(10.0),
+        },
+    };
+    let options = TrackOptions {
+        count,
+        route,
+        interval: integer("--interval")?.unwrap_or(defaults.interval),
+        noise_m: number("--noise")?.unwrap_or(defaults.noise_m),
+        dropout: number("--dropout")?.unwrap_or(defaults.dropout),
+        seed: integer("--seed")?.unwrap_or(defaults.seed),
+        start_time: parse_clock(flags)?.now_unix_secs(),
+    };
+    let positions = synthetic::generate(&options).map_err(|err| err.to_string())?;
+
+    let sign = flags.iter().any(|arg| arg == "--sign");
+    let format = flag_values(flags, "--format").first().copied();
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    let references: Vec<&Position> = positions.iter().collect();
+    match format {
+        None | Some("jsonl") if sign => {
+            let key = synthetic::throwaway_key(options.seed);
+            for position in positions {
+                serde_json::to_writer(&mut out, &sign_position(position, &key))
+                    .map_err(|err| err.to_string())?;
+                writeln!(out).map_err(|err| err.to_string())?;
+            }
+        }
+        None | Some("jsonl") => {
+            for position in &positions {
+                serde_json::to_writer(&mut out, position).map_err(|err| err.to_string())?;
+                writeln!(out).map_err(|err| err.to_string())?;
+            }
+        }
+        Some(_) if sign => return Err("signed positions are only written as jsonl".to_string()),
+        Some("gpx") => {
+            gpx::write_gpx_positions(&mut out, &references).map_err(|err| err.to_string())?
+        }
+        Some("csv") => csv::write_csv(&mut out, &references).map_err(|err| err.to_string())?,
+        Some(other) => return Err(format!("unknown format {}", other)),
+    }
+    out.flush().map_err(|err| err.to_string())
+}
+
+/// `key status <key state file>`, `key limit <key state file> <public key hex> <n>|none`
+///
+/// `status` prints the signatures counted for every key of a state file, see `usage`, and how
+/// many are left before its limit. `limit` sets the limit of a key, or lifts it with `none`.
+fn key_command(args: &[String]) -> Result<(), String> {
+    let load =
+        |path: &str| UsageState::load(Path::new(path)).map_err(|err| format!("{}: {}", path, err));
+    match args {
+        [command, path, ..] if command == "status" => {
+            let state = load(path)?;
+            for (public_key, usage) in state.keys() {
+                match (usage.limit, usage.remaining()) {
+                    (Some(limit), Some(0)) => println!(
+                        "{}: {} of {} signatures, exhausted",
+                        public_key, usage.count, limit
+                    ),
+                    (Some(limit), Some(remaining)) => println!(
+                        "{}: {} of {} signatures, {} left",
+                        public_key, usage.count, limit, remaining
+                    ),
+                    _ => println!("{}: {} signatures, no limit", public_key, usage.count),
+                }
+            }
+            Ok(())
+        }
+        [command, path, public_key, limit, ..] if command == "limit" => {
+            let limit = match limit.as_str() {
+                "none" => None,
+                limit => Some(limit.parse::<u64>().map_err(|_| "invalid limit")?),
+            };
+            let public_key = parse_public_key(public_key)?;
+            load(path)?
+                .set_limit(&public_key.to_hex(), limit)
+                .map_err(|err| format!("{}: {}", path, err))
+        }
+        _ => Err(
+            "usage: key status <key state file>, or key limit <key state file> <public key hex> <n>|none"
+                .to_string(),
+        ),
+    }
+}
+
+/// `audit verify <audit log> [--head <hex>]`
+///
+/// Walks the hash chain of an audit log, see `audit`, and prints its head. With `--head`, the
+/// log must also end with that line, which catches an edit of the last line.
+fn audit_command(args: &[String]) -> Result<(), String> {
+    let path = match args {
+        [command, path, ..] if command == "verify" => path,
+        _ => return Err("usage: audit verify <audit log> [--head <hex>]".to_string()),
+    };
+    let head = match flag_values(&args[2..], "--head").first() {
+        Some(head) => Some(audit::parse_head(head).ok_or("invalid head, expected 32 hex bytes")?),
+        None => None,
+    };
+    let head = audit::verify_log(Path::new(path), head.as_ref())
+        .map_err(|err| format!("{}: {}", path, err))?;
+    println!("Audit log intact, head {}", hex::encode(head));
+    Ok(())
+}
+
+/// `burst sign <private key> --inputs <positions file>`, `burst verify <burst file>`
+///
+/// `sign` signs the JSON positions of a file, one per line, with a single signature over their
+/// combined digest, see `burst`, and prints the burst. Positions keep the order of the file.
+/// `verify` recomputes the digest of the positions published in a burst and checks it.
+fn burst_command(args: &[String]) -> Result<(), String> {
+    let usage = "usage: burst sign <private key hex> --inputs <positions file> \
+                 or burst verify <burst file>";
+    match args.first().map(String::as_str) {
+        Some("sign") => {
+            let key = args
+                .get(1)
+                .filter(|key| !key.starts_with("--"))
+                .ok_or(usage)?;
+            let input = flag_values(&args[2..], "--inputs")
+                .first()
+                .copied()
+                .ok_or(usage)?;
+            let content =
+                std::fs::read_to_string(input).map_err(|err| format!("{}: {}", input, err))?;
+            let positions = content
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(index, line)| {
+                    serde_json::from_str(line).map_err(|err| format!("line {}: {}", index + 1, err))
+                })
+                .collect::<Result<Vec<Position>, _>>()?;
+            let signer = parse_signer(key)?;
+            let signed_burst =
+                burst::sign_burst(positions, signer.as_ref()).map_err(|err| err.to_string())?;
+            println!(
+                "{}",
+                serde_json::to_string(&signed_burst).expect("JSON serialization")
+            );
+            Ok(())
+        }
+        Some("verify") => {
+            let path = args.get(1).ok_or(usage)?;
+            let content =
+                std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            let signed_burst: burst::SignedBurst = serde_json::from_str(&content)
+                .map_err(|err| format!("malformed burst: {}", err))?;
+            burst::verify_burst(&signed_burst).map_err(|err| format!("{}: {}", path, err))?;
+            println!(
+                "Burst of {} positions signed by {}",
+                signed_burst.positions.len(),
+                signed_burst.public_key
+            );
+            Ok(())
+        }
+        _ => Err(usage.to_string()),
+    }
+}
+
+/// `verify <signed positions file> [--trusted-key [p256:]<public key hex>]... [--threshold <k>]
+/// [--export-gpx <gpx file>] [--export-kml <kml file>] [--reject-legacy] [--verbose]
+/// [--check-chain [--chain-head <hex>]] [--max-age <duration>] [--max-future <duration>]
+/// [--now <unix seconds>] [--revocations <list file> --authority [p256:]<public key hex>]`
+///
+/// Without `--threshold` every signature of a record must verify, otherwise at least `k` of the
+/// trusted keys must have signed it. `--export-gpx` writes the records that verified as a track,
+/// `--export-kml` writes every record with its verification status. `--reject-legacy` fails
+/// version 1 records, whose signed hash covers JSON. `--verbose` tells the encoding, hex or
+/// base64, detected for each public key and signature, and the key provenance claimed by the
+/// signer. `--check-chain` also walks the hash chain of the records, starting from
+/// `--chain-head` when given. `--max-age` and `--max-future`, e.g. `300s`, `5m` or `1d`, fail
+/// valid records taken longer ago, or further ahead, than that, compared to the current time or
+/// the time given by `--now`. Records past their signed expiry fail too. `--revocations` fails
+/// records signed by a key revoked at or before their time, see `revocation`, the list having to
+/// be signed by `--authority`.
+fn verify_command(args: &[String]) -> Result<(), String> {
+    let path = args
+        .first()
+        .ok_or("usage: verify <signed positions file>")?;
+    let trusted = flag_values(&args[1..], "--trusted-key")
+        .into_iter()
+        .map(parse_public_key)
+        .collect::<Result<Vec<_>, _>>()?;
+    let threshold = match flag_values(&args[1..], "--threshold").first() {
+        Some(k) => Some(k.parse::<usize>().map_err(|_| "invalid threshold")?),
+        None => None,
+    };
+
+    let reject_legacy = args[1..].iter().any(|arg| arg == "--reject-legacy");
+    let verbose = args[1..].iter().any(|arg| arg == "--verbose");
+    let check_chain = args[1..].iter().any(|arg| arg == "--check-chain");
+    let chain_head = match flag_values(&args[1..], "--chain-head").first() {
+        Some(head) => Some(<[u8; 32]>::from_hex(head).map_err(|_| "invalid chain head")?),
+        None => None,
+    };
+    let duration = |flag| match flag_values(&args[1..], flag).first() {
+        Some(duration) => time::parse_duration(duration)
+            .map(Some)
+            .ok_or_else(|| format!("invalid {} {}, expected e.g. 300s", flag, duration)),
+        None => Ok(None),
+    };
+    let freshness = FreshnessPolicy {
+        max_age: duration("--max-age")?,
+        max_future: duration("--max-future")?,
+    };
+    let clock = parse_clock(&args[1..])?;
+    let revocations = match flag_values(&args[1..], "--revocations").first() {
+        Some(list_path) => {
+            let authority = flag_values(&args[1..], "--authority")
+                .first()
+                .ok_or("--revocations needs the --authority key")?
+                .to_string();
+            let list = read_revocation_list(Path::new(list_path))?;
+            list.verify(&parse_public_key(&authority)?)
+                .map_err(|err| format!("{}: {}", list_path, err))?;
+            Some(list)
+        }
+        None => None,
+    };
+    let export_gpx = flag_values(&args[1..], "--export-gpx").first().copied();
+    let mut kml = match flag_values(&args[1..], "--export-kml").first() {
+        Some(export_path) => {
+            let file = std::fs::File::create(export_path)
+                .map_err(|err| format!("{}: {}", export_path, err))?;
+            let writer = KmlWriter::new(std::io::BufWriter::new(file), path)
+                .map_err(|err| err.to_string())?;
+            Some((writer, *export_path))
+        }
+        None => None,
+    };
+
+    let records = read_signed_positions(Path::new(path))?;
+    let mut verified = Vec::new();
+    let mut not_current = 0;
+    for (index, signed_position) in records.iter().enumerate() {
+        let result = match threshold {
+            _ if reject_legacy && signed_position.version < 2 => {
+                Err(VerifyError::UnsupportedVersion(signed_position.version))
+            }
+            Some(required) => verify_threshold(signed_position, &trusted, required).map(|_| ()),
+            None => verify_signed_position(signed_position),
+        }
+        .and_then(|()| match &revocations {
+            Some(list) => list.check(signed_position),
+            None => Ok(()),
+        });
+        let verdict = freshness.check(&signed_position.position, clock.as_ref());
+        if let Some((writer, _)) = kml.as_mut() {
+            writer
+                .add(signed_position, &result, &verdict)
+                .map_err(|err| err.to_string())?;
+        }
+        match result {
+            Ok(()) => match verdict {
+                Freshness::Fresh => {
+                    verified.push(signed_position);
+                    println!("record {}: valid", index);
+                }
+                verdict => {
+                    not_current += 1;
+                    println!("record {}: valid but {}", index, verdict);
+                }
+            },
+            Err(err) => println!("record {}: invalid, {}", index, err),
+        }
+        if verbose {
+            let describe = |encoding: Option<Encoding>| match encoding {
+                Some(encoding) => encoding.to_string(),
+                None => "undecodable".to_string(),
+            };
+            for (scheme, public_key, signature) in signed_position.signatures() {
+                println!(
+                    "  {} key {}: key as {}, signature as {}",
+                    scheme,
+                    public_key,
+                    describe(encoding::detect_public_key(public_key)),
+                    describe(encoding::detect_signature(signature))
+                );
+            }
+            println!("  device key provenance: {}", signed_position.provenance);
+        }
+    }
+    let failed = records.len() - verified.len() - not_current;
+    println!("{} of {} records verified", verified.len(), records.len());
+
+    if let Some(export_path) = export_gpx {
+        let mut file = std::fs::File::create(export_path)
+            .map_err(|err| format!("{}: {}", export_path, err))?;
+        gpx::write_gpx(&mut file, &verified, failed + not_current)
+            .map_err(|err| err.to_string())?;
+        println!(
+            "Track of {} positions written to {}",
+            verified.len(),
+            export_path
+        );
+    }
+    if let Some((writer, export_path)) = kml {
+        writer.finish().map_err(|err| err.to_string())?;
+        println!("{} placemarks written to {}", records.len(), export_path);
+    }
+    if check_chain {
+        match chain::verify_chain(&records, chain_head.as_ref()) {
+            Ok(()) => println!("Chain of {} records intact", records.len()),
+            Err(err) => return Err(format!("broken chain: {}", err)),
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} records failed verification", failed));
+    }
+    if not_current > 0 {
+        return Err(format!("{} records are not current", not_current));
+    }
+    Ok(())
+}
+
+/// `verify-track <signed positions file> [--max-gap <duration>] [--max-speed <m/s>]
+/// [--check-sequence]`
+///
+/// Verifies the records of a track in order and checks that they make a plausible one, see
+/// `continuity`: timestamps increasing, fixes at most `--max-gap` apart, e.g. `60s`, and implied
+/// speeds of at most `--max-speed`. `--check-sequence` also reports, as advisory findings, the
+/// `key_use` counts of a key not following each other, those counts not being signed. Prints a
+/// JSON report listing invalid records, plausibility findings and advisory findings apart, and
+/// fails when either of the first two is not empty.
+fn verify_track_command(args: &[String]) -> Result<(), String> {
+    let path = args
+        .first()
+        .ok_or("usage: verify-track <signed positions file>")?;
+    let max_gap = match flag_values(&args[1..], "--max-gap").first() {
+        Some(max_gap) => Some(
+            time::parse_duration(max_gap)
+                .ok_or_else(|| format!("invalid --max-gap {}, expected e.g. 60s", max_gap))?,
+        ),
+        None => None,
+    };
+    let max_speed_mps = match flag_values(&args[1..], "--max-speed").first() {
+        Some(max_speed) => Some(
+            max_speed
+                .parse::<f64>()
+                .map_err(|_| format!("invalid --max-speed {}", max_speed))?,
+        ),
+        None => None,
+    };
+    let policy = ContinuityPolicy {
+        max_gap,
+        max_speed_mps,
+        check_sequence: args[1..].iter().any(|arg| arg == "--check-sequence"),
+    };
+    let records = read_signed_positions(Path::new(path))?;
+    let report = continuity::verify_track(&records, &policy);
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).map_err(|err| err.to_string())?
     );
+    if !report.is_clean() {
+        return Err(format!(
+            "{} invalid records, {} plausibility findings",
+            report.invalid.len(),
+            report.findings.len()
+        ));
+    }
+    Ok(())
+}
+
+/// `lora pack <latitude> <longitude> <private key> --epoch <unix seconds> --sequence <n>
+/// [--now <unix seconds>]`, `lora unpack <frames file> <public key> --epoch <unix seconds>`
+///
+/// `pack` prints the two hex encoded uplink frames of a fix, see `lora`. `unpack` reads hex
+/// frames, one per line and in any order, and prints the verified signed positions they give.
+/// Public keys are hex encoded secp256k1 keys, or `p256:<hex key>`.
+fn lora_command(args: &[String]) -> Result<(), String> {
+    let epoch = || match flag_values(args, "--epoch").first() {
+        Some(epoch) => epoch
+            .parse::<u64>()
+            .map_err(|_| "invalid epoch".to_string()),
+        None => Err("missing --epoch".to_string()),
+    };
+    match args {
+        [command, latitude, longitude, key, ..] if command == "pack" => {
+            let sequence = match flag_values(args, "--sequence").first() {
+                Some(sequence) => sequence.parse::<u16>().map_err(|_| "invalid sequence")?,
+                None => return Err("missing --sequence".to_string()),
+            };
+            let position = Position {
+                latitude: latitude.parse().map_err(|_| "invalid latitude")?,
+                longitude: longitude.parse().map_err(|_| "invalid longitude")?,
+                timestamp: parse_clock(args)?.now_unix_secs(),
+                altitude: None,
+                prev_hash: None,
+                expires_at: None,
+                dwell_count: None,
+                last_seen: None,
+            };
+            let signer = parse_signer(key)?;
+            let frames = lora::pack(&position, epoch()?, sequence, signer.as_ref())
+                .map_err(|err| err.to_string())?;
+            for frame in frames {
+                println!("{}", hex::encode(frame));
+            }
+            Ok(())
+        }
+        [command, input, key, ..] if command == "unpack" => {
+            let public_key = parse_public_key(key)?;
+            let content =
+                std::fs::read_to_string(input).map_err(|err| format!("{}: {}", input, err))?;
+            let mut reassembler = lora::Reassembler::new(epoch()?, public_key);
+            for (index, line) in content.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let invalid = |err: &dyn std::fmt::Display| format!("line {}: {}", index + 1, err);
+                let frame = hex::decode(line.trim()).map_err(|err| invalid(&err))?;
+                if let Some(record) = reassembler.push(&frame).map_err(|err| invalid(&err))? {
+                    verify_signed_position(&record).map_err(|err| invalid(&err))?;
+                    println!(
+                        "{}",
+                        serde_json::to_string(&record).expect("JSON serialization")
+                    );
+                }
+            }
+            if reassembler.dropped() > 0 {
+                eprintln!("{} incomplete fixes dropped", reassembler.dropped());
+            }
+            Ok(())
+        }
+        _ => Err(
+            "usage: lora pack <latitude> <longitude> <private key> --epoch <unix seconds> \
+--sequence <n>, or lora unpack <frames file> <public key> --epoch <unix seconds>"
+                .to_string(),
+        ),
+    }
+}
+
+/// `nostr <signed positions file> <private key> [--now <unix seconds>]`
+///
+/// Prints one Nostr event per record, signed with the secp256k1 device key that signed the
+/// records, see `nostr`. Events are created at the current time, or the time given by `--now`.
+fn nostr_command(args: &[String]) -> Result<(), String> {
+    let (input, key) = match args {
+        [input, key, ..] => (input, key),
+        _ => return Err("usage: nostr <signed positions file> <private key hex>".to_string()),
+    };
+    let records = read_signed_positions(Path::new(input))?;
+    let secret_key = parse_secret_key(key)?;
+    let created_at = parse_clock(&args[2..])?.now_unix_secs();
+    for (index, record) in records.iter().enumerate() {
+        let event = nostr::to_event(record, &secret_key, created_at)
+            .map_err(|err| format!("record {}: {}", index, err))?;
+        println!(
+            "{}",
+            serde_json::to_string(&event).expect("JSON serialization")
+        );
+    }
+    Ok(())
+}
+
+/// `ots stamp <batch> [--calendar <url>]...`, `ots upgrade <batch>`, `ots info <batch>`
+fn ots_command(args: &[String]) -> Result<(), String> {
+    let (command, batch) = match args {
+        [command, batch, ..] => (command.as_str(), Path::new(batch)),
+        _ => return Err("usage: ots <stamp|upgrade|info> <batch file>".to_string()),
+    };
+    match command {
+        "stamp" => {
+            let calendars: Vec<&str> = flag_values(&args[2..], "--calendar");
+            let calendars = if calendars.is_empty() {
+                ots::DEFAULT_CALENDARS.to_vec()
+            } else {
+                calendars
+            };
+            ots::stamp(&HttpTransport, &calendars, batch).map_err(|err| err.to_string())?;
+            println!(
+                "Pending proof written to {}",
+                ots::proof_path(batch).display()
+            );
+        }
+        "upgrade" => {
+            let upgrade = ots::upgrade(&HttpTransport, batch).map_err(|err| err.to_string())?;
+            for (calendar, err) in &upgrade.rejected {
+                eprintln!("warning: {}: {}, kept pending", calendar, err);
+            }
+            println!("Timestamp complete: {}", upgrade.complete);
+        }
+        "info" => {
+            let proof = ots::read_proof(batch).map_err(|err| err.to_string())?;
+            println!("Batch digest: {}", hex::encode(proof.digest()));
+            for (msg, attestation) in proof.timestamp.all_attestations() {
+                match attestation {
+                    Attestation::Pending { uri } => println!("Pending at {}", uri),
+                    Attestation::Bitcoin { height } => println!(
+                        "Bitcoin block {}, expected merkle root {}",
+                        height,
+                        hex::encode(msg)
+                    ),
+                    Attestation::Unknown { tag, .. } => {
+                        println!("Unknown attestation {}", hex::encode(tag))
+                    }
+                }
+            }
+        }
+        other => return Err(format!("unknown ots command: {}", other)),
+    }
+    Ok(())
+}
+
+/// `revoke <list file> <authority private key> [p256:]<public key hex> [--at <unix seconds>]
+/// [--now <unix seconds>]`
+///
+/// Adds a device key to the revocation list kept in a file, created when missing, and signs the
+/// list again with the authority key, see `revocation`. Records of the key taken at or after
+/// `--at`, the current time or `--now` by default, are rejected by `verify --revocations`. A key
+/// already in the list keeps the earliest of its revocation times.
+fn revoke_command(args: &[String]) -> Result<(), String> {
+    let (path, authority, public_key) = match args {
+        [path, authority, public_key, ..] => (Path::new(path), authority, public_key),
+        _ => {
+            return Err(
+                "usage: revoke <list file> <authority private key> <public key hex>".to_string(),
+            )
+        }
+    };
+    let authority = parse_signer(authority)?;
+    let public_key = parse_public_key(public_key)?;
+    let now = parse_clock(&args[3..])?.now_unix_secs();
+    let revoked_at = match flag_values(&args[3..], "--at").first() {
+        Some(at) => at.parse().map_err(|_| "invalid --at")?,
+        None => now,
+    };
+    let mut revocations = match path.exists() {
+        true => {
+            let list = read_revocation_list(path)?;
+            let issuer = VerifyingKey::parse(authority.scheme(), &authority.public_key())
+                .map_err(|err| err.to_string())?;
+            list.verify(&issuer)
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+            list.revocations
+        }
+        false => Vec::new(),
+    };
+    let revocation = Revocation {
+        public_key: public_key.to_hex(),
+        scheme: public_key.scheme(),
+        revoked_at,
+    };
+    match revocations.iter_mut().find(|existing| {
+        existing.scheme == revocation.scheme && existing.public_key == revocation.public_key
+    }) {
+        Some(existing) => existing.revoked_at = existing.revoked_at.min(revoked_at),
+        None => revocations.push(revocation),
+    }
+    let list = RevocationList::issue(now, revocations, authority.as_ref())
+        .map_err(|err| err.to_string())?;
+    let json = serde_json::to_string_pretty(&list).expect("JSON serialization");
+    std::fs::write(path, json + "\n").map_err(|err| format!("{}: {}", path.display(), err))?;
+    println!(
+        "{} keys revoked in {}",
+        list.revocations.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// `track pack <signed positions file> <container> [--proof <file>] [--engine <id>]
+/// [--compress-level <level>]`, `track unpack <container> <signed positions file>
+/// [--proof <file> [--compress-level <level>]]` or `track verify <container>`
+///
+/// `--compress-level` writes the container, or the unpacked proof, zstd compressed. Compressed
+/// containers and proofs are read transparently.
+fn track_command(args: &[String]) -> Result<(), String> {
+    let usage = "usage: track <pack|unpack|verify> <file>...";
+    let command = args.first().ok_or(usage)?;
+    match (command.as_str(), &args[1..]) {
+        ("pack", [input, output, flags @ ..]) => {
+            let records = read_signed_positions(Path::new(input))?;
+            let proof = match flag_values(flags, "--proof").first() {
+                Some(path) => compress::read_artifact(Path::new(path))
+                    .map_err(|err| format!("{}: {}", path, err))?,
+                None => Vec::new(),
+            };
+            let engine = flag_values(flags, "--engine").first().copied();
+            let container = track::pack(&records, engine, &proof).map_err(|err| err.to_string())?;
+            compress::write_artifact(Path::new(output), &container, compress_level(flags)?)
+                .map_err(|err| format!("{}: {}", output, err))?;
+            println!("{} records packed into {}", records.len(), output);
+        }
+        ("unpack", [input, output, flags @ ..]) => {
+            let track = read_track(input)?;
+            let mut lines = String::new();
+            for record in &track.records {
+                lines.push_str(&serde_json::to_string(record).expect("JSON serialization"));
+                lines.push('\n');
+            }
+            std::fs::write(output, lines).map_err(|err| format!("{}: {}", output, err))?;
+            if let Some(path) = flag_values(flags, "--proof").first() {
+                compress::write_artifact(Path::new(path), &track.proof, compress_level(flags)?)
+                    .map_err(|err| format!("{}: {}", path, err))?;
+            }
+            println!("{} records unpacked to {}", track.records.len(), output);
+        }
+        ("verify", [input, ..]) => {
+            let track = read_track(input)?;
+            track::verify_track(&track).map_err(|err| err.to_string())?;
+            println!(
+                "{} records of device {} verified, proof of {} bytes not checked",
+                track.records.len(),
+                track.header.device_public_key,
+                track.proof.len()
+            );
+        }
+        _ => return Err(usage.to_string()),
+    }
+    Ok(())
+}
+
+/// `vectors generate <seed> <vectors file>` or `vectors check <vectors file>`
+fn vectors_command(args: &[String]) -> Result<(), String> {
+    match args {
+        [command, seed, output, ..] if command == "generate" => {
+            let set = vectors::generate(seed.as_bytes());
+            let json = serde_json::to_string_pretty(&set).expect("JSON serialization");
+            std::fs::write(output, json + "\n").map_err(|err| format!("{}: {}", output, err))?;
+            println!("{} vectors written to {}", set.vectors.len(), output);
+        }
+        [command, input, ..] if command == "check" => {
+            let content =
+                std::fs::read_to_string(input).map_err(|err| format!("{}: {}", input, err))?;
+            let set: vectors::VectorSet = serde_json::from_str(&content)
+                .map_err(|err| format!("malformed vectors file: {}", err))?;
+            let differences = vectors::check(&set);
+            for difference in &differences {
+                println!("{}", difference);
+            }
+            if !differences.is_empty() {
+                return Err(format!("{} differences found", differences.len()));
+            }
+            println!("{} vectors match", set.vectors.len());
+        }
+        _ => {
+            return Err(
+                "usage: vectors generate <seed> <vectors file> | vectors check <vectors file>"
+                    .to_string(),
+            )
+        }
+    }
+    Ok(())
+}
+
+fn read_track(path: &str) -> Result<track::Track, String> {
+    let data =
+        compress::read_artifact(Path::new(path)).map_err(|err| format!("{}: {}", path, err))?;
+    track::unpack(&data).map_err(|err| err.to_string())
+}
+
+/// Signed positions of a file, plain, in the multiformats profile or as checked Nostr events
+fn read_signed_positions(path: &Path) -> Result<Vec<SignedPosition>, String> {
+    let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let malformed = |err: &dyn std::fmt::Display| format!("malformed signed position: {}", err);
+    serde_json::Deserializer::from_str(&content)
+        .into_iter::<serde_json::Value>()
+        .enumerate()
+        .map(|(index, value)| {
+            let value = value.map_err(|err| malformed(&err))?;
+            if value.get("sig").is_some() {
+                let event: nostr::Event =
+                    serde_json::from_value(value).map_err(|err| malformed(&err))?;
+                nostr::verify_event(&event).map_err(|err| format!("record {}: {}", index, err))
+            } else if value.get("profile").is_some() {
+                let record: MultiformatRecord =
+                    serde_json::from_value(value).map_err(|err| malformed(&err))?;
+                record
+                    .to_signed_position()
+                    .map_err(|err| format!("record {}: {}", index, err))
+            } else {
+                serde_json::from_value(value).map_err(|err| malformed(&err))
+            }
+        })
+        .collect()
+}
+
+fn read_revocation_list(path: &Path) -> Result<RevocationList, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    serde_json::from_str(&content)
+        .map_err(|err| format!("{}: malformed revocation list: {}", path.display(), err))
+}
+
+fn record_json(signed_position: &SignedPosition, multiformats: bool) -> Result<String, String> {
+    let json = match multiformats {
+        true => serde_json::to_string(
+            &MultiformatRecord::from_signed_position(signed_position)
+                .map_err(|err| err.to_string())?,
+        ),
+        false => serde_json::to_string(signed_position),
+    };
+    Ok(json.expect("JSON serialization"))
+}
+
+/// Signature counters of `--key-state`, see `usage`
+struct KeyCounter {
+    state: UsageState,
+    /// Sign past the limits, given by `--force`
+    force: bool,
+    /// Write the count of the device key in records, given by `--include-key-use`
+    include: bool,
+}
+
+fn open_key_counter(args: &[String]) -> Result<Option<KeyCounter>, String> {
+    let Some(path) = flag_values(args, "--key-state").first().copied() else {
+        return Ok(None);
+    };
+    Ok(Some(KeyCounter {
+        state: UsageState::load(Path::new(path)).map_err(|err| format!("{}: {}", path, err))?,
+        force: args.iter().any(|arg| arg == "--force"),
+        include: args.iter().any(|arg| arg == "--include-key-use"),
+    }))
+}
+
+/// Count a signature of `signer` before it is made, recording a refusal in the audit log
+///
+/// Returns the count when it is to be included in the record.
+fn count_signature(
+    counter: Option<&mut KeyCounter>,
+    signer: &dyn Signer,
+    audit: &Option<(AuditLog, String)>,
+) -> Result<Option<u64>, String> {
+    let Some(counter) = counter else {
+        return Ok(None);
+    };
+    match counter.state.reserve(&signer.public_key(), counter.force) {
+        Ok(count) => Ok(counter.include.then_some(count)),
+        Err(err) => {
+            audit_failure(signer, audit, &err)?;
+            Err(err.to_string())
+        }
+    }
+}
+
+/// Audit log of `--audit-log`, and the client of `--client` or `cli:<user>` it records
+fn open_audit_log(args: &[String]) -> Result<Option<(AuditLog, String)>, String> {
+    let Some(path) = flag_values(args, "--audit-log").first().copied() else {
+        return Ok(None);
+    };
+    let log = AuditLog::open(Path::new(path), Box::new(SystemClock))
+        .map_err(|err| format!("{}: {}", path, err))?;
+    let client = match flag_values(args, "--client").first() {
+        Some(client) => client.to_string(),
+        None => format!(
+            "cli:{}",
+            std::env::var("USER").unwrap_or_else(|_| "unknown".to_string())
+        ),
+    };
+    Ok(Some((log, client)))
+}
+
+/// `signer`, recording what it signs in the audit log when there is one
+fn audited<'a>(
+    signer: &'a dyn Signer,
+    audit: &'a Option<(AuditLog, String)>,
+) -> Box<dyn Signer + 'a> {
+    match audit {
+        Some((log, client)) => Box::new(AuditedSigner {
+            signer,
+            log,
+            client: client.clone(),
+        }),
+        None => Box::new(signer),
+    }
+}
+
+fn audit_failure(
+    signer: &dyn Signer,
+    audit: &Option<(AuditLog, String)>,
+    reason: &dyn std::fmt::Display,
+) -> Result<(), String> {
+    if let Some((log, client)) = audit {
+        log.record(
+            &signer.public_key(),
+            None,
+            client,
+            Outcome::Failed(reason.to_string()),
+        )
+        .and_then(|()| log.flush())
+        .map_err(|err| format!("audit log: {}", err))?;
+    }
+    Ok(())
+}
+
+fn flush_audit_log(audit: &Option<(AuditLog, String)>) -> Result<(), String> {
+    match audit {
+        Some((log, _)) => log.flush().map_err(|err| format!("audit log: {}", err)),
+        None => Ok(()),
+    }
+}
+
+/// Hex encoded secp256k1 public key, or P-256 one prefixed with `p256:`
+fn parse_public_key(key: &str) -> Result<VerifyingKey, String> {
+    let (scheme, key) = match key.strip_prefix("p256:") {
+        Some(key) => (Scheme::P256, key),
+        None => (Scheme::Secp256k1, key),
+    };
+    VerifyingKey::parse(scheme, key).map_err(|err| err.to_string())
+}
+
+fn parse_signer(key: &str) -> Result<Box<dyn Signer>, String> {
+    match key.strip_prefix("p256:") {
+        Some(path) => {
+            let data = std::fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+            Ok(Box::new(
+                load_p256_key(&data).map_err(|err| err.to_string())?,
+            ))
+        }
+        None => Ok(Box::new(parse_secret_key(key)?)),
+    }
+}
+
+fn parse_secret_key(key: &str) -> Result<SecretKey, String> {
+    hex::decode(key)
+        .ok()
+        .and_then(|bytes| SecretKey::from_slice(&bytes).ok())
+        .ok_or_else(|| "invalid private key, expected 32 bytes hex".to_string())
+}
+
+/// Clock stopped at `--now <unix seconds>` when given, the system clock otherwise
+fn parse_clock(args: &[String]) -> Result<Box<dyn Clock>, String> {
+    match flag_values(args, "--now").first() {
+        Some(now) => {
+            let now = now
+                .parse::<u64>()
+                .ok()
+                .and_then(|now| now.checked_mul(1000))
+                .ok_or("invalid --now, expected unix seconds")?;
+            Ok(Box::new(MockClock::new(now)))
+        }
+        None => Ok(Box::new(SystemClock)),
+    }
+}
+
+/// Expiry `--valid-for` after `timestamp`, when given
+fn expiry(args: &[String], timestamp: u64) -> Result<Option<u64>, String> {
+    match flag_values(args, "--valid-for").first() {
+        Some(valid_for) => {
+            let valid_for = time::parse_duration(valid_for)
+                .ok_or_else(|| format!("invalid --valid-for {}, expected e.g. 600", valid_for))?;
+            Ok(Some(timestamp.saturating_add(valid_for.as_secs())))
+        }
+        None => Ok(None),
+    }
+}
+
+fn compress_level(args: &[String]) -> Result<Option<i32>, String> {
+    match flag_values(args, "--compress-level").first() {
+        Some(level) => Ok(Some(
+            level.parse().map_err(|_| "invalid compression level")?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Values of every occurrence of `--flag <value>`
+fn flag_values<'a>(args: &'a [String], flag: &str) -> Vec<&'a str> {
+    args.windows(2)
+        .filter(|pair| pair[0] == flag)
+        .map(|pair| pair[1].as_str())
+        .collect()
+}
+
+/// Sign a position, serialize it, and verify it as the receiving party would
+fn demo() {
+    // build SignedPosition object, to be sent
+    let signed_position = sign_coordinates(37.7749, -122.4194, SystemClock.now_unix_secs());
     println!(
         "Built a SignedPosition object, containing the position object, signature and public key\nThis object can be serialized and sent to other party for verification\n"
     );
@@ -48,94 +1656,22 @@ fn main() {
         serde_json::from_str(&signed_payload).expect("JSON deserialization");
 
     let recovered_position = deserialized_signed_position.position;
-    let recovered_ser_sig = deserialized_signed_position.signature;
 
     // recover signature
-    let recovered_sig = secp256k1::ecdsa::Signature::from_compact(
-        <[u8; 64]>::from_hex(&recovered_ser_sig).unwrap().as_ref(),
-    )
-    .expect("64 bytes");
+    let recovered_sig = deser_signature(&deserialized_signed_position.signature);
     println!("Recovered signature: {:?}", recovered_sig);
 
     // recover public key
-    let recovered_ser_pub_key = deserialized_signed_position.public_key;
-    let recovered_pub_key = deser_pubkey(&recovered_ser_pub_key);
+    let recovered_pub_key = deser_pubkey(&deserialized_signed_position.public_key);
 
     // hash recovered position object
     println!("The position object is recovered and a hash of it is computed\nThen the signature is verified using the recovered hash and public key\n");
-    let recovered_payload = serde_json::to_string(&recovered_position).expect("JSON serialization");
-    let recovered_result = hash_message(&recovered_payload);
+    let recovered_result =
+        position_digest(&recovered_position, deserialized_signed_position.version)
+            .expect("supported version");
     let recieved_payload_hash = recovered_result.as_ref();
 
     // verify signature
     let is_valid = verify_signature(&recovered_pub_key, &recovered_sig, recieved_payload_hash);
     println!("Signature is valid: {}", is_valid);
 }
-
-#[no_mangle]
-fn sign_coordinates(latitude: f64, longitude: f64, timestamp: u64) {
-    // convert hex encoded secret key to bytes
-    let secret_key = "3132333435363738393031323334353637383930313233343536373839303131";
-    let secret_key_bytes = hex::decode(&secret_key).expect("Invalid hex");
-    let secret_key_slice = secret_key_bytes.as_slice();
-
-    let position = Position {
-        latitude,
-        longitude,
-        timestamp,
-    };
-
-    // serialize position for hashing purpose
-    let payload = serde_json::to_string(&position).expect("JSON serialization");
-
-    // hash payload
-    let result = hash_message(&payload);
-    let hash = result.as_ref();
-
-    // sign hash
-    let (secret_key, public_key) = create_key_pair_from_bytes(secret_key_slice);
-    let sig = sign_hash_slice(&secret_key, hash);
-
-    // serialize signature and public key - needed as ecdsa::Signature does not implement Serialize
-    let serialized_signature = sig.serialize_compact().encode_hex::<String>();
-    let serialized_public_key = public_key.serialize().encode_hex::<String>();
-
-    SignedPosition {
-        position,
-        signature: serialized_signature,
-        public_key: serialized_public_key,
-    };
-}
-
-fn create_key_pair_from_bytes(secret_bytes: &[u8]) -> (SecretKey, PublicKey) {
-    let secp = Secp256k1::new();
-    let secret_key = SecretKey::from_slice(secret_bytes).expect("32 bytes");
-    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
-    (secret_key, public_key)
-}
-
-fn hash_message(message: &str) -> Box<[u8]> {
-    let mut hasher = sha2::Sha256::new();
-    hasher.update(message.as_bytes());
-    hasher.finalize().as_slice().into()
-}
-
-fn sign_hash_slice(secret_key: &SecretKey, hash: &[u8]) -> secp256k1::ecdsa::Signature {
-    let message = Message::from_digest_slice(&hash).expect("32 bytes");
-    let secp = Secp256k1::new();
-    secp.sign_ecdsa(&message, &secret_key)
-}
-
-fn verify_signature(
-    public_key: &PublicKey,
-    sig: &secp256k1::ecdsa::Signature,
-    hash: &[u8],
-) -> bool {
-    let secp = Secp256k1::new();
-    let message = Message::from_digest_slice(&hash).expect("32 bytes");
-    secp.verify_ecdsa(&message, &sig, &public_key).is_ok()
-}
-
-fn deser_pubkey(pubkey_str: &str) -> PublicKey {
-    PublicKey::from_slice(<[u8; 33]>::from_hex(&pubkey_str).unwrap().as_ref()).expect("33 bytes")
-}
@@ -0,0 +1,409 @@
+//! Self-describing keys, signatures and digests, following multibase and multicodec
+//!
+//! Every binary field is prefixed with the unsigned varint of its multicodec code, then written
+//! in multibase, base58btc (`z`) by default. Readers dispatch on the code, so that the bytes carry
+//! their own scheme and new schemes need no change to the record layout:
+//!
+//! ```text
+//! code      name            content
+//! 0xe7      secp256k1-pub   SEC1 compressed point, 33 bytes
+//! 0x1200    p256-pub        SEC1 compressed point, 33 bytes
+//! 0xd0e7    es256k          compact secp256k1 signature, 64 bytes
+//! 0xd01200  es256           compact P-256 signature, 64 bytes
+//! 0x12      sha2-256        multihash, the varint digest length 32 then the digest
+//! ```
+//!
+//! Decoding also accepts base16 (`f`), base64 (`m`) and base64url (`u`) multibase strings.
+//!
+//! Test vectors, cross-checked with the `multibase` and `unsigned-varint` crates:
+//!
+//! ```text
+//! secp256k1 key of the secret key 32 bytes 0x01
+//!   031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f
+//!   zQ3shgVXZLaMzm5S5x7XzGUG6YFHFLtoEMiv9ao2Bqa7hGyg2
+//! P-256 key of the secret key 32 bytes 0x01
+//!   026ff03b949241ce1dadd43519e6960e0a85b41a69a05c328103aa2bce1594ca16
+//!   zDnaeXxvmFHMHjqgQTbadpWG7gPHwnga1i7SMwxrV2BSdUjAD
+//! es256k signature of 64 bytes 0x00
+//!   z7sD8GHfobrcbUBMhSm7LkLaHJmfSAxhnWN8CAMayiqsFmHJeycuN6FAdgQv87aHs2Txyt3pEX3pJAvA1kbhfdsbRYMzo
+//! es256 signature of 64 bytes 0x07
+//!   zHq9vjFFJqhMPoYixGVWKhMYu5KWZNWuHziso8nUVa1kk3ueEgRRueYxGD3AF6Gt1AnQNNNzNxQcjAug3hSmSWvtLKDVzE
+//! sha2-256 multihash of the empty string
+//!   zQmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n
+//! ```
+
+use std::fmt;
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::{self, SIGNATURE_LENGTH};
+use crate::scheme::{KeyProvenance, Scheme, VerifyingKey};
+use crate::session::Endorsement;
+use crate::{verify_signed_position, CoSignature, Position, SignedPosition, VerifyError};
+
+pub const SECP256K1_PUB: u64 = 0xe7;
+pub const P256_PUB: u64 = 0x1200;
+pub const ES256K: u64 = 0xd0e7;
+pub const ES256: u64 = 0xd01200;
+pub const SHA2_256: u64 = 0x12;
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, PartialEq)]
+pub enum MultiformatError {
+    UnsupportedBase(char),
+    MalformedBase,
+    MalformedVarint,
+    UnsupportedCodec(u64),
+    /// A codec of another kind than expected, e.g. a signature given as a public key
+    UnexpectedCodec(u64),
+    BadLength {
+        codec: u64,
+        length: usize,
+    },
+    /// The signature codec does not belong to the scheme of its public key
+    SchemeMismatch,
+    /// The digest carried by the record is not the digest of its position
+    DigestMismatch,
+    Verify(VerifyError),
+}
+
+impl fmt::Display for MultiformatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultiformatError::UnsupportedBase(prefix) => {
+                write!(f, "unsupported multibase prefix {:?}", prefix)
+            }
+            MultiformatError::MalformedBase => write!(f, "malformed multibase string"),
+            MultiformatError::MalformedVarint => write!(f, "malformed multicodec varint"),
+            MultiformatError::UnsupportedCodec(codec) => {
+                write!(f, "unsupported codec {:#x}", codec)
+            }
+            MultiformatError::UnexpectedCodec(codec) => write!(f, "unexpected codec {:#x}", codec),
+            MultiformatError::BadLength { codec, length } => {
+                write!(f, "bad length {} for codec {:#x}", length, codec)
+            }
+            MultiformatError::SchemeMismatch => {
+                write!(f, "signature codec does not match the public key")
+            }
+            MultiformatError::DigestMismatch => write!(f, "digest does not match the position"),
+            MultiformatError::Verify(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for MultiformatError {}
+
+impl From<VerifyError> for MultiformatError {
+    fn from(err: VerifyError) -> Self {
+        MultiformatError::Verify(err)
+    }
+}
+
+/// `SignedPosition` with its keys, signatures and digest as multiformats
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MultiformatRecord {
+    pub profile: Profile,
+    pub version: u8,
+    pub position: Position,
+    pub public_key: String,
+    pub signature: String,
+    /// Multihash of the signed digest
+    pub digest: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub co_signatures: Vec<MultiformatCoSignature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_token: Option<String>,
+    #[serde(default, skip_serializing_if = "KeyProvenance::is_software")]
+    pub provenance: KeyProvenance,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_use: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endorsement: Option<Endorsement>,
+}
+
+/// Tag telling multiformat records apart from plain ones
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    Multiformats,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MultiformatCoSignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl MultiformatRecord {
+    pub fn from_signed_position(record: &SignedPosition) -> Result<Self, MultiformatError> {
+        let key = |scheme, public_key| VerifyingKey::parse(scheme, public_key).map(encode_key);
+        let signature = |scheme, signature: &str| -> Result<String, MultiformatError> {
+            let (bytes, _) = encoding::decode(signature, &[SIGNATURE_LENGTH])
+                .ok_or_else(|| VerifyError::MalformedSignature(signature.to_string()))?;
+            Ok(encode_signature(scheme, &bytes))
+        };
+        Ok(MultiformatRecord {
+            profile: Profile::Multiformats,
+            version: record.version,
+            position: record.position.clone(),
+            public_key: key(record.scheme, &record.public_key)?,
+            signature: signature(record.scheme, &record.signature)?,
+            digest: encode_digest(&record.digest()?),
+            co_signatures: record
+                .co_signatures
+                .iter()
+                .map(|co| {
+                    Ok(MultiformatCoSignature {
+                        public_key: key(co.scheme, &co.public_key)?,
+                        signature: signature(co.scheme, &co.signature)?,
+                    })
+                })
+                .collect::<Result<_, MultiformatError>>()?,
+            timestamp_token: record.timestamp_token.clone(),
+            provenance: record.provenance,
+            key_use: record.key_use,
+            endorsement: record.endorsement.clone(),
+        })
+    }
+
+    /// Plain record, the scheme of every signature being read from its codecs
+    ///
+    /// Fails when the digest carried by the record is not the digest of its position.
+    pub fn to_signed_position(&self) -> Result<SignedPosition, MultiformatError> {
+        let (scheme, public_key, signature) = decode_pair(&self.public_key, &self.signature)?;
+        let co_signatures = self
+            .co_signatures
+            .iter()
+            .map(|co| {
+                let (scheme, public_key, signature) = decode_pair(&co.public_key, &co.signature)?;
+                Ok(CoSignature {
+                    public_key,
+                    signature,
+                    scheme,
+                })
+            })
+            .collect::<Result<_, MultiformatError>>()?;
+        let signed_position = SignedPosition {
+            version: self.version,
+            position: self.position.clone(),
+            signature,
+            public_key,
+            scheme,
+            co_signatures,
+            timestamp_token: self.timestamp_token.clone(),
+            provenance: self.provenance,
+            key_use: self.key_use,
+            endorsement: self.endorsement.clone(),
+        };
+        if decode_digest(&self.digest)? != *signed_position.digest()? {
+            return Err(MultiformatError::DigestMismatch);
+        }
+        Ok(signed_position)
+    }
+}
+
+/// Check the digest of a record, then its signatures
+pub fn verify_record(record: &MultiformatRecord) -> Result<(), MultiformatError> {
+    verify_signed_position(&record.to_signed_position()?)?;
+    Ok(())
+}
+
+pub fn encode_key(key: VerifyingKey) -> String {
+    let codec = match key.scheme() {
+        Scheme::Secp256k1 => SECP256K1_PUB,
+        Scheme::P256 => P256_PUB,
+    };
+    let bytes = hex::decode(key.to_hex()).expect("hex encoded key");
+    encode(codec, &bytes)
+}
+
+pub fn decode_key(text: &str) -> Result<VerifyingKey, MultiformatError> {
+    let (codec, bytes) = decode(text)?;
+    let scheme = match codec {
+        SECP256K1_PUB => Scheme::Secp256k1,
+        P256_PUB => Scheme::P256,
+        ES256K | ES256 | SHA2_256 => return Err(MultiformatError::UnexpectedCodec(codec)),
+        _ => return Err(MultiformatError::UnsupportedCodec(codec)),
+    };
+    if bytes.len() != 33 {
+        return Err(MultiformatError::BadLength {
+            codec,
+            length: bytes.len(),
+        });
+    }
+    Ok(VerifyingKey::parse(scheme, &hex::encode(bytes))?)
+}
+
+/// Compact signature of `scheme`
+pub fn encode_signature(scheme: Scheme, signature: &[u8]) -> String {
+    let codec = match scheme {
+        Scheme::Secp256k1 => ES256K,
+        Scheme::P256 => ES256,
+    };
+    encode(codec, signature)
+}
+
+pub fn decode_signature(text: &str) -> Result<(Scheme, Vec<u8>), MultiformatError> {
+    let (codec, bytes) = decode(text)?;
+    let scheme = match codec {
+        ES256K => Scheme::Secp256k1,
+        ES256 => Scheme::P256,
+        SECP256K1_PUB | P256_PUB | SHA2_256 => {
+            return Err(MultiformatError::UnexpectedCodec(codec))
+        }
+        _ => return Err(MultiformatError::UnsupportedCodec(codec)),
+    };
+    if bytes.len() != SIGNATURE_LENGTH {
+        return Err(MultiformatError::BadLength {
+            codec,
+            length: bytes.len(),
+        });
+    }
+    Ok((scheme, bytes))
+}
+
+/// SHA-256 multihash
+pub fn encode_digest(digest: &[u8]) -> String {
+    let mut content = Vec::with_capacity(digest.len() + 1);
+    write_varint(digest.len() as u64, &mut content);
+    content.extend_from_slice(digest);
+    encode(SHA2_256, &content)
+}
+
+pub fn decode_digest(text: &str) -> Result<Vec<u8>, MultiformatError> {
+    let (codec, bytes) = decode(text)?;
+    match codec {
+        SHA2_256 => {}
+        SECP256K1_PUB | P256_PUB | ES256K | ES256 => {
+            return Err(MultiformatError::UnexpectedCodec(codec))
+        }
+        _ => return Err(MultiformatError::UnsupportedCodec(codec)),
+    }
+    let (length, digest) = read_varint(&bytes)?;
+    if length != 32 || digest.len() != 32 {
+        return Err(MultiformatError::BadLength {
+            codec,
+            length: digest.len(),
+        });
+    }
+    Ok(digest.to_vec())
+}
+
+/// Multicodec `codec` followed by `content`, in base58btc
+pub fn encode(codec: u64, content: &[u8]) -> String {
+    let mut bytes = Vec::with_capacity(content.len() + 4);
+    write_varint(codec, &mut bytes);
+    bytes.extend_from_slice(content);
+    format!("z{}", base58_encode(&bytes))
+}
+
+/// Multicodec code and content of a multibase string
+pub fn decode(text: &str) -> Result<(u64, Vec<u8>), MultiformatError> {
+    let mut chars = text.chars();
+    let prefix = chars.next().ok_or(MultiformatError::MalformedBase)?;
+    let rest = chars.as_str();
+    let bytes = match prefix {
+        'z' => base58_decode(rest),
+        'f' => hex::decode(rest).ok(),
+        'm' => base64::engine::general_purpose::STANDARD_NO_PAD
+            .decode(rest)
+            .ok(),
+        'u' => base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(rest)
+            .ok(),
+        _ => return Err(MultiformatError::UnsupportedBase(prefix)),
+    }
+    .ok_or(MultiformatError::MalformedBase)?;
+    let (codec, content) = read_varint(&bytes)?;
+    Ok((codec, content.to_vec()))
+}
+
+fn decode_pair(
+    public_key: &str,
+    signature: &str,
+) -> Result<(Scheme, String, String), MultiformatError> {
+    let key = decode_key(public_key)?;
+    let (scheme, signature) = decode_signature(signature)?;
+    if scheme != key.scheme() {
+        return Err(MultiformatError::SchemeMismatch);
+    }
+    Ok((scheme, key.to_hex(), hex::encode(signature)))
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Value and remaining bytes, rejecting non-minimal encodings, as unsigned-varint does
+fn read_varint(bytes: &[u8]) -> Result<(u64, &[u8]), MultiformatError> {
+    let mut value = 0u64;
+    // multiformats varints are at most 9 bytes long
+    for (index, &byte) in bytes.iter().enumerate().take(9) {
+        value |= u64::from(byte & 0x7f) << (7 * index);
+        if byte & 0x80 == 0 {
+            if byte == 0 && index > 0 {
+                return Err(MultiformatError::MalformedVarint);
+            }
+            return Ok((value, &bytes[index + 1..]));
+        }
+    }
+    Err(MultiformatError::MalformedVarint)
+}
+
+fn base58_encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+    // base 58 digits, least significant first
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for &byte in &bytes[zeros..] {
+        let mut carry = u32::from(byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    std::iter::repeat_n(b'1', zeros)
+        .chain(
+            digits
+                .iter()
+                .rev()
+                .map(|&digit| BASE58_ALPHABET[digit as usize]),
+        )
+        .map(char::from)
+        .collect()
+}
+
+fn base58_decode(text: &str) -> Option<Vec<u8>> {
+    let zeros = text.bytes().take_while(|&byte| byte == b'1').count();
+    // bytes, least significant first
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len() * 733 / 1000 + 1);
+    for character in text.bytes().skip(zeros) {
+        let mut carry = BASE58_ALPHABET
+            .iter()
+            .position(|&letter| letter == character)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    Some(
+        std::iter::repeat_n(0, zeros)
+            .chain(bytes.into_iter().rev())
+            .collect(),
+    )
+}
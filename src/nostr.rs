@@ -0,0 +1,204 @@
+//! Nostr events carrying signed positions
+//!
+//! A record is published as a NIP-01 event of kind `LOCATION_KIND`, chosen in the regular range
+//! and not assigned by any NIP. The content is the JSON serialized `SignedPosition`, and tags
+//! repeat where and when the fix was taken so that relays can filter on them:
+//!
+//! ```text
+//! ["g", <geohash of the position, GEOHASH_PRECISION characters>]
+//! ["timestamp", <position timestamp, unix seconds>]
+//! ["alt", "GPS location attestation"]     NIP-31 description for clients not knowing the kind
+//! ```
+//!
+//! The event id is the SHA-256 of the JSON array
+//! `[0,<pubkey>,<created_at>,<kind>,<tags>,<content>]` without whitespace, strings escaped as
+//! `JSON.stringify` does, and `sig` the BIP-340 Schnorr signature of the id by the device key,
+//! which must be a secp256k1 key.
+//!
+//! Test vector for the secret key 32 bytes 0x01, of the record signed by
+//! `sign 48.85 2.35 <same key> --now 1700000000`, created at 1700000100. It was checked against
+//! the `nostr` crate, as were ids of contents and tags with control and non-ASCII characters.
+//!
+//! ```text
+//! id  3d2d887de08a5c268436bc0a55de79dce235e8c9b634a5ced986d3539829cbde
+//! sig 7a14b935d8490d030693177b32a2b5aade22d03407144faee92b22361c3ff3be
+//!     10366bc237880275b71946c6b6278ae75f4acd385f782e9118c8d884046ca3e7
+//! ```
+
+use std::fmt;
+
+use secp256k1::{schnorr, Keypair, Message, Secp256k1, SecretKey, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::geo::geohash;
+use crate::scheme::{Scheme, VerifyingKey};
+use crate::{verify_signed_position, SignedPosition, VerifyError};
+
+/// Kind of location attestation events
+pub const LOCATION_KIND: u32 = 7400;
+
+/// Length of the geohash tag, a cell of about 5 by 5 meters
+pub const GEOHASH_PRECISION: usize = 9;
+
+const ALT: &str = "GPS location attestation";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Event {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: u64,
+    pub kind: u32,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String,
+}
+
+#[derive(Debug)]
+pub enum NostrError {
+    /// Schnorr signatures need a secp256k1 device key
+    UnsupportedScheme(Scheme),
+    /// The secret key is not the one that signed the record
+    KeyMismatch,
+    WrongKind(u32),
+    MalformedEvent(&'static str),
+    IdMismatch,
+    BadSignature,
+    /// A tag missing, or not matching the position
+    Tag(&'static str),
+    MalformedContent(serde_json::Error),
+    Verify(VerifyError),
+}
+
+impl fmt::Display for NostrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NostrError::UnsupportedScheme(scheme) => {
+                write!(f, "nostr events need a secp256k1 key, not {}", scheme)
+            }
+            NostrError::KeyMismatch => write!(f, "key did not sign the record"),
+            NostrError::WrongKind(kind) => {
+                write!(f, "event kind {} instead of {}", kind, LOCATION_KIND)
+            }
+            NostrError::MalformedEvent(field) => write!(f, "malformed event {}", field),
+            NostrError::IdMismatch => write!(f, "event id does not match its content"),
+            NostrError::BadSignature => write!(f, "bad event signature"),
+            NostrError::Tag(tag) => write!(f, "{} tag does not match the position", tag),
+            NostrError::MalformedContent(err) => write!(f, "malformed event content: {}", err),
+            NostrError::Verify(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for NostrError {}
+
+impl From<VerifyError> for NostrError {
+    fn from(err: VerifyError) -> Self {
+        NostrError::Verify(err)
+    }
+}
+
+/// Event publishing `record`, signed with the device key that signed the record
+pub fn to_event(
+    record: &SignedPosition,
+    secret_key: &SecretKey,
+    created_at: u64,
+) -> Result<Event, NostrError> {
+    let secp = Secp256k1::new();
+    let keypair = Keypair::from_secret_key(&secp, secret_key);
+    let (pubkey, _) = keypair.x_only_public_key();
+    if device_key(record)? != pubkey {
+        return Err(NostrError::KeyMismatch);
+    }
+    let pubkey = hex::encode(pubkey.serialize());
+    let tags = tags(record);
+    let content = serde_json::to_string(record).expect("JSON serialization");
+    let id = event_id(&pubkey, created_at, LOCATION_KIND, &tags, &content);
+    let sig = secp.sign_schnorr_no_aux_rand(&Message::from_digest(id), &keypair);
+    Ok(Event {
+        id: hex::encode(id),
+        pubkey,
+        created_at,
+        kind: LOCATION_KIND,
+        tags,
+        content,
+        sig: hex::encode(sig.serialize()),
+    })
+}
+
+/// Check an incoming event and the record it carries, returning the record
+pub fn verify_event(event: &Event) -> Result<SignedPosition, NostrError> {
+    if event.kind != LOCATION_KIND {
+        return Err(NostrError::WrongKind(event.kind));
+    }
+    let id = event_id(
+        &event.pubkey,
+        event.created_at,
+        event.kind,
+        &event.tags,
+        &event.content,
+    );
+    if hex::decode(&event.id).map_err(|_| NostrError::MalformedEvent("id"))? != id {
+        return Err(NostrError::IdMismatch);
+    }
+    let pubkey = hex::decode(&event.pubkey)
+        .ok()
+        .and_then(|bytes| XOnlyPublicKey::from_slice(&bytes).ok())
+        .ok_or(NostrError::MalformedEvent("pubkey"))?;
+    let sig = hex::decode(&event.sig)
+        .ok()
+        .and_then(|bytes| schnorr::Signature::from_slice(&bytes).ok())
+        .ok_or(NostrError::MalformedEvent("sig"))?;
+    Secp256k1::verification_only()
+        .verify_schnorr(&sig, &Message::from_digest(id), &pubkey)
+        .map_err(|_| NostrError::BadSignature)?;
+
+    let record: SignedPosition =
+        serde_json::from_str(&event.content).map_err(NostrError::MalformedContent)?;
+    if device_key(&record)? != pubkey {
+        return Err(NostrError::KeyMismatch);
+    }
+    let expected = tags(&record);
+    for name in ["g", "timestamp"] {
+        let find = |tags: &[Vec<String>]| {
+            tags.iter()
+                .find(|tag| tag.first().map(String::as_str) == Some(name))
+                .cloned()
+        };
+        if find(&event.tags) != find(&expected) {
+            return Err(NostrError::Tag(name));
+        }
+    }
+    verify_signed_position(&record)?;
+    Ok(record)
+}
+
+/// NIP-01 id of an event
+pub fn event_id(
+    pubkey: &str,
+    created_at: u64,
+    kind: u32,
+    tags: &[Vec<String>],
+    content: &str,
+) -> [u8; 32] {
+    let serialized = serde_json::to_string(&(0, pubkey, created_at, kind, tags, content))
+        .expect("JSON serialization");
+    Sha256::digest(serialized.as_bytes()).into()
+}
+
+fn tags(record: &SignedPosition) -> Vec<Vec<String>> {
+    let tag = |name: &str, value: String| vec![name.to_string(), value];
+    vec![
+        tag("g", geohash(&record.position, GEOHASH_PRECISION)),
+        tag("timestamp", record.position.timestamp.to_string()),
+        tag("alt", ALT.to_string()),
+    ]
+}
+
+/// X-only form of the key that signed a record
+fn device_key(record: &SignedPosition) -> Result<XOnlyPublicKey, NostrError> {
+    match VerifyingKey::parse(record.scheme, &record.public_key)? {
+        VerifyingKey::Secp256k1(public_key) => Ok(public_key.x_only_public_key().0),
+        VerifyingKey::P256(_) => Err(NostrError::UnsupportedScheme(Scheme::P256)),
+    }
+}
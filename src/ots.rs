@@ -0,0 +1,651 @@
+//! OpenTimestamps anchoring of batch files
+//!
+//! A batch file (e.g. a JSON lines file of `SignedPosition`) is hashed with SHA-256, a random
+//! nonce is appended to the digest and the result is hashed again before being submitted to the
+//! calendar servers, so that calendars do not learn the batch digest.
+//!
+//! The proof is kept in a detached `<batch>.ots` file next to the batch, in the usual
+//! OpenTimestamps serialization. Right after `stamp` it only carries pending attestations, `upgrade`
+//! asks the calendars for the completed Bitcoin attestations and rewrites the file.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use sha2::Digest;
+
+use crate::fresh_entropy;
+use crate::transport::{Transport, TransportError};
+
+const HEADER_MAGIC: &[u8] = b"\x00OpenTimestamps\x00\x00Proof\x00\xbf\x89\xe2\xe8\x84\xe8\x92\x94";
+const MAJOR_VERSION: u64 = 1;
+
+const TAG_ATTESTATION: u8 = 0x00;
+const TAG_FORK: u8 = 0xff;
+const OP_SHA1: u8 = 0x02;
+const OP_RIPEMD160: u8 = 0x03;
+const OP_SHA256: u8 = 0x08;
+const OP_KECCAK256: u8 = 0x67;
+const OP_APPEND: u8 = 0xf0;
+const OP_PREPEND: u8 = 0xf1;
+const OP_REVERSE: u8 = 0xf2;
+const OP_HEXLIFY: u8 = 0xf3;
+
+const PENDING_TAG: [u8; 8] = [0x83, 0xdf, 0xe3, 0x0d, 0x2e, 0xf9, 0x0c, 0x8e];
+const BITCOIN_TAG: [u8; 8] = [0x05, 0x88, 0x96, 0x0d, 0x73, 0xd7, 0x19, 0x01];
+
+// limits from the reference implementation, keeps malformed proofs from exhausting memory
+const MAX_OP_ARG_LENGTH: usize = 4096;
+const MAX_MESSAGE_LENGTH: usize = 4096;
+const MAX_URI_LENGTH: usize = 1000;
+const MAX_RECURSION_DEPTH: usize = 256;
+
+pub const DEFAULT_CALENDARS: &[&str] = &[
+    "http://a.pool.opentimestamps.org",
+    "http://b.pool.opentimestamps.org",
+    "http://a.pool.eternitywall.com",
+];
+
+#[derive(Debug)]
+pub enum OtsError {
+    Io(std::io::Error),
+    Transport(TransportError),
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u64),
+    UnsupportedFileHash(u8),
+    UnknownOp(u8),
+    /// Operation that is valid in proofs but not implemented here
+    UnsupportedOp(u8),
+    Malformed(&'static str),
+    DigestMismatch,
+    NoCalendarResponded,
+    Pending,
+    MerkleRootMismatch {
+        height: u64,
+    },
+}
+
+impl fmt::Display for OtsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OtsError::Io(err) => write!(f, "{}", err),
+            OtsError::Transport(err) => write!(f, "{}", err),
+            OtsError::Truncated => write!(f, "truncated timestamp proof"),
+            OtsError::BadMagic => write!(f, "not an OpenTimestamps proof"),
+            OtsError::UnsupportedVersion(version) => {
+                write!(f, "unsupported OpenTimestamps proof version {}", version)
+            }
+            OtsError::UnsupportedFileHash(op) => {
+                write!(f, "unsupported file hash operation 0x{:02x}", op)
+            }
+            OtsError::UnknownOp(op) => write!(f, "unknown operation 0x{:02x}", op),
+            OtsError::UnsupportedOp(op) => write!(f, "unsupported operation 0x{:02x}", op),
+            OtsError::Malformed(reason) => write!(f, "malformed timestamp proof: {}", reason),
+            OtsError::DigestMismatch => write!(f, "proof does not match the batch digest"),
+            OtsError::NoCalendarResponded => write!(f, "no calendar accepted the digest"),
+            OtsError::Pending => write!(f, "timestamp is still pending"),
+            OtsError::MerkleRootMismatch { height } => {
+                write!(
+                    f,
+                    "proof does not match the merkle root of block {}",
+                    height
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for OtsError {}
+
+impl From<std::io::Error> for OtsError {
+    fn from(err: std::io::Error) -> Self {
+        OtsError::Io(err)
+    }
+}
+
+impl From<TransportError> for OtsError {
+    fn from(err: TransportError) -> Self {
+        OtsError::Transport(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Sha1,
+    Ripemd160,
+    Sha256,
+    Keccak256,
+    Append(Vec<u8>),
+    Prepend(Vec<u8>),
+    Reverse,
+    Hexlify,
+}
+
+impl Op {
+    fn tag(&self) -> u8 {
+        match self {
+            Op::Sha1 => OP_SHA1,
+            Op::Ripemd160 => OP_RIPEMD160,
+            Op::Sha256 => OP_SHA256,
+            Op::Keccak256 => OP_KECCAK256,
+            Op::Append(_) => OP_APPEND,
+            Op::Prepend(_) => OP_PREPEND,
+            Op::Reverse => OP_REVERSE,
+            Op::Hexlify => OP_HEXLIFY,
+        }
+    }
+
+    pub fn apply(&self, msg: &[u8]) -> Result<Vec<u8>, OtsError> {
+        let result = match self {
+            Op::Sha256 => sha2::Sha256::digest(msg).to_vec(),
+            Op::Append(arg) => [msg, arg.as_slice()].concat(),
+            Op::Prepend(arg) => [arg.as_slice(), msg].concat(),
+            Op::Reverse => msg.iter().rev().copied().collect(),
+            Op::Hexlify => hex::encode(msg).into_bytes(),
+            Op::Sha1 | Op::Ripemd160 | Op::Keccak256 => {
+                return Err(OtsError::UnsupportedOp(self.tag()))
+            }
+        };
+        if result.len() > MAX_MESSAGE_LENGTH {
+            return Err(OtsError::Malformed("message too long"));
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attestation {
+    /// Calendar promises a Bitcoin attestation later, retrievable at this uri
+    Pending {
+        uri: String,
+    },
+    /// Message is the merkle root of the block at this height
+    Bitcoin {
+        height: u64,
+    },
+    Unknown {
+        tag: [u8; 8],
+        payload: Vec<u8>,
+    },
+}
+
+/// Commitment operations applied to `msg`, and the attestations it directly carries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timestamp {
+    pub msg: Vec<u8>,
+    pub attestations: Vec<Attestation>,
+    pub ops: Vec<(Op, Timestamp)>,
+}
+
+impl Timestamp {
+    pub fn new(msg: Vec<u8>) -> Self {
+        Timestamp {
+            msg,
+            attestations: Vec::new(),
+            ops: Vec::new(),
+        }
+    }
+
+    /// Apply `op`, reusing an existing branch for the same operation
+    pub fn add_op(&mut self, op: Op) -> Result<&mut Timestamp, OtsError> {
+        let index = match self.ops.iter().position(|(existing, _)| *existing == op) {
+            Some(index) => index,
+            None => {
+                let msg = op.apply(&self.msg)?;
+                self.ops.push((op, Timestamp::new(msg)));
+                self.ops.len() - 1
+            }
+        };
+        Ok(&mut self.ops[index].1)
+    }
+
+    /// Merge another timestamp over the same message into this one
+    pub fn merge(&mut self, other: Timestamp) -> Result<(), OtsError> {
+        if other.msg != self.msg {
+            return Err(OtsError::Malformed(
+                "merging timestamps of different messages",
+            ));
+        }
+        for attestation in other.attestations {
+            if !self.attestations.contains(&attestation) {
+                self.attestations.push(attestation);
+            }
+        }
+        for (op, child) in other.ops {
+            self.add_op(op)?.merge(child)?;
+        }
+        Ok(())
+    }
+
+    /// Every attestation in the tree, with the message it attests
+    pub fn all_attestations(&self) -> Vec<(&[u8], &Attestation)> {
+        let mut found: Vec<(&[u8], &Attestation)> = self
+            .attestations
+            .iter()
+            .map(|attestation| (self.msg.as_slice(), attestation))
+            .collect();
+        for (_, child) in &self.ops {
+            found.extend(child.all_attestations());
+        }
+        found
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.all_attestations()
+            .iter()
+            .any(|(_, attestation)| matches!(attestation, Attestation::Bitcoin { .. }))
+    }
+
+    /// Deserialize a timestamp over `msg`, as returned by calendars
+    pub fn deserialize(msg: Vec<u8>, data: &[u8]) -> Result<Self, OtsError> {
+        let mut reader = OtsReader { data };
+        let timestamp = reader.timestamp(msg, 0)?;
+        if !reader.data.is_empty() {
+            return Err(OtsError::Malformed("trailing bytes"));
+        }
+        Ok(timestamp)
+    }
+
+    pub fn serialize(&self, out: &mut Vec<u8>) {
+        // every branch but the last one is preceded by a fork marker
+        let branches = self.attestations.len() + self.ops.len();
+        for (index, attestation) in self.attestations.iter().enumerate() {
+            if index + 1 < branches {
+                out.push(TAG_FORK);
+            }
+            out.push(TAG_ATTESTATION);
+            write_attestation(attestation, out);
+        }
+        for (index, (op, child)) in self.ops.iter().enumerate() {
+            if self.attestations.len() + index + 1 < branches {
+                out.push(TAG_FORK);
+            }
+            out.push(op.tag());
+            if let Op::Append(arg) | Op::Prepend(arg) = op {
+                write_varbytes(arg, out);
+            }
+            child.serialize(out);
+        }
+    }
+}
+
+/// Detached proof over the SHA-256 digest of a file, the content of a `.ots` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetachedTimestamp {
+    pub timestamp: Timestamp,
+}
+
+impl DetachedTimestamp {
+    pub fn digest(&self) -> &[u8] {
+        &self.timestamp.msg
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = HEADER_MAGIC.to_vec();
+        write_varuint(MAJOR_VERSION, &mut out);
+        out.push(OP_SHA256);
+        out.extend_from_slice(&self.timestamp.msg);
+        self.timestamp.serialize(&mut out);
+        out
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, OtsError> {
+        let data = data.strip_prefix(HEADER_MAGIC).ok_or(OtsError::BadMagic)?;
+        let mut reader = OtsReader { data };
+        let version = reader.varuint()?;
+        if version != MAJOR_VERSION {
+            return Err(OtsError::UnsupportedVersion(version));
+        }
+        let file_hash = reader.byte()?;
+        if file_hash != OP_SHA256 {
+            return Err(OtsError::UnsupportedFileHash(file_hash));
+        }
+        let digest = reader.bytes(32)?.to_vec();
+        let timestamp = reader.timestamp(digest, 0)?;
+        if !reader.data.is_empty() {
+            return Err(OtsError::Malformed("trailing bytes"));
+        }
+        Ok(DetachedTimestamp { timestamp })
+    }
+}
+
+/// Source of Bitcoin block merkle roots, in the internal byte order of block headers
+pub trait BlockHeaders {
+    fn merkle_root(&self, height: u64) -> Result<[u8; 32], OtsError>;
+}
+
+/// Path of the proof kept next to a batch file
+pub fn proof_path(batch_path: &Path) -> PathBuf {
+    let mut name = batch_path.as_os_str().to_os_string();
+    name.push(".ots");
+    PathBuf::from(name)
+}
+
+pub fn batch_digest(batch_path: &Path) -> Result<Vec<u8>, OtsError> {
+    let content = std::fs::read(batch_path)?;
+    Ok(sha2::Sha256::digest(content).to_vec())
+}
+
+pub fn read_proof(batch_path: &Path) -> Result<DetachedTimestamp, OtsError> {
+    DetachedTimestamp::deserialize(&std::fs::read(proof_path(batch_path))?)
+}
+
+pub fn write_proof(batch_path: &Path, proof: &DetachedTimestamp) -> Result<(), OtsError> {
+    // write to a temporary file first, so that an interrupted upgrade keeps the previous proof
+    let path = proof_path(batch_path);
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, proof.serialize())?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// Submit the digest of the batch to the calendars and store the pending proof next to it
+///
+/// Calendars that fail, or answer with something else than a proof, are skipped, at least one
+/// must accept the digest.
+pub fn stamp(
+    transport: &dyn Transport,
+    calendars: &[&str],
+    batch_path: &Path,
+) -> Result<DetachedTimestamp, OtsError> {
+    let digest = batch_digest(batch_path)?;
+    let mut root = Timestamp::new(digest);
+    let nonce = fresh_entropy(&root.msg)[..16].to_vec();
+    let commitment = root.add_op(Op::Append(nonce))?.add_op(Op::Sha256)?;
+
+    let mut accepted = 0;
+    for calendar in calendars {
+        let url = format!("{}/digest", calendar.trim_end_matches('/'));
+        let response = match transport.post(&url, "application/octet-stream", &commitment.msg) {
+            Ok(response) => response,
+            Err(_) => continue,
+        };
+        let Ok(timestamp) = Timestamp::deserialize(commitment.msg.clone(), &response) else {
+            continue;
+        };
+        commitment.merge(timestamp)?;
+        accepted += 1;
+    }
+    if accepted == 0 {
+        return Err(OtsError::NoCalendarResponded);
+    }
+
+    let proof = DetachedTimestamp { timestamp: root };
+    write_proof(batch_path, &proof)?;
+    Ok(proof)
+}
+
+/// Outcome of `upgrade`
+#[derive(Debug)]
+pub struct Upgrade {
+    /// Whether the proof now carries a Bitcoin attestation
+    pub complete: bool,
+    /// Calendars whose answer is not a proof of the pending message, with the reason, their
+    /// attestations being kept pending
+    pub rejected: Vec<(String, OtsError)>,
+}
+
+/// Ask the calendars of pending attestations for their completed proofs
+///
+/// Attestations that are still pending are kept as they are, as are those of calendars that
+/// cannot be reached or answer with something else than a proof, the proof file being rewritten
+/// only when something changed.
+pub fn upgrade(transport: &dyn Transport, batch_path: &Path) -> Result<Upgrade, OtsError> {
+    let mut proof = read_proof(batch_path)?;
+    let mut rejected = Vec::new();
+    if upgrade_timestamp(transport, &mut proof.timestamp, &mut rejected) {
+        write_proof(batch_path, &proof)?;
+    }
+    Ok(Upgrade {
+        complete: proof.timestamp.is_complete(),
+        rejected,
+    })
+}
+
+fn upgrade_timestamp(
+    transport: &dyn Transport,
+    timestamp: &mut Timestamp,
+    rejected: &mut Vec<(String, OtsError)>,
+) -> bool {
+    let mut changed = false;
+    for (_, child) in timestamp.ops.iter_mut() {
+        changed |= upgrade_timestamp(transport, child, rejected);
+    }
+
+    let pending: Vec<String> = timestamp
+        .attestations
+        .iter()
+        .filter_map(|attestation| match attestation {
+            Attestation::Pending { uri } => Some(uri.clone()),
+            _ => None,
+        })
+        .collect();
+    for uri in pending {
+        let url = format!(
+            "{}/timestamp/{}",
+            uri.trim_end_matches('/'),
+            hex::encode(&timestamp.msg)
+        );
+        let response = match transport.get(&url) {
+            Ok(response) => response,
+            // not yet anchored, or calendar unreachable: try again later
+            Err(_) => continue,
+        };
+        let upgraded = match Timestamp::deserialize(timestamp.msg.clone(), &response) {
+            Ok(upgraded) => upgraded,
+            Err(err) => {
+                rejected.push((uri, err));
+                continue;
+            }
+        };
+        if !upgraded.is_complete() {
+            continue;
+        }
+        // merged into a copy, a failed merge leaving the timestamp as it was
+        let mut merged = timestamp.clone();
+        merged
+            .attestations
+            .retain(|attestation| *attestation != Attestation::Pending { uri: uri.clone() });
+        match merged.merge(upgraded) {
+            Ok(()) => *timestamp = merged,
+            Err(err) => {
+                rejected.push((uri, err));
+                continue;
+            }
+        }
+        changed = true;
+    }
+    changed
+}
+
+/// Check the proof of a batch, returning the heights of the blocks it is anchored in
+pub fn verify(batch_path: &Path, headers: &dyn BlockHeaders) -> Result<Vec<u64>, OtsError> {
+    let proof = read_proof(batch_path)?;
+    if batch_digest(batch_path)? != proof.digest() {
+        return Err(OtsError::DigestMismatch);
+    }
+    verify_timestamp(&proof.timestamp, headers)
+}
+
+/// Walk the operations of a timestamp and check every Bitcoin attestation against the chain
+pub fn verify_timestamp(
+    timestamp: &Timestamp,
+    headers: &dyn BlockHeaders,
+) -> Result<Vec<u64>, OtsError> {
+    let mut heights = Vec::new();
+    check_messages(timestamp)?;
+    for (msg, attestation) in timestamp.all_attestations() {
+        if let Attestation::Bitcoin { height } = attestation {
+            if headers.merkle_root(*height)? != msg {
+                return Err(OtsError::MerkleRootMismatch { height: *height });
+            }
+            heights.push(*height);
+        }
+    }
+    if heights.is_empty() {
+        return Err(OtsError::Pending);
+    }
+    Ok(heights)
+}
+
+// recompute every message from the root, so that a tampered intermediate message is caught
+fn check_messages(timestamp: &Timestamp) -> Result<(), OtsError> {
+    for (op, child) in &timestamp.ops {
+        if op.apply(&timestamp.msg)? != child.msg {
+            return Err(OtsError::Malformed("operation result mismatch"));
+        }
+        check_messages(child)?;
+    }
+    Ok(())
+}
+
+struct OtsReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> OtsReader<'a> {
+    fn byte(&mut self) -> Result<u8, OtsError> {
+        let (first, rest) = self.data.split_first().ok_or(OtsError::Truncated)?;
+        self.data = rest;
+        Ok(*first)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], OtsError> {
+        if self.data.len() < len {
+            return Err(OtsError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn varuint(&mut self) -> Result<u64, OtsError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as u64;
+            // only the lowest bit of the tenth byte still fits in 64 bits
+            if shift > 63 || (shift == 63 && bits > 1) {
+                return Err(OtsError::Malformed("varuint overflow"));
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn varbytes(&mut self, max_len: usize) -> Result<&'a [u8], OtsError> {
+        let len = self.varuint()? as usize;
+        if len > max_len {
+            return Err(OtsError::Malformed("field too long"));
+        }
+        self.bytes(len)
+    }
+
+    fn timestamp(&mut self, msg: Vec<u8>, depth: usize) -> Result<Timestamp, OtsError> {
+        if depth > MAX_RECURSION_DEPTH {
+            return Err(OtsError::Malformed("proof nested too deeply"));
+        }
+        let mut timestamp = Timestamp::new(msg);
+        loop {
+            let tag = self.byte()?;
+            let is_last = tag != TAG_FORK;
+            let tag = if is_last { tag } else { self.byte()? };
+            if tag == TAG_ATTESTATION {
+                timestamp.attestations.push(self.attestation()?);
+            } else {
+                let op = self.op(tag)?;
+                let child_msg = op.apply(&timestamp.msg)?;
+                let child = self.timestamp(child_msg, depth + 1)?;
+                timestamp.ops.push((op, child));
+            }
+            if is_last {
+                return Ok(timestamp);
+            }
+        }
+    }
+
+    fn op(&mut self, tag: u8) -> Result<Op, OtsError> {
+        Ok(match tag {
+            OP_SHA1 => Op::Sha1,
+            OP_RIPEMD160 => Op::Ripemd160,
+            OP_SHA256 => Op::Sha256,
+            OP_KECCAK256 => Op::Keccak256,
+            OP_APPEND => Op::Append(self.varbytes(MAX_OP_ARG_LENGTH)?.to_vec()),
+            OP_PREPEND => Op::Prepend(self.varbytes(MAX_OP_ARG_LENGTH)?.to_vec()),
+            OP_REVERSE => Op::Reverse,
+            OP_HEXLIFY => Op::Hexlify,
+            _ => return Err(OtsError::UnknownOp(tag)),
+        })
+    }
+
+    fn attestation(&mut self) -> Result<Attestation, OtsError> {
+        let tag: [u8; 8] = self.bytes(8)?.try_into().unwrap();
+        let payload = self.varbytes(8192)?;
+        let mut payload_reader = OtsReader { data: payload };
+        let attestation = match tag {
+            PENDING_TAG => {
+                let uri = payload_reader.varbytes(MAX_URI_LENGTH)?;
+                let uri = std::str::from_utf8(uri)
+                    .map_err(|_| OtsError::Malformed("non utf-8 calendar uri"))?;
+                Attestation::Pending {
+                    uri: uri.to_string(),
+                }
+            }
+            BITCOIN_TAG => Attestation::Bitcoin {
+                height: payload_reader.varuint()?,
+            },
+            _ => {
+                return Ok(Attestation::Unknown {
+                    tag,
+                    payload: payload.to_vec(),
+                })
+            }
+        };
+        if !payload_reader.data.is_empty() {
+            return Err(OtsError::Malformed("trailing attestation bytes"));
+        }
+        Ok(attestation)
+    }
+}
+
+fn write_attestation(attestation: &Attestation, out: &mut Vec<u8>) {
+    let mut payload = Vec::new();
+    let tag = match attestation {
+        Attestation::Pending { uri } => {
+            write_varbytes(uri.as_bytes(), &mut payload);
+            PENDING_TAG
+        }
+        Attestation::Bitcoin { height } => {
+            write_varuint(*height, &mut payload);
+            BITCOIN_TAG
+        }
+        Attestation::Unknown { tag, payload: raw } => {
+            payload.extend_from_slice(raw);
+            *tag
+        }
+    };
+    out.extend_from_slice(&tag);
+    write_varbytes(&payload, out);
+}
+
+fn write_varuint(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_varbytes(bytes: &[u8], out: &mut Vec<u8>) {
+    write_varuint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
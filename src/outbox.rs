@@ -0,0 +1,369 @@
+//! Durable outbox of signed records waiting to be sent over the network
+//!
+//! Records are appended to a spool file before anything is sent, one line each, numbered by an
+//! increasing sequence number:
+//!
+//! ```text
+//! <sequence number> <record>
+//! ```
+//!
+//! A cursor file next to the spool, `<spool>.sent`, holds the sequence number of the next record
+//! to send. The sender posts records in order and moves the cursor past a record only once the
+//! server answered it with a 2xx status, so records are delivered at least once: a sender killed
+//! between the answer and the cursor update sends that record again when it restarts, and the
+//! server has to tell duplicates apart, e.g. by signature.
+//!
+//! The spool is written by a single `Spool`, usually the signing process, and the cursor by a
+//! single sender, which may be another process. Sent records are removed by the `Spool` when it
+//! rewrites the file, a line never being removed before the cursor moved past it unless it was
+//! dropped by the overflow policy.
+//!
+//! Records can also be sent in bulk by `sync`, as track containers of up to a batch of records
+//! signed by the same device, see `track`. The cursor then moves past a whole container at
+//! once, so an interrupted sync resumes with the first container that was not accepted.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::synthetic::SplitMix64;
+use crate::track::{self, TrackError};
+use crate::transport::{Transport, TransportError};
+use crate::usage::replace_file;
+use crate::SignedPosition;
+
+#[derive(Debug)]
+pub enum OutboxError {
+    Io(io::Error),
+    /// Line number, counting from 1, that is not a spooled record
+    Malformed(usize),
+    MalformedCursor,
+    /// Sequence number of a spooled record that is not a signed position
+    MalformedRecord(u64),
+    Track(TrackError),
+    /// The spool holds as many unsent records as its limit allows
+    Full {
+        limit: usize,
+    },
+    /// A record was refused or could not be posted, after `sent` records were delivered
+    Delivery {
+        sent: usize,
+        err: TransportError,
+    },
+}
+
+impl fmt::Display for OutboxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutboxError::Io(err) => write!(f, "{}", err),
+            OutboxError::Malformed(line) => write!(f, "malformed spool line {}", line),
+            OutboxError::MalformedCursor => write!(f, "malformed spool cursor"),
+            OutboxError::MalformedRecord(sequence) => {
+                write!(f, "spooled record {} is not a signed position", sequence)
+            }
+            OutboxError::Track(err) => write!(f, "{}", err),
+            OutboxError::Full { limit } => {
+                write!(f, "spool is full, {} records are waiting to be sent", limit)
+            }
+            OutboxError::Delivery { sent, err } => {
+                write!(f, "delivery failed after {} records: {}", sent, err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OutboxError {}
+
+impl From<io::Error> for OutboxError {
+    fn from(err: io::Error) -> Self {
+        OutboxError::Io(err)
+    }
+}
+
+/// What a full spool does with a new record
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Overflow {
+    /// Refuse the new record, keeping the ones waiting
+    #[default]
+    RejectNewest,
+    /// Drop the oldest unsent record to make room
+    DropOldest,
+}
+
+/// Writing side of a spool
+pub struct Spool {
+    path: PathBuf,
+    file: File,
+    /// Lines of the file, oldest first, sent ones included until the next rewrite
+    lines: VecDeque<(u64, String)>,
+    next_sequence: u64,
+    limit: Option<usize>,
+    overflow: Overflow,
+    dropped: usize,
+}
+
+impl Spool {
+    /// Open a spool, created when it does not exist yet, of at most `limit` unsent records
+    ///
+    /// A last line cut short by a crash is removed: its record was never durably spooled.
+    pub fn open(
+        path: &Path,
+        limit: Option<usize>,
+        overflow: Overflow,
+    ) -> Result<Self, OutboxError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let complete = content.rfind('\n').map_or(0, |end| end + 1);
+        let lines = parse_lines(&content[..complete])?;
+        if complete != content.len() {
+            replace_file(path, &content.as_bytes()[..complete])?;
+        }
+        let cursor = read_cursor(&cursor_path(path))?;
+        let next_sequence = lines
+            .back()
+            .map_or(cursor, |(sequence, _)| cursor.max(sequence + 1));
+        Ok(Spool {
+            path: path.to_path_buf(),
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+            lines,
+            next_sequence,
+            limit,
+            overflow,
+            dropped: 0,
+        })
+    }
+
+    /// Append a record, on disk once this returns
+    pub fn push(&mut self, record: &str) -> Result<(), OutboxError> {
+        let cursor = read_cursor(&cursor_path(&self.path))?;
+        let sent = self
+            .lines
+            .iter()
+            .take_while(|(sequence, _)| *sequence < cursor)
+            .count();
+        let mut rewrite = sent > self.lines.len() - sent;
+        self.lines.drain(..sent);
+        if let Some(limit) = self.limit {
+            if self.lines.len() >= limit {
+                match self.overflow {
+                    Overflow::RejectNewest => return Err(OutboxError::Full { limit }),
+                    Overflow::DropOldest => {
+                        let excess = self.lines.len() + 1 - limit.max(1);
+                        self.lines.drain(..excess);
+                        self.dropped += excess;
+                        rewrite = true;
+                    }
+                }
+            }
+        }
+        if rewrite {
+            let content: String = self
+                .lines
+                .iter()
+                .map(|(sequence, record)| format!("{} {}\n", sequence, record))
+                .collect();
+            replace_file(&self.path, content.as_bytes())?;
+            self.file = OpenOptions::new().append(true).open(&self.path)?;
+        }
+
+        let sequence = self.next_sequence;
+        // whitespace in JSON, but a record spanning lines would be cut in two
+        let record = record.replace('\n', " ");
+        self.file
+            .write_all(format!("{} {}\n", sequence, record).as_bytes())?;
+        self.file.sync_data()?;
+        self.lines.push_back((sequence, record));
+        self.next_sequence += 1;
+        Ok(())
+    }
+
+    /// Number of unsent records dropped by the overflow policy so far
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+/// Records of a spool not sent yet, oldest first, with their sequence numbers
+pub fn pending(path: &Path) -> Result<Vec<(u64, String)>, OutboxError> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    // the last line may still be being written
+    let complete = content.rfind('\n').map_or(0, |end| end + 1);
+    let cursor = read_cursor(&cursor_path(path))?;
+    Ok(parse_lines(&content[..complete])?
+        .into_iter()
+        .filter(|(sequence, _)| *sequence >= cursor)
+        .collect())
+}
+
+/// Post the pending records of a spool to `url` in order, returning how many were delivered
+///
+/// Stops at the first record that is not delivered, which is sent again by the next call.
+pub fn deliver(path: &Path, transport: &dyn Transport, url: &str) -> Result<usize, OutboxError> {
+    let cursor = cursor_path(path);
+    let mut sent = 0;
+    for (sequence, record) in pending(path)? {
+        transport
+            .post(url, "application/json", record.as_bytes())
+            .map_err(|err| OutboxError::Delivery { sent, err })?;
+        replace_file(&cursor, format!("{}\n", sequence + 1).as_bytes())?;
+        sent += 1;
+    }
+    Ok(sent)
+}
+
+/// Post `record` to every sink at once, returning the sinks that did not accept it, in order,
+/// with their errors
+///
+/// Sinks are posted to in parallel, so that a sink timing out does not delay the others.
+pub fn broadcast<'a>(
+    record: &str,
+    transport: &(dyn Transport + Sync),
+    sinks: &[&'a str],
+) -> Vec<(&'a str, TransportError)> {
+    std::thread::scope(|scope| {
+        let posts: Vec<_> = sinks
+            .iter()
+            .map(|sink| {
+                scope.spawn(move || transport.post(sink, "application/json", record.as_bytes()))
+            })
+            .collect();
+        sinks
+            .iter()
+            .zip(posts)
+            .filter_map(|(sink, post)| match post.join() {
+                Ok(Ok(_)) => None,
+                Ok(Err(err)) => Some((*sink, err)),
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect()
+    })
+}
+
+/// Post the pending records of a spool to `url` as track containers of at most `batch` records,
+/// returning how many records were delivered
+///
+/// A container ends early where the device key changes. Stops at the first container that is
+/// not delivered: the next call starts with its records again, which makes running it again
+/// after an interruption safe.
+pub fn sync(
+    path: &Path,
+    transport: &dyn Transport,
+    url: &str,
+    batch: usize,
+) -> Result<usize, OutboxError> {
+    let cursor = cursor_path(path);
+    let records = pending(path)?
+        .into_iter()
+        .map(|(sequence, record)| {
+            let record: SignedPosition = serde_json::from_str(&record)
+                .map_err(|_| OutboxError::MalformedRecord(sequence))?;
+            Ok((sequence, record))
+        })
+        .collect::<Result<Vec<_>, OutboxError>>()?;
+    let mut sent = 0;
+    let mut rest = &records[..];
+    while let Some((_, first)) = rest.first() {
+        let length = rest
+            .iter()
+            .take(batch.max(1))
+            .take_while(|(_, record)| {
+                record.public_key == first.public_key && record.scheme == first.scheme
+            })
+            .count();
+        let (bundle, remaining) = rest.split_at(length);
+        let bundle_records: Vec<SignedPosition> =
+            bundle.iter().map(|(_, record)| record.clone()).collect();
+        let container = track::pack(&bundle_records, None, &[]).map_err(OutboxError::Track)?;
+        transport
+            .post(url, "application/octet-stream", &container)
+            .map_err(|err| OutboxError::Delivery { sent, err })?;
+        let (last, _) = bundle[length - 1];
+        replace_file(&cursor, format!("{}\n", last + 1).as_bytes())?;
+        sent += length;
+        rest = remaining;
+    }
+    Ok(sent)
+}
+
+/// Exponential backoff with jitter between delivery attempts
+///
+/// After `n` failures in a row the delay is drawn uniformly between half and all of
+/// `initial * 2^(n - 1)`, capped at `max`, so that senders cut off together do not retry
+/// together.
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    failures: u32,
+    random: SplitMix64,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration, seed: u64) -> Self {
+        Backoff {
+            initial,
+            max,
+            failures: 0,
+            random: SplitMix64(seed),
+        }
+    }
+
+    /// Delay before the next attempt, counting a failure
+    pub fn next_delay(&mut self) -> Duration {
+        let ceiling = self
+            .initial
+            .saturating_mul(1 << self.failures.min(31))
+            .min(self.max);
+        self.failures = self.failures.saturating_add(1);
+        ceiling.mul_f64(0.5 + 0.5 * self.random.next_f64())
+    }
+
+    /// Failures in a row so far
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Start over after a successful attempt
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+fn cursor_path(path: &Path) -> PathBuf {
+    let mut cursor = path.as_os_str().to_owned();
+    cursor.push(".sent");
+    PathBuf::from(cursor)
+}
+
+fn read_cursor(path: &Path) -> Result<u64, OutboxError> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => content
+            .trim()
+            .parse()
+            .map_err(|_| OutboxError::MalformedCursor),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn parse_lines(content: &str) -> Result<VecDeque<(u64, String)>, OutboxError> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| {
+            line.split_once(' ')
+                .and_then(|(sequence, record)| Some((sequence.parse().ok()?, record.to_string())))
+                .ok_or(OutboxError::Malformed(index + 1))
+        })
+        .collect()
+}
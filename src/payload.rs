@@ -0,0 +1,117 @@
+//! Signatures over arbitrary payloads taken at a position, e.g. a sensor reading
+//!
+//! The signed digest binds the position and the payload hash together:
+//!
+//! ```text
+//! SHA-256( len(tag) || tag || len(position) || position || len(payload_sha256) || payload_sha256 )
+//! ```
+//!
+//! where every length is a 4 bytes big endian integer, and `tag` and `position` depend on the
+//! version of the record, as for `SignedPosition`:
+//!
+//! - version 1: the tag `sign_GPS_coords/payload` and the JSON serialized position
+//! - version 2: the tag `sign_GPS_coords/payload/v2` and the binary encoding of `wire`
+//!
+//! The tag keeps these signatures distinct from plain position signatures, and from the
+//! signatures of another version.
+
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::scheme::{Scheme, Signer, VerifyingKey};
+use crate::{wire, Position, VerifyError, CURRENT_VERSION};
+
+const DOMAIN_TAG: &[u8] = b"sign_GPS_coords/payload";
+const DOMAIN_TAG_V2: &[u8] = b"sign_GPS_coords/payload/v2";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedPayload {
+    /// Signing rules of the record, records predating this field are version 1
+    #[serde(default = "crate::legacy_version")]
+    pub version: u8,
+    pub position: Position,
+    /// Hex encoded SHA-256 of the payload, the payload itself is not embedded
+    pub payload_sha256: String,
+    pub signature: String,
+    pub public_key: String,
+    #[serde(default)]
+    pub scheme: Scheme,
+}
+
+/// Digest signed for a payload hash taken at a position, under the rules of `version`
+pub fn payload_digest(
+    position: &Position,
+    payload_sha256: &[u8],
+    version: u8,
+) -> Result<[u8; 32], VerifyError> {
+    let (tag, position) = match version {
+        1 => (
+            DOMAIN_TAG,
+            serde_json::to_vec(position).expect("JSON serialization"),
+        ),
+        2 => (
+            DOMAIN_TAG_V2,
+            wire::encode(position)
+                .map_err(|err| VerifyError::MalformedCoordinate(err.to_string()))?,
+        ),
+        _ => return Err(VerifyError::UnsupportedVersion(version)),
+    };
+    let mut hasher = sha2::Sha256::new();
+    for field in [tag, &position, payload_sha256] {
+        hasher.update((field.len() as u32).to_be_bytes());
+        hasher.update(field);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Sign a payload taken at a position under the current version
+///
+/// # Panics
+///
+/// If the coordinates cannot be encoded, see `wire::encode`.
+pub fn sign_payload_at(position: Position, payload: &[u8], signer: &dyn Signer) -> SignedPayload {
+    try_sign_payload_at(position, payload, CURRENT_VERSION, signer).expect("coordinates in range")
+}
+
+/// Sign a payload taken at a position under the rules of `version`, failing on unknown versions
+/// and positions that version cannot hash
+pub fn try_sign_payload_at(
+    position: Position,
+    payload: &[u8],
+    version: u8,
+    signer: &dyn Signer,
+) -> Result<SignedPayload, VerifyError> {
+    let payload_sha256 = sha2::Sha256::digest(payload);
+    let digest = payload_digest(&position, &payload_sha256, version)?;
+    Ok(SignedPayload {
+        version,
+        position,
+        payload_sha256: hex::encode(payload_sha256),
+        signature: signer.sign_digest(&digest),
+        public_key: signer.public_key(),
+        scheme: signer.scheme(),
+    })
+}
+
+/// Verify a record against the original payload
+pub fn verify_payload(record: &SignedPayload, payload: &[u8]) -> Result<(), VerifyError> {
+    verify_payload_hash(record, &sha2::Sha256::digest(payload))
+}
+
+/// Verify a record against the SHA-256 of the original payload
+pub fn verify_payload_hash(
+    record: &SignedPayload,
+    payload_sha256: &[u8],
+) -> Result<(), VerifyError> {
+    if hex::decode(&record.payload_sha256).ok().as_deref() != Some(payload_sha256) {
+        return Err(VerifyError::PayloadMismatch);
+    }
+    let digest = payload_digest(&record.position, payload_sha256, record.version)?;
+    let public_key = VerifyingKey::parse(record.scheme, &record.public_key)?;
+    if !public_key.verify(&digest, &record.signature)? {
+        return Err(VerifyError::InvalidSignature {
+            public_key: record.public_key.clone(),
+        });
+    }
+    Ok(())
+}
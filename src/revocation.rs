@@ -0,0 +1,172 @@
+//! Revocation lists of compromised device keys
+//!
+//! A list is issued by a certificate authority key trusted by verifiers. Each entry names a
+//! device key and the time it was compromised: records of that key taken at or after the time
+//! are rejected, earlier ones stay valid. The list is JSON, its signature covering this layout:
+//!
+//! ```text
+//! SHA-256("revocations" || version (1 byte, 1) || issued at (8 bytes) || count (4 bytes)
+//!         || for each entry, in order: scheme (1 byte, 0 for secp256k1, 1 for P-256)
+//!            || compressed public key (33 bytes) || revoked at (8 bytes))
+//! ```
+//!
+//! Integers are big endian, times unix seconds.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::scheme::{Scheme, Signer, VerifyingKey};
+use crate::{SignedPosition, VerifyError};
+
+/// Domain separating list digests from record digests
+const DOMAIN: &[u8] = b"revocations";
+
+pub const LIST_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Revocation {
+    /// Hex encoded compressed public key
+    pub public_key: String,
+    #[serde(default)]
+    pub scheme: Scheme,
+    /// Records taken at or after this time are rejected
+    pub revoked_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RevocationList {
+    pub version: u8,
+    pub issued_at: u64,
+    pub revocations: Vec<Revocation>,
+    /// Hex encoded public key of the issuer
+    pub issuer: String,
+    #[serde(default)]
+    pub issuer_scheme: Scheme,
+    pub signature: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RevocationError {
+    UnsupportedVersion(u8),
+    /// The list is not issued by the trusted authority
+    UntrustedIssuer,
+    BadSignature,
+    Verify(VerifyError),
+}
+
+impl fmt::Display for RevocationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RevocationError::UnsupportedVersion(version) => {
+                write!(f, "unsupported revocation list version {}", version)
+            }
+            RevocationError::UntrustedIssuer => {
+                write!(f, "revocation list is not issued by the trusted authority")
+            }
+            RevocationError::BadSignature => write!(f, "bad revocation list signature"),
+            RevocationError::Verify(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for RevocationError {}
+
+impl From<VerifyError> for RevocationError {
+    fn from(err: VerifyError) -> Self {
+        RevocationError::Verify(err)
+    }
+}
+
+impl RevocationList {
+    /// List of `revocations` signed by the authority, keys being normalized to compressed hex
+    pub fn issue(
+        issued_at: u64,
+        revocations: Vec<Revocation>,
+        authority: &dyn Signer,
+    ) -> Result<Self, RevocationError> {
+        let revocations = revocations
+            .into_iter()
+            .map(|revocation| {
+                Ok(Revocation {
+                    public_key: VerifyingKey::parse(revocation.scheme, &revocation.public_key)?
+                        .to_hex(),
+                    ..revocation
+                })
+            })
+            .collect::<Result<Vec<_>, RevocationError>>()?;
+        let mut list = RevocationList {
+            version: LIST_VERSION,
+            issued_at,
+            revocations,
+            issuer: authority.public_key(),
+            issuer_scheme: authority.scheme(),
+            signature: String::new(),
+        };
+        list.signature = authority.sign_digest(&list.digest()?);
+        Ok(list)
+    }
+
+    /// Check that the list is signed by `authority`, before trusting any entry
+    pub fn verify(&self, authority: &VerifyingKey) -> Result<(), RevocationError> {
+        if self.version != LIST_VERSION {
+            return Err(RevocationError::UnsupportedVersion(self.version));
+        }
+        let issuer = VerifyingKey::parse(self.issuer_scheme, &self.issuer)?;
+        if issuer != *authority {
+            return Err(RevocationError::UntrustedIssuer);
+        }
+        if !issuer.verify(&self.digest()?, &self.signature)? {
+            return Err(RevocationError::BadSignature);
+        }
+        Ok(())
+    }
+
+    /// Reject a record taken after a key that signed it was revoked
+    ///
+    /// Co-signing keys are checked as well as the device key, and the device key endorsing a
+    /// session key, see `session`, as well as the session key. The list must have been verified.
+    pub fn check(&self, record: &SignedPosition) -> Result<(), VerifyError> {
+        let endorsing = record
+            .endorsement
+            .iter()
+            .map(|endorsement| (endorsement.device_scheme, endorsement.device_key.as_str()));
+        let signing = record
+            .signatures()
+            .map(|(scheme, public_key, _)| (scheme, public_key));
+        for (scheme, public_key) in signing.chain(endorsing) {
+            let key = VerifyingKey::parse(scheme, public_key)?;
+            let revoked = self.revocations.iter().find(|revocation| {
+                record.position.timestamp >= revocation.revoked_at
+                    && VerifyingKey::parse(revocation.scheme, &revocation.public_key).ok()
+                        == Some(key)
+            });
+            if let Some(revocation) = revoked {
+                return Err(VerifyError::Revoked {
+                    public_key: public_key.to_string(),
+                    revoked_at: revocation.revoked_at,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn digest(&self) -> Result<[u8; 32], VerifyError> {
+        let mut hasher = Sha256::new();
+        hasher.update(DOMAIN);
+        hasher.update([self.version]);
+        hasher.update(self.issued_at.to_be_bytes());
+        hasher.update((self.revocations.len() as u32).to_be_bytes());
+        for revocation in &self.revocations {
+            let key = VerifyingKey::parse(revocation.scheme, &revocation.public_key)?;
+            hasher.update([match revocation.scheme {
+                Scheme::Secp256k1 => 0,
+                Scheme::P256 => 1,
+            }]);
+            hasher.update(hex::decode(key.to_hex()).expect("hex encoded key"));
+            hasher.update(revocation.revoked_at.to_be_bytes());
+        }
+        Ok(hasher.finalize().into())
+    }
+}
@@ -0,0 +1,224 @@
+//! Signature schemes used to sign the position hash
+//!
+//! Every scheme signs the same 32 bytes SHA-256 digest of the position, and carries its public
+//! key and signature as hex strings: SEC1 compressed points (33 bytes) and compact `r || s`
+//! signatures (64 bytes). The scheme of a signature is recorded next to it, so that verifiers
+//! know how to interpret those bytes. Verification also accepts base64, see `encoding`.
+//!
+//! Only secp256k1 signatures are used for proving, P-256 is there for devices whose secure
+//! hardware only exposes NIST keys.
+
+use std::fmt;
+
+use hex::ToHex;
+use p256::ecdsa::signature::hazmat::{PrehashSigner, PrehashVerifier};
+use p256::pkcs8::DecodePrivateKey;
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::{self, PUBLIC_KEY_LENGTHS, SIGNATURE_LENGTH};
+use crate::{sign_hash_slice, verify_signature, VerifyError};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    /// Records without a scheme tag predate P-256 support, and are secp256k1
+    #[default]
+    Secp256k1,
+    P256,
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scheme::Secp256k1 => write!(f, "secp256k1"),
+            Scheme::P256 => write!(f, "p256"),
+        }
+    }
+}
+
+/// Where a signing key is kept, as claimed by its signer
+///
+/// The claim is not covered by the signature: verifiers that rely on it must check the platform
+/// key attestation of the device key out of band.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyProvenance {
+    /// Records without a provenance were signed with keys held in memory
+    #[default]
+    Software,
+    AndroidKeystore,
+    /// Android Keystore backed by a StrongBox secure element
+    AndroidStrongbox,
+    AppleSecureEnclave,
+}
+
+impl KeyProvenance {
+    pub fn is_software(&self) -> bool {
+        *self == KeyProvenance::Software
+    }
+}
+
+impl fmt::Display for KeyProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyProvenance::Software => write!(f, "software"),
+            KeyProvenance::AndroidKeystore => write!(f, "android_keystore"),
+            KeyProvenance::AndroidStrongbox => write!(f, "android_strongbox"),
+            KeyProvenance::AppleSecureEnclave => write!(f, "apple_secure_enclave"),
+        }
+    }
+}
+
+pub trait Signer {
+    fn scheme(&self) -> Scheme;
+
+    fn provenance(&self) -> KeyProvenance {
+        KeyProvenance::Software
+    }
+
+    /// Hex encoded SEC1 compressed public key
+    fn public_key(&self) -> String;
+
+    /// Hex encoded compact signature over a 32 bytes digest
+    fn sign_digest(&self, digest: &[u8]) -> String;
+}
+
+impl Signer for secp256k1::SecretKey {
+    fn scheme(&self) -> Scheme {
+        Scheme::Secp256k1
+    }
+
+    fn public_key(&self) -> String {
+        let secp = secp256k1::Secp256k1::new();
+        let public_key = secp256k1::PublicKey::from_secret_key(&secp, self);
+        public_key.serialize().encode_hex::<String>()
+    }
+
+    fn sign_digest(&self, digest: &[u8]) -> String {
+        sign_hash_slice(self, digest)
+            .serialize_compact()
+            .encode_hex::<String>()
+    }
+}
+
+impl<S: Signer + ?Sized> Signer for &S {
+    fn scheme(&self) -> Scheme {
+        (**self).scheme()
+    }
+
+    fn provenance(&self) -> KeyProvenance {
+        (**self).provenance()
+    }
+
+    fn public_key(&self) -> String {
+        (**self).public_key()
+    }
+
+    fn sign_digest(&self, digest: &[u8]) -> String {
+        (**self).sign_digest(digest)
+    }
+}
+
+impl Signer for p256::ecdsa::SigningKey {
+    fn scheme(&self) -> Scheme {
+        Scheme::P256
+    }
+
+    fn public_key(&self) -> String {
+        self.verifying_key()
+            .to_encoded_point(true)
+            .as_bytes()
+            .encode_hex::<String>()
+    }
+
+    fn sign_digest(&self, digest: &[u8]) -> String {
+        let signature: p256::ecdsa::Signature = self.sign_prehash(digest).expect("32 bytes");
+        signature.to_bytes().encode_hex::<String>()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyError {
+    InvalidPem,
+    Unrecognized,
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyError::InvalidPem => write!(f, "invalid PEM encoded key"),
+            KeyError::Unrecognized => write!(f, "expected a SEC1 or PKCS#8 P-256 private key"),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+/// Load a P-256 private key, either PEM or DER, in SEC1 or PKCS#8 form
+pub fn load_p256_key(data: &[u8]) -> Result<p256::ecdsa::SigningKey, KeyError> {
+    let secret_key = if data.starts_with(b"-----BEGIN") {
+        let pem = std::str::from_utf8(data).map_err(|_| KeyError::InvalidPem)?;
+        p256::SecretKey::from_pkcs8_pem(pem)
+            .or_else(|_| p256::SecretKey::from_sec1_pem(pem))
+            .map_err(|_| KeyError::Unrecognized)?
+    } else {
+        p256::SecretKey::from_pkcs8_der(data)
+            .or_else(|_| p256::SecretKey::from_sec1_der(data))
+            .map_err(|_| KeyError::Unrecognized)?
+    };
+    Ok(secret_key.into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyingKey {
+    Secp256k1(secp256k1::PublicKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl VerifyingKey {
+    pub fn parse(scheme: Scheme, public_key: &str) -> Result<Self, VerifyError> {
+        let malformed = || VerifyError::MalformedPublicKey(public_key.to_string());
+        let (bytes, _) = encoding::decode(public_key, &PUBLIC_KEY_LENGTHS).ok_or_else(malformed)?;
+        match scheme {
+            Scheme::Secp256k1 => secp256k1::PublicKey::from_slice(&bytes)
+                .map(VerifyingKey::Secp256k1)
+                .map_err(|_| malformed()),
+            Scheme::P256 => p256::ecdsa::VerifyingKey::from_sec1_bytes(&bytes)
+                .map(VerifyingKey::P256)
+                .map_err(|_| malformed()),
+        }
+    }
+
+    pub fn scheme(&self) -> Scheme {
+        match self {
+            VerifyingKey::Secp256k1(_) => Scheme::Secp256k1,
+            VerifyingKey::P256(_) => Scheme::P256,
+        }
+    }
+
+    /// Hex encoded SEC1 compressed form, as carried in records
+    pub fn to_hex(&self) -> String {
+        match self {
+            VerifyingKey::Secp256k1(key) => key.serialize().encode_hex(),
+            VerifyingKey::P256(key) => key.to_encoded_point(true).as_bytes().encode_hex(),
+        }
+    }
+
+    /// Check a hex or base64 encoded compact signature over a 32 bytes digest
+    pub fn verify(&self, digest: &[u8], signature: &str) -> Result<bool, VerifyError> {
+        let malformed = || VerifyError::MalformedSignature(signature.to_string());
+        let (bytes, _) = encoding::decode(signature, &[SIGNATURE_LENGTH]).ok_or_else(malformed)?;
+        match self {
+            VerifyingKey::Secp256k1(public_key) => {
+                let signature =
+                    secp256k1::ecdsa::Signature::from_compact(&bytes).map_err(|_| malformed())?;
+                Ok(verify_signature(public_key, &signature, digest))
+            }
+            VerifyingKey::P256(public_key) => {
+                let signature =
+                    p256::ecdsa::Signature::from_slice(&bytes).map_err(|_| malformed())?;
+                Ok(public_key.verify_prehash(digest, &signature).is_ok())
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Ephemeral session keys endorsed by the long-term device key
+//!
+//! Rather than keeping the device key in memory for every fix, a device draws a session key when
+//! it starts and has the device key sign an endorsement of it, valid over a window of time. Fixes
+//! are then signed with the session key, each record carrying the endorsement, and the device key
+//! can be dropped. Verifiers check the endorsement against the device key it names, and that the
+//! record was taken within the window, bounds included. The endorsement signature covers:
+//!
+//! ```text
+//! SHA-256("session" || version (1 byte, 1) || session scheme (1 byte, 0 for secp256k1, 1 for P-256)
+//!         || compressed session key (33 bytes) || not before (8 bytes) || not after (8 bytes)
+//!         || device id length (2 bytes) || device id, UTF-8)
+//! ```
+//!
+//! Integers are big endian, times unix seconds. Keys and identities checked against a trusted
+//! set, or a revocation list, are those of the device key.
+
+use std::fmt;
+
+use p256::elliptic_curve::rand_core::{OsRng, RngCore};
+use secp256k1::{Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::scheme::{Scheme, Signer, VerifyingKey};
+use crate::{SignedPosition, VerifyError};
+
+/// Domain separating endorsement digests from record digests
+const DOMAIN: &[u8] = b"session";
+
+pub const ENDORSEMENT_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Endorsement {
+    pub version: u8,
+    /// Hex encoded compressed session key, the key records are signed with
+    pub session_key: String,
+    #[serde(default)]
+    pub session_scheme: Scheme,
+    pub not_before: u64,
+    pub not_after: u64,
+    /// Identity the device is registered under
+    pub device_id: String,
+    /// Hex encoded long-term device key, which signed the endorsement
+    pub device_key: String,
+    #[serde(default)]
+    pub device_scheme: Scheme,
+    pub signature: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionError {
+    /// The window ends before it starts
+    EmptyWindow,
+    /// The device id does not fit its length prefix
+    DeviceIdTooLong,
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::EmptyWindow => write!(f, "session ends before it starts"),
+            SessionError::DeviceIdTooLong => write!(f, "device id is too long"),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl Endorsement {
+    /// Check that the device key endorsed the session key of `record` at the time of the record
+    pub fn check(&self, record: &SignedPosition) -> Result<(), VerifyError> {
+        if self.version != ENDORSEMENT_VERSION {
+            return Err(VerifyError::UnsupportedVersion(self.version));
+        }
+        let session_key = VerifyingKey::parse(self.session_scheme, &self.session_key)?;
+        if VerifyingKey::parse(record.scheme, &record.public_key)? != session_key {
+            return Err(VerifyError::NotEndorsed {
+                public_key: record.public_key.clone(),
+            });
+        }
+        let device_key = VerifyingKey::parse(self.device_scheme, &self.device_key)?;
+        let digest = digest(
+            &session_key,
+            self.not_before,
+            self.not_after,
+            &self.device_id,
+        )
+        .ok_or_else(|| VerifyError::InvalidEndorsement {
+            device_key: self.device_key.clone(),
+        })?;
+        if !device_key.verify(&digest, &self.signature)? {
+            return Err(VerifyError::InvalidEndorsement {
+                device_key: self.device_key.clone(),
+            });
+        }
+        let timestamp = record.position.timestamp;
+        if timestamp < self.not_before || timestamp > self.not_after {
+            return Err(VerifyError::OutsideSession {
+                timestamp,
+                not_before: self.not_before,
+                not_after: self.not_after,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Key standing for the device that signed a record: the endorsing device key when the record
+/// carries a valid endorsement, its own key otherwise
+pub fn device_key(record: &SignedPosition) -> Result<VerifyingKey, VerifyError> {
+    match &record.endorsement {
+        Some(endorsement) => {
+            endorsement.check(record)?;
+            VerifyingKey::parse(endorsement.device_scheme, &endorsement.device_key)
+        }
+        None => VerifyingKey::parse(record.scheme, &record.public_key),
+    }
+}
+
+/// Software secp256k1 session key with its endorsement
+pub struct Session {
+    secret_key: SecretKey,
+    endorsement: Endorsement,
+}
+
+impl Session {
+    /// Draw a session key from the operating system and have `device` endorse it for
+    /// `[not_before, not_after]`
+    pub fn start(
+        device: &dyn Signer,
+        device_id: &str,
+        not_before: u64,
+        not_after: u64,
+    ) -> Result<Self, SessionError> {
+        if not_after < not_before {
+            return Err(SessionError::EmptyWindow);
+        }
+        let secret_key = loop {
+            let mut bytes = [0; 32];
+            OsRng.fill_bytes(&mut bytes);
+            if let Ok(secret_key) = SecretKey::from_slice(&bytes) {
+                break secret_key;
+            }
+        };
+        let session_key = VerifyingKey::Secp256k1(secret_key.public_key(&Secp256k1::new()));
+        let digest = digest(&session_key, not_before, not_after, device_id)
+            .ok_or(SessionError::DeviceIdTooLong)?;
+        let endorsement = Endorsement {
+            version: ENDORSEMENT_VERSION,
+            session_key: session_key.to_hex(),
+            session_scheme: Scheme::Secp256k1,
+            not_before,
+            not_after,
+            device_id: device_id.to_string(),
+            device_key: device.public_key(),
+            device_scheme: device.scheme(),
+            signature: device.sign_digest(&digest),
+        };
+        Ok(Session {
+            secret_key,
+            endorsement,
+        })
+    }
+
+    /// Endorsement to carry in every record signed with the session key
+    pub fn endorsement(&self) -> &Endorsement {
+        &self.endorsement
+    }
+}
+
+impl Signer for Session {
+    fn scheme(&self) -> Scheme {
+        Scheme::Secp256k1
+    }
+
+    fn public_key(&self) -> String {
+        self.endorsement.session_key.clone()
+    }
+
+    fn sign_digest(&self, digest: &[u8]) -> String {
+        self.secret_key.sign_digest(digest)
+    }
+}
+
+fn digest(
+    session_key: &VerifyingKey,
+    not_before: u64,
+    not_after: u64,
+    device_id: &str,
+) -> Option<[u8; 32]> {
+    let device_id_length = u16::try_from(device_id.len()).ok()?;
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN);
+    hasher.update([ENDORSEMENT_VERSION]);
+    hasher.update([match session_key.scheme() {
+        Scheme::Secp256k1 => 0,
+        Scheme::P256 => 1,
+    }]);
+    hasher.update(hex::decode(session_key.to_hex()).expect("hex encoded key"));
+    hasher.update(not_before.to_be_bytes());
+    hasher.update(not_after.to_be_bytes());
+    hasher.update(device_id_length.to_be_bytes());
+    hasher.update(device_id.as_bytes());
+    Some(hasher.finalize().into())
+}
@@ -0,0 +1,368 @@
+//! Sources of fixes and the pipeline signing them as they come
+//!
+//! A `PositionSource` hands out fixes one at a time, blocking until the next one is available.
+//! Besides JSON positions read from a stream such as stdin, or the reports of a gpsd daemon,
+//! `RoutePlayback` replays a GPX or CSV route, so that the pipeline can run without a receiver:
+//! at the pace of the route times, sped up, or as fast as possible. Route points without a time
+//! are given one by interpolation.
+//!
+//! gpsd is read over its JSON protocol, by default on `localhost:2947`: after a `?WATCH` command
+//! it sends one JSON object per line, of which the `TPV` reports with a 2D or 3D fix carry
+//! positions. Their altitude is `altHAE`, above the WGS-84 ellipsoid.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::chain;
+use crate::clock::Clock;
+use crate::csv::{read_csv, CsvError};
+use crate::gpx::{read_gpx, GpxError, GpxPoint};
+use crate::scheme::Signer;
+use crate::time::parse_iso8601;
+use crate::wire::{self, WireError};
+use crate::{sign_position, Position, SignedPosition};
+
+pub trait PositionSource {
+    /// Next fix, `None` once the source is exhausted
+    fn next_fix(&mut self) -> Option<Position>;
+}
+
+#[derive(Debug)]
+pub enum SourceError {
+    Io(io::Error),
+    Gpx(GpxError),
+    Csv(CsvError),
+    /// Routes are read from `.gpx` or `.csv` files
+    UnknownFormat,
+    EmptyRoute,
+    /// A fix that cannot be signed, counting from 0
+    Fix {
+        index: usize,
+        err: WireError,
+    },
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceError::Io(err) => write!(f, "{}", err),
+            SourceError::Gpx(err) => write!(f, "{}", err),
+            SourceError::Csv(err) => write!(f, "{}", err),
+            SourceError::UnknownFormat => write!(f, "expected a .gpx or .csv route"),
+            SourceError::EmptyRoute => write!(f, "route has no points"),
+            SourceError::Fix { index, err } => write!(f, "fix {}: {}", index, err),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+impl From<io::Error> for SourceError {
+    fn from(err: io::Error) -> Self {
+        SourceError::Io(err)
+    }
+}
+
+/// Fixes given upfront, for driving the pipeline deterministically
+#[derive(Debug, Default)]
+pub struct MemorySource {
+    fixes: VecDeque<Position>,
+}
+
+impl From<Vec<Position>> for MemorySource {
+    fn from(fixes: Vec<Position>) -> Self {
+        MemorySource {
+            fixes: fixes.into(),
+        }
+    }
+}
+
+impl PositionSource for MemorySource {
+    fn next_fix(&mut self) -> Option<Position> {
+        self.fixes.pop_front()
+    }
+}
+
+/// Port gpsd listens on by default
+pub const GPSD_PORT: u16 = 2947;
+
+/// One JSON object per line, lines that are not positions being skipped
+///
+/// Reading stops at the first error of the reader, including lines that are not UTF-8, which is
+/// kept for `take_error` rather than taken for the end of the stream.
+pub struct JsonLinesSource<R> {
+    reader: R,
+    format: LineFormat,
+    skipped: usize,
+    err: Option<io::Error>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LineFormat {
+    /// A serialized `Position` per line
+    Position,
+    /// gpsd reports, of which only `TPV` ones are read
+    Gpsd,
+}
+
+/// Time-position-velocity report of gpsd, the fields used of it
+#[derive(Deserialize)]
+struct Report {
+    class: String,
+    #[serde(default)]
+    mode: u8,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    #[serde(rename = "altHAE")]
+    alt_hae: Option<f64>,
+    time: Option<String>,
+}
+
+impl Report {
+    /// Position of a report with a 2D or 3D fix and a time
+    fn position(&self) -> Option<Position> {
+        if self.mode < 2 {
+            return None;
+        }
+        Some(Position {
+            latitude: self.lat?,
+            longitude: self.lon?,
+            timestamp: parse_iso8601(self.time.as_deref()?)?,
+            altitude: self.alt_hae,
+            prev_hash: None,
+            expires_at: None,
+            dwell_count: None,
+            last_seen: None,
+        })
+    }
+}
+
+impl<R: BufRead> JsonLinesSource<R> {
+    pub fn new(reader: R) -> Self {
+        JsonLinesSource::with_format(reader, LineFormat::Position)
+    }
+
+    /// Reports of gpsd once watching, reports without a fix being skipped
+    pub fn gpsd(reader: R) -> Self {
+        JsonLinesSource::with_format(reader, LineFormat::Gpsd)
+    }
+
+    fn with_format(reader: R, format: LineFormat) -> Self {
+        JsonLinesSource {
+            reader,
+            format,
+            skipped: 0,
+            err: None,
+        }
+    }
+
+    /// Number of lines skipped so far
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Error that ended the stream, if it did not end cleanly
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.err.take()
+    }
+
+    fn parse(&mut self, line: &str) -> Option<Position> {
+        match self.format {
+            LineFormat::Position => match serde_json::from_str(line) {
+                Ok(position) => return Some(position),
+                Err(_) => self.skipped += 1,
+            },
+            LineFormat::Gpsd => match serde_json::from_str::<Report>(line) {
+                // version, device and satellite reports come between fixes
+                Ok(report) if report.class != "TPV" => {}
+                Ok(report) => match report.position() {
+                    Some(position) => return Some(position),
+                    None => self.skipped += 1,
+                },
+                Err(_) => self.skipped += 1,
+            },
+        }
+        None
+    }
+}
+
+/// Connect to gpsd at `address`, e.g. `localhost:2947`, and start watching its reports, to be
+/// read with `JsonLinesSource::gpsd`
+pub fn connect_gpsd(address: &str) -> io::Result<io::BufReader<TcpStream>> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.write_all(b"?WATCH={\"enable\":true,\"json\":true};\n")?;
+    Ok(io::BufReader::new(stream))
+}
+
+impl<R: BufRead> PositionSource for JsonLinesSource<R> {
+    fn next_fix(&mut self) -> Option<Position> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.err = Some(err);
+                    return None;
+                }
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(position) = self.parse(&line) {
+                return Some(position);
+            }
+        }
+    }
+}
+
+/// Pace of a route playback
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// Route seconds per wall clock second, 1 for real time
+    Factor(f64),
+    AsFastAsPossible,
+}
+
+impl std::str::FromStr for Speed {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "max" => Ok(Speed::AsFastAsPossible),
+            _ => match text.parse::<f64>() {
+                Ok(factor) if factor.is_finite() && factor > 0.0 => Ok(Speed::Factor(factor)),
+                _ => Err(format!("invalid speed {}, expected a factor or max", text)),
+            },
+        }
+    }
+}
+
+pub struct RoutePlayback {
+    fixes: VecDeque<Position>,
+    speed: Speed,
+    /// Route time and instant of the first fix handed out
+    start: Option<(u64, Instant)>,
+}
+
+impl RoutePlayback {
+    /// Route of points, those without a time being interpolated from their neighbours
+    ///
+    /// Points before the first timed one, or after the last one, are one second apart. When no
+    /// point has a time, the route starts at the time of `clock`.
+    pub fn new(points: &[GpxPoint], speed: Speed, clock: &dyn Clock) -> Result<Self, SourceError> {
+        if points.is_empty() {
+            return Err(SourceError::EmptyRoute);
+        }
+        let times = interpolate(points, clock.now_unix_secs());
+        let fixes = points
+            .iter()
+            .zip(times)
+            .map(|(point, time)| point.to_position(time))
+            .collect();
+        Ok(RoutePlayback {
+            fixes,
+            speed,
+            start: None,
+        })
+    }
+
+    /// Read a route from a `.gpx` or `.csv` file
+    pub fn open(path: &Path, speed: Speed, clock: &dyn Clock) -> Result<Self, SourceError> {
+        let document = std::fs::read_to_string(path)?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let points = match extension.as_deref() {
+            Some("gpx") => read_gpx(&document).map_err(SourceError::Gpx)?,
+            Some("csv") => read_csv(&document).map_err(SourceError::Csv)?,
+            _ => return Err(SourceError::UnknownFormat),
+        };
+        RoutePlayback::new(&points, speed, clock)
+    }
+}
+
+impl PositionSource for RoutePlayback {
+    fn next_fix(&mut self) -> Option<Position> {
+        let fix = self.fixes.pop_front()?;
+        if let Speed::Factor(factor) = self.speed {
+            match self.start {
+                Some((start_time, started)) => {
+                    let route_elapsed = fix.timestamp.saturating_sub(start_time) as f64;
+                    let due = started + Duration::from_secs_f64(route_elapsed / factor);
+                    std::thread::sleep(due.saturating_duration_since(Instant::now()));
+                }
+                None => self.start = Some((fix.timestamp, Instant::now())),
+            }
+        }
+        Some(fix)
+    }
+}
+
+/// Sign every fix of a source, chained after `head` when given, handing each record to `emit`
+///
+/// Returns the number of records signed.
+pub fn sign_stream(
+    source: &mut dyn PositionSource,
+    signer: &dyn Signer,
+    mut head: Option<&mut [u8; 32]>,
+    mut emit: impl FnMut(&SignedPosition, Option<&[u8; 32]>) -> io::Result<()>,
+) -> Result<usize, SourceError> {
+    let mut count = 0;
+    while let Some(position) = source.next_fix() {
+        wire::encode(&position).map_err(|err| SourceError::Fix { index: count, err })?;
+        let signed_position = match head.as_deref_mut() {
+            Some(head) => chain::sign_linked(position, head, signer),
+            None => sign_position(position, signer),
+        };
+        emit(&signed_position, head.as_deref())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn interpolate(points: &[GpxPoint], default_start: u64) -> Vec<u64> {
+    let timed: Vec<(usize, u64)> = points
+        .iter()
+        .enumerate()
+        .filter_map(|(index, point)| Some((index, point.time?)))
+        .collect();
+    let (Some(&(first_index, first_time)), Some(&(last_index, last_time))) =
+        (timed.first(), timed.last())
+    else {
+        return (0..points.len() as u64)
+            .map(|offset| default_start.saturating_add(offset))
+            .collect();
+    };
+
+    let mut times = Vec::with_capacity(points.len());
+    for index in 0..points.len() {
+        let time = if index <= first_index {
+            first_time.saturating_sub((first_index - index) as u64)
+        } else if index >= last_index {
+            last_time.saturating_add((index - last_index) as u64)
+        } else {
+            let next = timed.partition_point(|&(timed_index, _)| timed_index <= index);
+            let (before_index, before_time) = timed[next - 1];
+            let (after_index, after_time) = timed[next];
+            if before_index == index {
+                before_time
+            } else {
+                let span = after_time as f64 - before_time as f64;
+                let fraction = (index - before_index) as f64 / (after_index - before_index) as f64;
+                (before_time as f64 + span * fraction) as u64
+            }
+        };
+        times.push(time);
+    }
+    times
+}
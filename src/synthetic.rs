@@ -0,0 +1,223 @@
+//! Synthetic tracks, for load and soak testing
+//!
+//! A track is a number of fixes taken every `interval` seconds, either along a random walk or
+//! along the great circle between two points. Fixes are moved by GPS noise, a random offset of
+//! up to `noise_m` meters, and some are dropped, leaving gaps in time. Everything is drawn from
+//! `seed`, so that the same options always give the same track.
+
+use std::fmt;
+
+use sha2::Digest;
+
+use crate::geo::{bearing_deg, destination, distance_m};
+use crate::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Route {
+    /// Walk at a constant speed in meters per second, turning by up to 30 degrees at every fix
+    RandomWalk {
+        latitude: f64,
+        longitude: f64,
+        speed_mps: f64,
+    },
+    /// Great circle between two points, covered at a constant speed over the track
+    GreatCircle { from: (f64, f64), to: (f64, f64) },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackOptions {
+    pub count: usize,
+    pub route: Route,
+    /// Seconds between two fixes
+    pub interval: u64,
+    pub noise_m: f64,
+    /// Probability for a fix to be dropped, the first one never is
+    pub dropout: f64,
+    pub seed: u64,
+    /// Timestamp of the first fix
+    pub start_time: u64,
+}
+
+impl Default for TrackOptions {
+    fn default() -> Self {
+        TrackOptions {
+            count: 100,
+            route: Route::RandomWalk {
+                latitude: 48.8566,
+                longitude: 2.3522,
+                speed_mps: 10.0,
+            },
+            interval: 1,
+            noise_m: 0.0,
+            dropout: 0.0,
+            seed: 0,
+            start_time: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SyntheticError {
+    /// Option name and the constraint it breaks
+    Invalid(&'static str, &'static str),
+}
+
+impl fmt::Display for SyntheticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntheticError::Invalid(option, constraint) => {
+                write!(f, "{} must be {}", option, constraint)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SyntheticError {}
+
+pub fn generate(options: &TrackOptions) -> Result<Vec<Position>, SyntheticError> {
+    validate(options)?;
+    let mut random = SplitMix64(options.seed);
+
+    // slot of every fix, later ones being skipped by dropouts
+    let mut slots = Vec::with_capacity(options.count);
+    let mut slot = 0u64;
+    while slots.len() < options.count {
+        if slots.is_empty() || random.next_f64() >= options.dropout {
+            slots.push(slot);
+        }
+        slot += 1;
+    }
+    let last_slot = slots.last().copied().unwrap_or(0);
+    last_slot
+        .checked_mul(options.interval)
+        .and_then(|duration| options.start_time.checked_add(duration))
+        .ok_or(SyntheticError::Invalid(
+            "count",
+            "small enough for timestamps to fit",
+        ))?;
+
+    let mut positions = Vec::with_capacity(options.count);
+    match options.route {
+        Route::RandomWalk {
+            latitude,
+            longitude,
+            speed_mps,
+        } => {
+            let mut current = at(latitude, longitude);
+            let mut heading = random.next_f64() * 360.0;
+            let step = speed_mps * options.interval as f64;
+            let mut previous_slot = 0;
+            for &slot in &slots {
+                // fixes are dropped, the walk goes on
+                for _ in previous_slot..slot {
+                    heading = (heading + (random.next_f64() - 0.5) * 60.0).rem_euclid(360.0);
+                    current = destination(&current, heading, step);
+                }
+                previous_slot = slot;
+                positions.push((slot, current.clone()));
+            }
+        }
+        Route::GreatCircle { from, to } => {
+            let (from, to) = (at(from.0, from.1), at(to.0, to.1));
+            let bearing = bearing_deg(&from, &to);
+            let distance = distance_m(&from, &to);
+            for &slot in &slots {
+                let fraction = match last_slot {
+                    0 => 0.0,
+                    _ => slot as f64 / last_slot as f64,
+                };
+                positions.push((slot, destination(&from, bearing, distance * fraction)));
+            }
+        }
+    }
+
+    Ok(positions
+        .into_iter()
+        .map(|(slot, position)| {
+            let offset = options.noise_m * random.next_f64().sqrt();
+            let mut position = destination(&position, random.next_f64() * 360.0, offset);
+            position.timestamp = options.start_time + slot * options.interval;
+            position
+        })
+        .collect())
+}
+
+/// Secret key drawn from the seed, for signing synthetic tracks
+pub fn throwaway_key(seed: u64) -> secp256k1::SecretKey {
+    (0u32..)
+        .map(|attempt| {
+            let mut hasher = sha2::Sha256::new();
+            hasher.update(b"synthetic/key");
+            hasher.update(seed.to_be_bytes());
+            hasher.update(attempt.to_be_bytes());
+            hasher.finalize()
+        })
+        .find_map(|candidate| secp256k1::SecretKey::from_slice(&candidate).ok())
+        .expect("a valid secret key")
+}
+
+fn validate(options: &TrackOptions) -> Result<(), SyntheticError> {
+    let invalid = |option, constraint| Err(SyntheticError::Invalid(option, constraint));
+    let coordinates =
+        |latitude: f64, longitude: f64| latitude.abs() <= 90.0 && longitude.abs() <= 180.0;
+    if options.interval == 0 {
+        return invalid("interval", "at least 1 second");
+    }
+    if !(options.noise_m >= 0.0 && options.noise_m.is_finite()) {
+        return invalid("noise", "a positive number of meters");
+    }
+    if !(0.0..1.0).contains(&options.dropout) {
+        return invalid("dropout", "a probability in [0, 1)");
+    }
+    match options.route {
+        Route::RandomWalk {
+            latitude,
+            longitude,
+            speed_mps,
+        } => {
+            if !coordinates(latitude, longitude) {
+                return invalid("start", "a valid latitude and longitude");
+            }
+            if !(speed_mps >= 0.0 && speed_mps.is_finite()) {
+                return invalid("speed", "a positive number of meters per second");
+            }
+        }
+        Route::GreatCircle { from, to } => {
+            if !coordinates(from.0, from.1) || !coordinates(to.0, to.1) {
+                return invalid("start and end", "valid latitudes and longitudes");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn at(latitude: f64, longitude: f64) -> Position {
+    Position {
+        latitude,
+        longitude,
+        timestamp: 0,
+        altitude: None,
+        prev_hash: None,
+        expires_at: None,
+        dwell_count: None,
+        last_seen: None,
+    }
+}
+
+// small and good enough for test data, unlike a cryptographic generator
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
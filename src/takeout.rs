@@ -0,0 +1,235 @@
+//! Import of Google Takeout location history
+//!
+//! Android location history exports as a `Records.json` holding one object with a `locations`
+//! array, which runs to gigabytes for years of history. Entries are read one at a time from the
+//! stream and handed on, the file never being held in memory:
+//!
+//! ```json
+//! {"locations": [
+//!   {"latitudeE7": 488566000, "longitudeE7": 23522000, "accuracy": 20, "altitude": 35,
+//!    "timestamp": "2024-10-14T08:30:00.123Z"},
+//!   {"latitudeE7": 488567000, "longitudeE7": 23523000, "timestampMs": "1728894660000"}
+//! ]}
+//! ```
+//!
+//! Coordinates are integers in 10^-7 degrees, some exports storing negative values as their
+//! unsigned 32 bits complement, which is undone. They are converted as nanodegrees exactly like
+//! `wire` decodes them, so the signed encoding holds the exported integers times 100 with no
+//! float rounding. Times are ISO 8601 in `timestamp`, or milliseconds in `timestampMs` in older
+//! exports, truncated to seconds. `accuracy` is a radius in meters, smaller being better.
+//! Entries without coordinates or a readable time, or with a field of the wrong type, are skipped
+//! and counted, as are entries left out by the filter. Only JSON that does not parse at all stops
+//! the import.
+
+use std::fmt;
+use std::io::{self, Read};
+
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::core::DEGREE_SCALE;
+use crate::time::parse_iso8601;
+use crate::Position;
+
+#[derive(Debug)]
+pub enum TakeoutError {
+    Json(serde_json::Error),
+    /// Error of the consumer of the positions
+    Io(io::Error),
+}
+
+impl fmt::Display for TakeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TakeoutError::Json(err) => write!(f, "{}", err),
+            TakeoutError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for TakeoutError {}
+
+/// Entries kept, all of them when every field is `None`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TakeoutFilter {
+    /// Earliest time kept, in unix seconds, included
+    pub since: Option<u64>,
+    /// Latest time kept, in unix seconds, included
+    pub until: Option<u64>,
+    /// Largest accuracy radius kept, in meters, entries without an accuracy being left out
+    pub min_accuracy: Option<u32>,
+}
+
+/// Counts of the entries of a history
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TakeoutStats {
+    pub kept: usize,
+    pub filtered: usize,
+    pub malformed: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    latitude_e7: Option<i64>,
+    longitude_e7: Option<i64>,
+    timestamp: Option<String>,
+    timestamp_ms: Option<Millis>,
+    accuracy: Option<u32>,
+    altitude: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Millis {
+    Text(String),
+    Number(u64),
+}
+
+impl Entry {
+    fn time(&self) -> Option<u64> {
+        if let Some(timestamp) = &self.timestamp {
+            return parse_iso8601(timestamp);
+        }
+        match self.timestamp_ms.as_ref()? {
+            Millis::Text(millis) => millis.parse::<u64>().ok().map(|millis| millis / 1000),
+            Millis::Number(millis) => Some(millis / 1000),
+        }
+    }
+}
+
+/// Read the history of `reader`, handing every entry kept by `filter` to `emit` in file order
+pub fn read_takeout<R: Read>(
+    reader: R,
+    filter: &TakeoutFilter,
+    emit: impl FnMut(Position) -> io::Result<()>,
+) -> Result<TakeoutStats, TakeoutError> {
+    let mut history = History {
+        filter,
+        emit,
+        stats: TakeoutStats::default(),
+        err: None,
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(io::BufReader::new(reader));
+    let result = (&mut history).deserialize(&mut deserializer);
+    if let Some(err) = history.err {
+        return Err(TakeoutError::Io(err));
+    }
+    result
+        .and_then(|()| deserializer.end())
+        .map_err(TakeoutError::Json)?;
+    Ok(history.stats)
+}
+
+/// Coordinate in degrees of an E7 integer, through nanodegrees as in `wire`
+fn degrees(e7: i64) -> f64 {
+    let e7 = if e7 > i32::MAX as i64 {
+        e7 - (1 << 32)
+    } else {
+        e7
+    };
+    (e7 * 100) as f64 / DEGREE_SCALE
+}
+
+struct History<'a, F> {
+    filter: &'a TakeoutFilter,
+    emit: F,
+    stats: TakeoutStats,
+    /// Error of `emit`, which stopped the reading
+    err: Option<io::Error>,
+}
+
+impl<F: FnMut(Position) -> io::Result<()>> History<'_, F> {
+    fn take(&mut self, entry: Entry) -> io::Result<()> {
+        let (Some(latitude), Some(longitude), Some(timestamp)) =
+            (entry.latitude_e7, entry.longitude_e7, entry.time())
+        else {
+            self.stats.malformed += 1;
+            return Ok(());
+        };
+        let filter = self.filter;
+        if filter.since.is_some_and(|since| timestamp < since)
+            || filter.until.is_some_and(|until| timestamp > until)
+            || filter.min_accuracy.is_some_and(|min_accuracy| {
+                entry
+                    .accuracy
+                    .is_none_or(|accuracy| accuracy > min_accuracy)
+            })
+        {
+            self.stats.filtered += 1;
+            return Ok(());
+        }
+        self.stats.kept += 1;
+        (self.emit)(Position {
+            latitude: degrees(latitude),
+            longitude: degrees(longitude),
+            timestamp,
+            altitude: entry.altitude.map(|altitude| altitude as f64),
+            prev_hash: None,
+            expires_at: None,
+            dwell_count: None,
+            last_seen: None,
+        })
+    }
+}
+
+// the top level object, whose `locations` are visited one entry at a time
+impl<'de, F: FnMut(Position) -> io::Result<()>> DeserializeSeed<'de> for &mut History<'_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(Position) -> io::Result<()>> Visitor<'de> for &mut History<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a location history object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "locations" {
+                map.next_value_seed(Locations(&mut *self))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Locations<'a, 'b, F>(&'a mut History<'b, F>);
+
+impl<'de, F: FnMut(Position) -> io::Result<()>> DeserializeSeed<'de> for Locations<'_, '_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: FnMut(Position) -> io::Result<()>> Visitor<'de> for Locations<'_, '_, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of locations")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        // through a value, so that a field of the wrong type only skips its entry
+        while let Some(value) = seq.next_element::<serde_json::Value>()? {
+            let Ok(entry) = Entry::deserialize(value) else {
+                self.0.stats.malformed += 1;
+                continue;
+            };
+            if let Err(err) = self.0.take(entry) {
+                self.0.err = Some(err);
+                return Err(de::Error::custom("stopped by the consumer"));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+//! Conversions between unix timestamps and calendar dates, in UTC
+
+/// Format unix seconds as an ISO 8601 / RFC 3339 UTC date, e.g. `2024-10-14T08:30:00Z`
+pub fn format_iso8601(unix_seconds: u64) -> String {
+    let days = (unix_seconds / 86400) as i64;
+    let secs = unix_seconds % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+/// Parse an ISO 8601 / RFC 3339 date with time, fractions of seconds are dropped
+///
+/// Accepts a `Z` or `±hh:mm` offset, a missing offset is read as UTC. Years must have at most
+/// four digits.
+pub fn parse_iso8601(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let (date, time) = text.split_once(['T', ' '])?;
+
+    let mut date_fields = date.splitn(3, '-');
+    let year: i64 = date_fields.next()?.parse().ok()?;
+    let month: u32 = date_fields.next()?.parse().ok()?;
+    let day: u32 = date_fields.next()?.parse().ok()?;
+
+    let (clock, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(index) => (&time[..index], &time[index..]),
+        None => (time, ""),
+    };
+    let offset_seconds: i64 = match offset {
+        "" | "Z" | "z" => 0,
+        _ => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = offset[1..].split_once(':').unwrap_or((&offset[1..], "0"));
+            let (hours, minutes) = (hours.parse::<i64>().ok()?, minutes.parse::<i64>().ok()?);
+            if !(0..=23).contains(&hours) || !(0..=59).contains(&minutes) {
+                return None;
+            }
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+
+    let clock = clock.split('.').next()?;
+    let mut clock_fields = clock.splitn(3, ':');
+    let hour: i64 = clock_fields.next()?.parse().ok()?;
+    let minute: i64 = clock_fields.next()?.parse().ok()?;
+    let second: i64 = clock_fields.next().unwrap_or("0").parse().ok()?;
+
+    if !(0..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    if !(0..=23).contains(&hour) || !(0..=59).contains(&minute) || !(0..=60).contains(&second) {
+        return None;
+    }
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second
+        - offset_seconds;
+    u64::try_from(seconds).ok()
+}
+
+/// Parse a duration such as `300s`, `5m`, `2h` or `1d`, a bare number being seconds
+pub fn parse_duration(text: &str) -> Option<std::time::Duration> {
+    let text = text.trim();
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(digits);
+    let seconds_per_unit = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    let seconds = number.parse::<u64>().ok()?.checked_mul(seconds_per_unit)?;
+    Some(std::time::Duration::from_secs(seconds))
+}
+
+// Howard Hinnant's date algorithms, proleptic gregorian calendar
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
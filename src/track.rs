@@ -0,0 +1,301 @@
+//! Container bundling the signed positions of a track with its proof
+//!
+//! ```text
+//! magic "SGCT" || format version (1 byte) || header || positions || signatures || manifest || proof
+//! ```
+//!
+//! Every section is `tag (1 byte) || length (4 bytes big endian) || body || SHA-256(tag || length
+//! || body)`, and sections must come in the order above, so that corrupted or reordered sections
+//! are caught before anything is parsed. The bodies are:
+//!
+//! - header: JSON `TrackHeader`
+//! - positions: one JSON position per line
+//! - signatures: one JSON line per position, holding the rest of its `SignedPosition`
+//! - manifest: the 32 bytes digest of every record, in order, as committed to by the proof
+//! - proof: the serialized proof, opaque to this crate and possibly empty
+//!
+//! All the records of a track are signed by the same device key.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::scheme::{KeyProvenance, Scheme};
+use crate::session::Endorsement;
+use crate::{verify_signed_position, CoSignature, Position, SignedPosition, VerifyError};
+
+const MAGIC: &[u8; 4] = b"SGCT";
+pub const FORMAT_VERSION: u8 = 1;
+
+const HEADER: u8 = 1;
+const POSITIONS: u8 = 2;
+const SIGNATURES: u8 = 3;
+const MANIFEST: u8 = 4;
+const PROOF: u8 = 5;
+
+#[derive(Debug)]
+pub enum TrackError {
+    BadMagic,
+    UnsupportedFormat(u8),
+    Truncated,
+    /// A section other than the expected one was found at its place
+    UnexpectedSection {
+        expected: u8,
+        found: u8,
+    },
+    Checksum(u8),
+    TrailingData,
+    Malformed(String),
+    /// The header counts do not match the sections
+    CountMismatch,
+    /// The records are not all signed by the device key
+    MixedDevices,
+    /// The digest of a record is not the one listed in the manifest
+    ManifestMismatch(usize),
+    Verify {
+        index: usize,
+        err: VerifyError,
+    },
+}
+
+impl fmt::Display for TrackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrackError::BadMagic => write!(f, "not a signed track container"),
+            TrackError::UnsupportedFormat(version) => {
+                write!(f, "unsupported container format {}", version)
+            }
+            TrackError::Truncated => write!(f, "truncated container"),
+            TrackError::UnexpectedSection { expected, found } => {
+                write!(f, "expected section {}, found section {}", expected, found)
+            }
+            TrackError::Checksum(tag) => write!(f, "checksum mismatch in section {}", tag),
+            TrackError::TrailingData => write!(f, "trailing data after the last section"),
+            TrackError::Malformed(err) => write!(f, "malformed section: {}", err),
+            TrackError::CountMismatch => write!(f, "section sizes do not match the header"),
+            TrackError::MixedDevices => write!(f, "records are signed by different devices"),
+            TrackError::ManifestMismatch(index) => {
+                write!(f, "record {} does not match the proof manifest", index)
+            }
+            TrackError::Verify { index, err } => write!(f, "record {}: {}", index, err),
+        }
+    }
+}
+
+impl std::error::Error for TrackError {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrackHeader {
+    pub device_public_key: String,
+    pub scheme: Scheme,
+    /// Proving engine the proof was produced with, absent without a proof
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine_id: Option<String>,
+    pub records: usize,
+    pub proof_length: usize,
+}
+
+/// `SignedPosition` without its position
+#[derive(Serialize, Deserialize)]
+struct SignatureRecord {
+    version: u8,
+    signature: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    co_signatures: Vec<CoSignature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp_token: Option<String>,
+    #[serde(default, skip_serializing_if = "KeyProvenance::is_software")]
+    provenance: KeyProvenance,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_use: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    endorsement: Option<Endorsement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub header: TrackHeader,
+    pub records: Vec<SignedPosition>,
+    pub manifest: Vec<[u8; 32]>,
+    pub proof: Vec<u8>,
+}
+
+/// Bundle records signed by a single device with the proof over their digests
+pub fn pack(
+    records: &[SignedPosition],
+    engine_id: Option<&str>,
+    proof: &[u8],
+) -> Result<Vec<u8>, TrackError> {
+    let first = records
+        .first()
+        .ok_or_else(|| TrackError::Malformed("empty track".to_string()))?;
+    if records
+        .iter()
+        .any(|record| record.public_key != first.public_key || record.scheme != first.scheme)
+    {
+        return Err(TrackError::MixedDevices);
+    }
+
+    let header = TrackHeader {
+        device_public_key: first.public_key.clone(),
+        scheme: first.scheme,
+        engine_id: engine_id.map(str::to_string),
+        records: records.len(),
+        proof_length: proof.len(),
+    };
+    let mut positions = Vec::new();
+    let mut signatures = Vec::new();
+    let mut manifest = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        let digest = record
+            .digest()
+            .map_err(|err| TrackError::Verify { index, err })?;
+        manifest.extend_from_slice(&digest);
+        positions.extend(serde_json::to_vec(&record.position).expect("JSON serialization"));
+        positions.push(b'\n');
+        let signature = SignatureRecord {
+            version: record.version,
+            signature: record.signature.clone(),
+            co_signatures: record.co_signatures.clone(),
+            timestamp_token: record.timestamp_token.clone(),
+            provenance: record.provenance,
+            key_use: record.key_use,
+            endorsement: record.endorsement.clone(),
+        };
+        signatures.extend(serde_json::to_vec(&signature).expect("JSON serialization"));
+        signatures.push(b'\n');
+    }
+
+    let mut out = MAGIC.to_vec();
+    out.push(FORMAT_VERSION);
+    let header = serde_json::to_vec(&header).expect("JSON serialization");
+    for (tag, body) in [
+        (HEADER, header.as_slice()),
+        (POSITIONS, &positions),
+        (SIGNATURES, &signatures),
+        (MANIFEST, &manifest),
+        (PROOF, proof),
+    ] {
+        write_section(&mut out, tag, body);
+    }
+    Ok(out)
+}
+
+/// Parse a container, checking its structure and section checksums
+pub fn unpack(data: &[u8]) -> Result<Track, TrackError> {
+    let rest = data.strip_prefix(MAGIC).ok_or(TrackError::BadMagic)?;
+    let (&format, mut rest) = rest.split_first().ok_or(TrackError::Truncated)?;
+    if format != FORMAT_VERSION {
+        return Err(TrackError::UnsupportedFormat(format));
+    }
+    let mut sections = Vec::new();
+    for tag in [HEADER, POSITIONS, SIGNATURES, MANIFEST, PROOF] {
+        let (body, remaining) = read_section(rest, tag)?;
+        sections.push(body);
+        rest = remaining;
+    }
+    if !rest.is_empty() {
+        return Err(TrackError::TrailingData);
+    }
+    let [header, positions, signatures, manifest, proof] = sections[..] else {
+        unreachable!()
+    };
+
+    let malformed = |err: serde_json::Error| TrackError::Malformed(err.to_string());
+    let header: TrackHeader = serde_json::from_slice(header).map_err(malformed)?;
+    let positions = serde_json::Deserializer::from_slice(positions)
+        .into_iter::<Position>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(malformed)?;
+    let signatures = serde_json::Deserializer::from_slice(signatures)
+        .into_iter::<SignatureRecord>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(malformed)?;
+    if positions.len() != header.records
+        || signatures.len() != header.records
+        || Some(manifest.len()) != header.records.checked_mul(32)
+        || proof.len() != header.proof_length
+    {
+        return Err(TrackError::CountMismatch);
+    }
+
+    let records = positions
+        .into_iter()
+        .zip(signatures)
+        .map(|(position, signature)| SignedPosition {
+            version: signature.version,
+            position,
+            signature: signature.signature,
+            public_key: header.device_public_key.clone(),
+            scheme: header.scheme,
+            co_signatures: signature.co_signatures,
+            timestamp_token: signature.timestamp_token,
+            provenance: signature.provenance,
+            key_use: signature.key_use,
+            endorsement: signature.endorsement,
+        })
+        .collect();
+    let manifest = manifest
+        .chunks_exact(32)
+        .map(|digest| digest.try_into().expect("32 bytes chunk"))
+        .collect();
+    Ok(Track {
+        header,
+        records,
+        manifest,
+        proof: proof.to_vec(),
+    })
+}
+
+/// Check that every record is listed in the manifest, then verify their signatures
+///
+/// The proof itself is not verified, this crate has no proving engine.
+pub fn verify_track(track: &Track) -> Result<(), TrackError> {
+    if track.manifest.len() != track.records.len() {
+        return Err(TrackError::CountMismatch);
+    }
+    for (index, (record, listed)) in track.records.iter().zip(&track.manifest).enumerate() {
+        let digest = record
+            .digest()
+            .map_err(|err| TrackError::Verify { index, err })?;
+        if *digest != listed[..] {
+            return Err(TrackError::ManifestMismatch(index));
+        }
+    }
+    for (index, record) in track.records.iter().enumerate() {
+        verify_signed_position(record).map_err(|err| TrackError::Verify { index, err })?;
+    }
+    Ok(())
+}
+
+fn write_section(out: &mut Vec<u8>, tag: u8, body: &[u8]) {
+    let start = out.len();
+    out.push(tag);
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out.extend_from_slice(body);
+    let checksum = sha2::Sha256::digest(&out[start..]);
+    out.extend_from_slice(&checksum);
+}
+
+fn read_section(data: &[u8], expected: u8) -> Result<(&[u8], &[u8]), TrackError> {
+    if data.len() < 5 {
+        return Err(TrackError::Truncated);
+    }
+    let tag = data[0];
+    if tag != expected {
+        return Err(TrackError::UnexpectedSection {
+            expected,
+            found: tag,
+        });
+    }
+    let length = u32::from_be_bytes(data[1..5].try_into().unwrap()) as usize;
+    let end = 5usize
+        .checked_add(length)
+        .filter(|end| end + 32 <= data.len())
+        .ok_or(TrackError::Truncated)?;
+    if sha2::Sha256::digest(&data[..end])[..] != data[end..end + 32] {
+        return Err(TrackError::Checksum(tag));
+    }
+    Ok((&data[5..end], &data[end + 32..]))
+}
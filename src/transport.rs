@@ -0,0 +1,197 @@
+//! Network access used by the timestamping clients
+//!
+//! Everything that talks to a remote service goes through the `Transport` trait, so that
+//! callers can swap the plain HTTP implementation for canned responses.
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Longest wait for a connection, and for each read or write of a request, of `HttpTransport`
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum TransportError {
+    UnsupportedUrl(String),
+    Io(std::io::Error),
+    BadResponse(String),
+    Status(u16),
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransportError::UnsupportedUrl(url) => write!(f, "unsupported url: {}", url),
+            TransportError::Io(err) => write!(f, "network error: {}", err),
+            TransportError::BadResponse(reason) => write!(f, "malformed HTTP response: {}", reason),
+            TransportError::Status(code) => write!(f, "server answered with HTTP status {}", code),
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl TransportError {
+    /// Whether the same request cannot succeed later: an unsupported url, or a 4xx status other
+    /// than 408 and 429, which tell the client to come back
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            TransportError::UnsupportedUrl(_) => true,
+            TransportError::Status(code) => (400..500).contains(code) && ![408, 429].contains(code),
+            TransportError::Io(_) | TransportError::BadResponse(_) => false,
+        }
+    }
+}
+
+impl From<std::io::Error> for TransportError {
+    fn from(err: std::io::Error) -> Self {
+        TransportError::Io(err)
+    }
+}
+
+pub trait Transport {
+    /// POST `body` to `url` and return the response body of a 2xx answer
+    fn post(&self, url: &str, content_type: &str, body: &[u8]) -> Result<Vec<u8>, TransportError>;
+
+    /// GET `url` and return the response body of a 2xx answer
+    fn get(&self, url: &str) -> Result<Vec<u8>, TransportError>;
+}
+
+/// Blocking HTTP/1.1 client over a plain TCP socket, only `http://` urls are supported
+///
+/// Connecting, and every read or write, fails with a timeout error after `TIMEOUT`, so that an
+/// unresponsive server cannot stall a caller.
+pub struct HttpTransport;
+
+impl HttpTransport {
+    fn request(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<Vec<u8>, TransportError> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| TransportError::UnsupportedUrl(url.to_string()))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let address = if authority.contains(':') {
+            authority.to_string()
+        } else {
+            format!("{}:80", authority)
+        };
+
+        let mut stream = connect(&address)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            path,
+            authority,
+            body.len()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        stream
+            .write_all(request.as_bytes())
+            .and_then(|()| stream.write_all(body))
+            .map_err(timed_out)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).map_err(timed_out)?;
+        parse_response(&response)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn post(&self, url: &str, content_type: &str, body: &[u8]) -> Result<Vec<u8>, TransportError> {
+        self.request("POST", url, &[("Content-Type", content_type)], body)
+    }
+
+    fn get(&self, url: &str) -> Result<Vec<u8>, TransportError> {
+        self.request("GET", url, &[], &[])
+    }
+}
+
+/// Connect to the first address `address` resolves to that answers within `TIMEOUT`
+fn connect(address: &str) -> std::io::Result<TcpStream> {
+    let mut last_err = None;
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} resolves to no address", address),
+        )
+    }))
+}
+
+// sockets tell an expired timeout as `WouldBlock` on some platforms
+fn timed_out(err: std::io::Error) -> std::io::Error {
+    match err.kind() {
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("no answer within {}s", TIMEOUT.as_secs()),
+        ),
+        _ => err,
+    }
+}
+
+fn parse_response(response: &[u8]) -> Result<Vec<u8>, TransportError> {
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| TransportError::BadResponse("missing header terminator".to_string()))?;
+    let head = std::str::from_utf8(&response[..header_end])
+        .map_err(|_| TransportError::BadResponse("non utf-8 headers".to_string()))?;
+    let mut lines = head.split("\r\n");
+
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| TransportError::BadResponse("missing status line".to_string()))?;
+    if !(200..300).contains(&status) {
+        return Err(TransportError::Status(status));
+    }
+
+    let chunked = lines.any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    let body = &response[header_end + 4..];
+    if chunked {
+        decode_chunked(body)
+    } else {
+        Ok(body.to_vec())
+    }
+}
+
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>, TransportError> {
+    let bad = || TransportError::BadResponse("malformed chunked body".to_string());
+    let mut out = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n").ok_or_else(bad)?;
+        let size_line = std::str::from_utf8(&body[..line_end]).map_err(|_| bad())?;
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16).map_err(|_| bad())?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(out);
+        }
+        let chunk = body.get(..size).ok_or_else(bad)?;
+        out.extend_from_slice(chunk);
+        body = body.get(size + 2..).ok_or_else(bad)?;
+    }
+}
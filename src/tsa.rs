@@ -0,0 +1,267 @@
+//! RFC 3161 timestamping of signed positions
+//!
+//! A TimeStampReq is built over the position hash (or any other 32 bytes digest, e.g. the root of
+//! a batch), posted to a timestamp authority, and the DER TimeStampToken of its answer is stored
+//! in `SignedPosition::timestamp_token`.
+//!
+//! Verification checks the message imprint of the token against the recomputed hash and that the
+//! TSA time is within a tolerance of the claimed position timestamp. The CMS signature of the
+//! token is not checked here, as that needs the certificate chain of the TSA.
+
+use std::fmt;
+
+use crate::der::{self, DerError, Reader};
+use crate::transport::{Transport, TransportError};
+use crate::{fresh_entropy, SignedPosition};
+
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_TST_INFO: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04,
+];
+
+const TIMESTAMP_QUERY_CONTENT_TYPE: &str = "application/timestamp-query";
+
+#[derive(Debug)]
+pub enum TsaError {
+    Transport(TransportError),
+    Der(DerError),
+    /// The TSA refused the request, with its PKIStatus and optional status text
+    Rejected {
+        status: u8,
+        text: Option<String>,
+    },
+    MissingToken,
+    UnsupportedHashAlgorithm,
+    ImprintMismatch,
+    NonceMismatch,
+    TimeOutOfTolerance {
+        claimed: u64,
+        tsa_time: u64,
+    },
+    NotTimestamped,
+    InvalidHex,
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for TsaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TsaError::Transport(err) => write!(f, "{}", err),
+            TsaError::Der(err) => write!(f, "{}", err),
+            TsaError::Rejected { status, text } => match text {
+                Some(text) => write!(f, "TSA rejected the request (status {}): {}", status, text),
+                None => write!(f, "TSA rejected the request (status {})", status),
+            },
+            TsaError::MissingToken => write!(f, "TSA response carries no timestamp token"),
+            TsaError::UnsupportedHashAlgorithm => {
+                write!(f, "timestamp token does not use SHA-256")
+            }
+            TsaError::ImprintMismatch => {
+                write!(f, "timestamp token does not match the position hash")
+            }
+            TsaError::NonceMismatch => write!(f, "timestamp token nonce does not match request"),
+            TsaError::TimeOutOfTolerance { claimed, tsa_time } => write!(
+                f,
+                "TSA time {} is too far from the claimed timestamp {}",
+                tsa_time, claimed
+            ),
+            TsaError::NotTimestamped => write!(f, "position carries no timestamp token"),
+            TsaError::InvalidHex => write!(f, "timestamp token is not valid hex"),
+            TsaError::UnsupportedVersion(version) => write!(f, "unsupported version {}", version),
+        }
+    }
+}
+
+impl std::error::Error for TsaError {}
+
+impl From<TransportError> for TsaError {
+    fn from(err: TransportError) -> Self {
+        TsaError::Transport(err)
+    }
+}
+
+impl From<DerError> for TsaError {
+    fn from(err: DerError) -> Self {
+        TsaError::Der(err)
+    }
+}
+
+/// Content of the TSTInfo structure that matters for verification
+#[derive(Debug)]
+pub struct TstInfo {
+    pub hashed_message: Vec<u8>,
+    /// TSA time, in seconds since the unix epoch
+    pub gen_time: u64,
+    pub nonce: Option<Vec<u8>>,
+}
+
+/// DER TimeStampReq over a SHA-256 digest, asking for the TSA certificate to be included
+pub fn build_request(digest: &[u8], nonce: u64) -> Vec<u8> {
+    let version = der::unsigned_integer(&[1]);
+    let imprint = message_imprint(digest);
+    let nonce = der::unsigned_integer(&nonce.to_be_bytes());
+    let cert_req = der::encode(der::TAG_BOOLEAN, &[0xff]);
+    der::sequence(&[&version, &imprint, &nonce, &cert_req])
+}
+
+fn message_imprint(digest: &[u8]) -> Vec<u8> {
+    let algorithm = der::sequence(&[
+        &der::encode(der::TAG_OID, OID_SHA256),
+        &der::encode(der::TAG_NULL, &[]),
+    ]);
+    der::sequence(&[&algorithm, &der::encode(der::TAG_OCTET_STRING, digest)])
+}
+
+/// Extract the DER TimeStampToken from a TimeStampResp
+pub fn parse_response(response: &[u8]) -> Result<Vec<u8>, TsaError> {
+    let mut outer = Reader::new(response);
+    let mut resp = Reader::new(outer.read(der::TAG_SEQUENCE)?);
+
+    let mut status_info = Reader::new(resp.read(der::TAG_SEQUENCE)?);
+    let status = der::integer_bytes(status_info.read(der::TAG_INTEGER)?);
+    // granted (0) or grantedWithMods (1)
+    let status = match status {
+        [code] => *code,
+        _ => u8::MAX,
+    };
+    if status > 1 {
+        let text = status_info
+            .read_optional(der::TAG_SEQUENCE)?
+            .and_then(|free_text| Reader::new(free_text).read(der::TAG_UTF8_STRING).ok())
+            .map(|text| String::from_utf8_lossy(text).into_owned());
+        return Err(TsaError::Rejected { status, text });
+    }
+
+    if resp.is_empty() {
+        return Err(TsaError::MissingToken);
+    }
+    let (_, _, token) = resp.read_any()?;
+    Ok(token.to_vec())
+}
+
+/// Parse the TSTInfo enclosed in a TimeStampToken
+pub fn parse_token(token: &[u8]) -> Result<TstInfo, TsaError> {
+    let mut outer = Reader::new(token);
+    let mut content_info = Reader::new(outer.read(der::TAG_SEQUENCE)?);
+    if content_info.read(der::TAG_OID)? != OID_SIGNED_DATA {
+        return Err(TsaError::MissingToken);
+    }
+    let mut explicit = Reader::new(content_info.read(der::context(0))?);
+    let mut signed_data = Reader::new(explicit.read(der::TAG_SEQUENCE)?);
+    signed_data.read(der::TAG_INTEGER)?;
+    signed_data.read(der::TAG_SET)?;
+
+    let mut encap = Reader::new(signed_data.read(der::TAG_SEQUENCE)?);
+    if encap.read(der::TAG_OID)? != OID_TST_INFO {
+        return Err(TsaError::MissingToken);
+    }
+    let mut explicit = Reader::new(encap.read(der::context(0))?);
+    let tst_info = explicit.read(der::TAG_OCTET_STRING)?;
+
+    let mut outer = Reader::new(tst_info);
+    let mut tst_info = Reader::new(outer.read(der::TAG_SEQUENCE)?);
+    tst_info.read(der::TAG_INTEGER)?;
+    tst_info.read(der::TAG_OID)?;
+
+    let mut imprint = Reader::new(tst_info.read(der::TAG_SEQUENCE)?);
+    let mut algorithm = Reader::new(imprint.read(der::TAG_SEQUENCE)?);
+    if algorithm.read(der::TAG_OID)? != OID_SHA256 {
+        return Err(TsaError::UnsupportedHashAlgorithm);
+    }
+    let hashed_message = imprint.read(der::TAG_OCTET_STRING)?.to_vec();
+
+    tst_info.read(der::TAG_INTEGER)?;
+    let gen_time = der::parse_generalized_time(tst_info.read(der::TAG_GENERALIZED_TIME)?)?;
+
+    // accuracy and ordering are optional and come before the nonce
+    tst_info.read_optional(der::TAG_SEQUENCE)?;
+    tst_info.read_optional(der::TAG_BOOLEAN)?;
+    let nonce = tst_info
+        .read_optional(der::TAG_INTEGER)?
+        .map(|nonce| der::integer_bytes(nonce).to_vec());
+
+    Ok(TstInfo {
+        hashed_message,
+        gen_time,
+        nonce,
+    })
+}
+
+/// Request a timestamp token over `digest` from the TSA at `url`, returning the DER token
+pub fn request_timestamp(
+    transport: &dyn Transport,
+    url: &str,
+    digest: &[u8],
+) -> Result<Vec<u8>, TsaError> {
+    let nonce = u64::from_be_bytes(fresh_entropy(digest)[..8].try_into().unwrap());
+    let request = build_request(digest, nonce);
+    let response = transport.post(url, TIMESTAMP_QUERY_CONTENT_TYPE, &request)?;
+    let token = parse_response(&response)?;
+
+    let info = parse_token(&token)?;
+    if info.hashed_message != digest {
+        return Err(TsaError::ImprintMismatch);
+    }
+    if let Some(token_nonce) = &info.nonce {
+        let value = token_nonce
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        if token_nonce.len() > 8 || value != nonce {
+            return Err(TsaError::NonceMismatch);
+        }
+    }
+    Ok(token)
+}
+
+/// Timestamp the hash of a signed position and store the token in it
+pub fn timestamp_position(
+    transport: &dyn Transport,
+    url: &str,
+    signed_position: &mut SignedPosition,
+) -> Result<(), TsaError> {
+    let hash = signed_position
+        .digest()
+        .map_err(|_| TsaError::UnsupportedVersion(signed_position.version))?;
+    let token = request_timestamp(transport, url, &hash)?;
+    signed_position.timestamp_token = Some(hex::encode(token));
+    Ok(())
+}
+
+/// Check a token against `digest`, and that its time is at most `tolerance` seconds away
+/// from `claimed_timestamp`
+pub fn verify_token(
+    token: &[u8],
+    digest: &[u8],
+    claimed_timestamp: u64,
+    tolerance: u64,
+) -> Result<TstInfo, TsaError> {
+    let info = parse_token(token)?;
+    if info.hashed_message != digest {
+        return Err(TsaError::ImprintMismatch);
+    }
+    if info.gen_time.abs_diff(claimed_timestamp) > tolerance {
+        return Err(TsaError::TimeOutOfTolerance {
+            claimed: claimed_timestamp,
+            tsa_time: info.gen_time,
+        });
+    }
+    Ok(info)
+}
+
+/// Verify the timestamp token stored in a signed position, returning the TSA time
+pub fn verify_position_timestamp(
+    signed_position: &SignedPosition,
+    tolerance: u64,
+) -> Result<u64, TsaError> {
+    let token = signed_position
+        .timestamp_token
+        .as_ref()
+        .ok_or(TsaError::NotTimestamped)?;
+    let token = hex::decode(token).map_err(|_| TsaError::InvalidHex)?;
+    let hash = signed_position
+        .digest()
+        .map_err(|_| TsaError::UnsupportedVersion(signed_position.version))?;
+    let info = verify_token(&token, &hash, signed_position.position.timestamp, tolerance)?;
+    Ok(info.gen_time)
+}
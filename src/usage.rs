@@ -0,0 +1,185 @@
+//! Signature counters of device keys, capping how often a key signs before rotation
+//!
+//! A state file keeps, for every public key, the number of signatures made with it and the limit
+//! set for it, one key per line:
+//!
+//! ```text
+//! <hex public key> <count> [<limit>]
+//! ```
+//!
+//! A signature is counted before it is made: `reserve` writes the new count to the file, and only
+//! once the file is on disk may the key be used. A process killed between the two leaves a count
+//! for a signature that was never made, but a signature is never made without being counted, so
+//! a key cannot sign more than its limit however the process dies.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum UsageError {
+    Io(io::Error),
+    /// Line number, counting from 1, that cannot be read
+    Malformed(usize),
+    /// The key made as many signatures as its limit allows
+    KeyExhausted {
+        public_key: String,
+        limit: u64,
+    },
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UsageError::Io(err) => write!(f, "{}", err),
+            UsageError::Malformed(line) => write!(f, "malformed key state line {}", line),
+            UsageError::KeyExhausted { public_key, limit } => write!(
+                f,
+                "key {} exhausted, it made its {} signatures and must be rotated",
+                public_key, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+impl From<io::Error> for UsageError {
+    fn from(err: io::Error) -> Self {
+        UsageError::Io(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KeyUsage {
+    pub count: u64,
+    pub limit: Option<u64>,
+}
+
+impl KeyUsage {
+    /// Signatures left before the limit, when there is one
+    pub fn remaining(&self) -> Option<u64> {
+        self.limit.map(|limit| limit.saturating_sub(self.count))
+    }
+}
+
+pub struct UsageState {
+    path: PathBuf,
+    keys: BTreeMap<String, KeyUsage>,
+}
+
+impl UsageState {
+    /// State kept in a file, empty when the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self, UsageError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let mut keys = BTreeMap::new();
+        for (index, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let malformed = || UsageError::Malformed(index + 1);
+            let mut fields = line.split_whitespace();
+            let public_key = fields.next().ok_or_else(malformed)?;
+            let count = fields
+                .next()
+                .and_then(|count| count.parse().ok())
+                .ok_or_else(malformed)?;
+            let limit = match fields.next() {
+                Some(limit) => Some(limit.parse().map_err(|_| malformed())?),
+                None => None,
+            };
+            if fields.next().is_some() {
+                return Err(malformed());
+            }
+            keys.insert(public_key.to_string(), KeyUsage { count, limit });
+        }
+        Ok(UsageState {
+            path: path.to_path_buf(),
+            keys,
+        })
+    }
+
+    pub fn usage(&self, public_key: &str) -> KeyUsage {
+        self.keys.get(public_key).copied().unwrap_or_default()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = (&str, KeyUsage)> {
+        self.keys
+            .iter()
+            .map(|(public_key, usage)| (public_key.as_str(), *usage))
+    }
+
+    /// Set the limit of a key, `None` lifting it, and save the state
+    pub fn set_limit(&mut self, public_key: &str, limit: Option<u64>) -> Result<(), UsageError> {
+        self.keys.entry(public_key.to_string()).or_default().limit = limit;
+        self.save()
+    }
+
+    /// Count a signature about to be made with a key, returning the count including it
+    ///
+    /// Fails with `KeyExhausted` once the limit is reached, unless `force` is given. The state
+    /// is saved before returning, the signature may only be made after that.
+    pub fn reserve(&mut self, public_key: &str, force: bool) -> Result<u64, UsageError> {
+        let mut usage = self.usage(public_key);
+        if let Some(limit) = usage.limit {
+            if usage.count >= limit && !force {
+                return Err(UsageError::KeyExhausted {
+                    public_key: public_key.to_string(),
+                    limit,
+                });
+            }
+        }
+        usage.count += 1;
+        let previous = self.keys.insert(public_key.to_string(), usage);
+        if let Err(err) = self.save() {
+            // keep the state as it is on disk
+            match previous {
+                Some(previous) => self.keys.insert(public_key.to_string(), previous),
+                None => self.keys.remove(public_key),
+            };
+            return Err(err);
+        }
+        Ok(usage.count)
+    }
+
+    fn save(&self) -> Result<(), UsageError> {
+        let mut content = String::new();
+        for (public_key, usage) in &self.keys {
+            content.push_str(&format!("{} {}", public_key, usage.count));
+            if let Some(limit) = usage.limit {
+                content.push_str(&format!(" {}", limit));
+            }
+            content.push('\n');
+        }
+        // a complete file replaces the previous one, synced so that a count never goes back
+        replace_file(&self.path, content.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Replace a file with `content`, durably once this returns
+///
+/// The content goes to a temporary file that is synced, then renamed over the file, so that the
+/// file is either the previous one or the new one whenever the process dies.
+pub(crate) fn replace_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    // the rename is durable once the directory is synced, which some platforms do not allow
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let Ok(directory) = std::fs::File::open(directory) {
+        let _ = directory.sync_all();
+    }
+    Ok(())
+}
@@ -0,0 +1,234 @@
+//! Test vectors for other implementations of signing and verification
+//!
+//! A vector set is derived from a seed: every key of every scheme signs every position, under
+//! every supported version. Each vector carries the bytes hashed for the version, their digest,
+//! and the compact and DER signatures, all hex encoded. Signatures are deterministic (RFC 6979),
+//! so other implementations can reproduce a whole file and not only verify it.
+//!
+//! Besides positions drawn from the seed, every set holds cases that tend to break
+//! canonicalization: the poles, the antimeridian, timestamps 0 and close to `u64::MAX`, negative
+//! coordinates, values whose JSON keeps a trailing zero (`10.0`), and an expiry.
+
+use p256::ecdsa::signature::hazmat::PrehashSigner;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use crate::scheme::{Scheme, Signer, VerifyingKey};
+use crate::{wire, Position};
+
+/// Format of vector files, bumped whenever fields change meaning
+///
+/// Adding cases to `positions` does not change the format: vectors are checked one by one on
+/// their own fields, so older sets still check and only the new cases need new position fields.
+pub const VECTORS_FORMAT: u8 = 1;
+
+const KEYS_PER_SCHEME: u32 = 2;
+const RANDOM_POSITIONS: u32 = 4;
+const VERSIONS: [u8; 2] = [1, 2];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VectorSet {
+    pub format: u8,
+    /// Hex encoded seed the set was derived from
+    pub seed: String,
+    pub vectors: Vec<Vector>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Vector {
+    pub version: u8,
+    pub scheme: Scheme,
+    pub secret_key: String,
+    pub public_key: String,
+    pub position: Position,
+    /// Bytes hashed under `version`
+    pub payload: String,
+    pub digest: String,
+    pub signature: String,
+    pub signature_der: String,
+}
+
+pub fn generate(seed: &[u8]) -> VectorSet {
+    let mut vectors = Vec::new();
+    for position in positions(seed) {
+        for scheme in [Scheme::Secp256k1, Scheme::P256] {
+            for index in 0..KEYS_PER_SCHEME {
+                let secret_key = secret_key(seed, scheme, index);
+                for version in VERSIONS {
+                    vectors.push(
+                        derive(version, scheme, &secret_key, &position)
+                            .expect("generated vectors are valid"),
+                    );
+                }
+            }
+        }
+    }
+    VectorSet {
+        format: VECTORS_FORMAT,
+        seed: hex::encode(seed),
+        vectors,
+    }
+}
+
+/// Re-derive every vector of a set, returning a description of each difference found
+pub fn check(set: &VectorSet) -> Vec<String> {
+    let mut differences = Vec::new();
+    if set.format != VECTORS_FORMAT {
+        differences.push(format!("unsupported vectors format {}", set.format));
+        return differences;
+    }
+    for (index, vector) in set.vectors.iter().enumerate() {
+        let secret_key = hex::decode(&vector.secret_key).unwrap_or_default();
+        let expected = match derive(vector.version, vector.scheme, &secret_key, &vector.position) {
+            Ok(expected) => expected,
+            Err(err) => {
+                differences.push(format!("vector {}: {}", index, err));
+                continue;
+            }
+        };
+        for (field, found, expected) in [
+            ("public_key", &vector.public_key, &expected.public_key),
+            ("payload", &vector.payload, &expected.payload),
+            ("digest", &vector.digest, &expected.digest),
+            ("signature", &vector.signature, &expected.signature),
+            (
+                "signature_der",
+                &vector.signature_der,
+                &expected.signature_der,
+            ),
+        ] {
+            if found != expected {
+                differences.push(format!(
+                    "vector {}: {} is {}, expected {}",
+                    index, field, found, expected
+                ));
+            }
+        }
+        let verified = VerifyingKey::parse(vector.scheme, &vector.public_key)
+            .and_then(|key| {
+                let digest = hex::decode(&vector.digest).unwrap_or_default();
+                if digest.len() != 32 {
+                    return Ok(false);
+                }
+                key.verify(&digest, &vector.signature)
+            })
+            .unwrap_or(false);
+        if !verified {
+            differences.push(format!("vector {}: signature does not verify", index));
+        }
+    }
+    differences
+}
+
+fn derive(
+    version: u8,
+    scheme: Scheme,
+    secret_key: &[u8],
+    position: &Position,
+) -> Result<Vector, String> {
+    let payload = match version {
+        1 => serde_json::to_vec(position).expect("JSON serialization"),
+        2 => wire::encode(position).map_err(|err| err.to_string())?,
+        _ => return Err(format!("unsupported version {}", version)),
+    };
+    let digest = sha2::Sha256::digest(&payload);
+
+    let (public_key, signature, signature_der) = match scheme {
+        Scheme::Secp256k1 => {
+            let key =
+                secp256k1::SecretKey::from_slice(secret_key).map_err(|_| "invalid secret key")?;
+            let signature = crate::sign_hash_slice(&key, &digest);
+            (
+                Signer::public_key(&key),
+                hex::encode(signature.serialize_compact()),
+                hex::encode(signature.serialize_der()),
+            )
+        }
+        Scheme::P256 => {
+            let key = p256::ecdsa::SigningKey::from_slice(secret_key)
+                .map_err(|_| "invalid secret key")?;
+            let signature: p256::ecdsa::Signature = key.sign_prehash(&digest).expect("32 bytes");
+            (
+                Signer::public_key(&key),
+                hex::encode(signature.to_bytes()),
+                hex::encode(signature.to_der()),
+            )
+        }
+    };
+    Ok(Vector {
+        version,
+        scheme,
+        secret_key: hex::encode(secret_key),
+        public_key,
+        position: position.clone(),
+        payload: hex::encode(&payload),
+        digest: hex::encode(digest),
+        signature,
+        signature_der,
+    })
+}
+
+// SHA-256 counter mode, with a domain per use
+fn stream(seed: &[u8], domain: &str, counter: u32) -> [u8; 32] {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(seed);
+    hasher.update(domain.as_bytes());
+    hasher.update(counter.to_be_bytes());
+    hasher.finalize().into()
+}
+
+fn secret_key(seed: &[u8], scheme: Scheme, index: u32) -> Vec<u8> {
+    // skip the rare outputs that are not valid scalars
+    (0..)
+        .map(|attempt| stream(seed, &format!("key/{}/{}", scheme, index), attempt))
+        .find(|candidate| match scheme {
+            Scheme::Secp256k1 => secp256k1::SecretKey::from_slice(candidate).is_ok(),
+            Scheme::P256 => p256::ecdsa::SigningKey::from_slice(candidate).is_ok(),
+        })
+        .expect("a valid secret key")
+        .to_vec()
+}
+
+fn positions(seed: &[u8]) -> Vec<Position> {
+    let at = |latitude, longitude, timestamp, altitude| Position {
+        latitude,
+        longitude,
+        timestamp,
+        altitude,
+        prev_hash: None,
+        expires_at: None,
+        dwell_count: None,
+        last_seen: None,
+    };
+    let mut positions = vec![
+        at(90.0, 0.0, 1_700_000_000, None),
+        at(-90.0, 0.0, 1_700_000_000, None),
+        at(0.0, 180.0, 1_700_000_000, None),
+        at(0.0, -180.0, 1_700_000_000, None),
+        at(48.8566, 2.3522, 0, None),
+        at(48.8566, 2.3522, u64::MAX - 1, None),
+        at(-33.8688197, -151.2092955, 1_700_000_000, Some(-12.5)),
+        at(10.0, -20.5, 1_700_000_000, Some(100.0)),
+        Position {
+            expires_at: Some(1_700_000_600),
+            ..at(48.8566, 2.3522, 1_700_000_000, None)
+        },
+        Position {
+            dwell_count: Some(42),
+            last_seen: Some(1_700_000_041),
+            ..at(48.8566, 2.3522, 1_700_000_042, None)
+        },
+    ];
+    for counter in 0..RANDOM_POSITIONS {
+        let bytes = stream(seed, "position", counter);
+        let number =
+            |offset: usize| u64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        positions.push(at(
+            (number(0) % 180_000_001) as f64 / 1e6 - 90.0,
+            (number(8) % 360_000_001) as f64 / 1e6 - 180.0,
+            number(16) % 4_102_444_800,
+            None,
+        ));
+    }
+    positions
+}
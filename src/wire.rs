@@ -0,0 +1,178 @@
+//! Binary encoding of positions, hashed for signing from version 2 on
+//!
+//! Hashing JSON forces every implementation to reproduce the float formatting of serde_json. From
+//! version 2 the signed digest is the SHA-256 of this fixed layout instead, JSON only being the
+//! envelope records are carried in. All integers are big endian:
+//!
+//! ```text
+//! offset  size  field
+//!      0     1  version, 2
+//!      1     1  flags, bit 0 set when an altitude follows, bit 1 when a previous hash
+//!                 follows, bit 2 when an expiry follows, bit 3 when a dwell follows, other
+//!                 bits zero
+//!      2     8  latitude, i64 in nanodegrees
+//!     10     8  longitude, i64 in nanodegrees
+//!     18     8  timestamp, u64 unix seconds
+//!     26     8  altitude, i64 in millimeters, only when flagged
+//!  26|34    32  previous hash of the chain, only when flagged
+//! 26..66     8  expiry, u64 unix seconds, only when flagged
+//! 26..74     4  dwell count, u32 fixes skipped since the previous record, only when flagged
+//! 30..78     8  last seen, u64 unix seconds of the last skipped fix, with the dwell count
+//! ```
+//!
+//! for 26 to 86 bytes in total. Degrees and meters are scaled and rounded half away from zero,
+//! latitudes must lie in [-90, 90], longitudes in [-180, 180] and altitudes within 10^9 meters.
+//!
+//! Test vectors:
+//!
+//! ```text
+//! {"latitude":48.8566,"longitude":2.3522,"timestamp":1728894600}
+//!   encoding 02000000000b60148dc0000000008c33b94000000000670cd688
+//!   digest   7e03307cedf2b2f6864276749fc5725abb824be1387e0533e5d9f92ea1f1e6a8
+//!
+//! {"latitude":-33.8688197,"longitude":151.2092955,"timestamp":1700000000,"altitude":58.25}
+//!   encoding 0201fffffff81d42d30c0000002334c6be8c000000006553f100000000000000e38a
+//!   digest   f1d88302533e8297b0c47b0f710d2f2a8cc177e81d187b0a99c14633b7e3f948
+//!
+//! {"latitude":48.8566,"longitude":2.3522,"timestamp":1728894600,"expires_at":1728895200}
+//!   encoding 02040000000b60148dc0000000008c33b94000000000670cd68800000000670cd8e0
+//!   digest   bdb9d8a56cdd7e9d474d463770c831492111ffe75b25f74b0223893f9c53c8b7
+//!
+//! {"latitude":48.8566,"longitude":2.3522,"timestamp":1728894600,"dwell_count":12,
+//!  "last_seen":1728894588}
+//!   encoding 02080000000b60148dc0000000008c33b94000000000670cd6880000000c00000000670cd67c
+//!   digest   b1a3b57c457dbd4e36a5a950356877d1abf36f139ecc417ac19a227ecc3b5066
+//! ```
+
+use std::fmt;
+
+use hex::FromHex;
+
+use crate::core::{
+    self, Fix, DEGREE_SCALE, FLAG_ALTITUDE, FLAG_DWELL, FLAG_EXPIRY, FLAG_PREV_HASH, MAX_ALTITUDE,
+    METER_SCALE,
+};
+use crate::Position;
+
+pub use crate::core::WIRE_VERSION;
+
+#[derive(Debug, PartialEq)]
+pub enum WireError {
+    /// The encoding is not as long as its flags require
+    BadLength(usize),
+    UnsupportedVersion(u8),
+    UnknownFlags(u8),
+    OutOfRange(&'static str),
+    /// The previous hash is not 32 hex encoded bytes
+    MalformedPrevHash,
+    /// A dwell count without a last seen time, or the other way around
+    IncompleteDwell,
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WireError::BadLength(length) => write!(f, "bad encoded position length {}", length),
+            WireError::UnsupportedVersion(version) => {
+                write!(f, "unsupported encoding version {}", version)
+            }
+            WireError::UnknownFlags(flags) => write!(f, "unknown flags {:#04x}", flags),
+            WireError::OutOfRange(field) => write!(f, "{} is out of range", field),
+            WireError::MalformedPrevHash => write!(f, "malformed previous hash"),
+            WireError::IncompleteDwell => write!(f, "dwell count and last seen go together"),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+impl From<core::OutOfRange> for WireError {
+    fn from(err: core::OutOfRange) -> Self {
+        WireError::OutOfRange(err.0)
+    }
+}
+
+pub fn encode(position: &Position) -> Result<Vec<u8>, WireError> {
+    Ok(core::encode(&to_fix(position)?)?.as_bytes().to_vec())
+}
+
+pub fn decode(data: &[u8]) -> Result<Position, WireError> {
+    if data.len() < 2 {
+        return Err(WireError::BadLength(data.len()));
+    }
+    if data[0] != WIRE_VERSION {
+        return Err(WireError::UnsupportedVersion(data[0]));
+    }
+    let flags = data[1];
+    if flags & !(FLAG_ALTITUDE | FLAG_PREV_HASH | FLAG_EXPIRY | FLAG_DWELL) != 0 {
+        return Err(WireError::UnknownFlags(flags));
+    }
+    let has_altitude = flags & FLAG_ALTITUDE != 0;
+    let has_prev_hash = flags & FLAG_PREV_HASH != 0;
+    let has_expiry = flags & FLAG_EXPIRY != 0;
+    let has_dwell = flags & FLAG_DWELL != 0;
+    let prev_hash_offset = if has_altitude { 34 } else { 26 };
+    let expiry_offset = prev_hash_offset + if has_prev_hash { 32 } else { 0 };
+    let dwell_offset = expiry_offset + if has_expiry { 8 } else { 0 };
+    let expected = dwell_offset + if has_dwell { 12 } else { 0 };
+    if data.len() != expected {
+        return Err(WireError::BadLength(data.len()));
+    }
+
+    let field = |offset: usize| -> [u8; 8] { data[offset..offset + 8].try_into().unwrap() };
+    let position = Position {
+        latitude: i64::from_be_bytes(field(2)) as f64 / DEGREE_SCALE,
+        longitude: i64::from_be_bytes(field(10)) as f64 / DEGREE_SCALE,
+        timestamp: u64::from_be_bytes(field(18)),
+        altitude: has_altitude.then(|| i64::from_be_bytes(field(26)) as f64 / METER_SCALE),
+        prev_hash: has_prev_hash
+            .then(|| hex::encode(&data[prev_hash_offset..prev_hash_offset + 32])),
+        expires_at: has_expiry.then(|| u64::from_be_bytes(field(expiry_offset))),
+        dwell_count: has_dwell
+            .then(|| u32::from_be_bytes(data[dwell_offset..dwell_offset + 4].try_into().unwrap())),
+        last_seen: has_dwell.then(|| u64::from_be_bytes(field(dwell_offset + 4))),
+    };
+    // reject what encode would not produce, so that every position has a single encoding
+    if position.latitude.abs() > 90.0 {
+        return Err(WireError::OutOfRange("latitude"));
+    }
+    if position.longitude.abs() > 180.0 {
+        return Err(WireError::OutOfRange("longitude"));
+    }
+    if position
+        .altitude
+        .is_some_and(|altitude| altitude.abs() > MAX_ALTITUDE)
+    {
+        return Err(WireError::OutOfRange("altitude"));
+    }
+    Ok(position)
+}
+
+/// SHA-256 of the encoded position, the message signed in version 2
+pub fn digest(position: &Position) -> Result<[u8; 32], WireError> {
+    Ok(core::digest(&to_fix(position)?)?)
+}
+
+/// Position as signed by `core`, which owns the encoding
+pub fn to_fix(position: &Position) -> Result<Fix, WireError> {
+    let prev_hash = match &position.prev_hash {
+        Some(prev_hash) => {
+            Some(<[u8; 32]>::from_hex(prev_hash).map_err(|_| WireError::MalformedPrevHash)?)
+        }
+        None => None,
+    };
+    let dwell = match (position.dwell_count, position.last_seen) {
+        (Some(count), Some(last_seen)) => Some((count, last_seen)),
+        (None, None) => None,
+        _ => return Err(WireError::IncompleteDwell),
+    };
+    Ok(Fix {
+        latitude: position.latitude,
+        longitude: position.longitude,
+        timestamp: position.timestamp,
+        altitude: position.altitude,
+        prev_hash,
+        expires_at: position.expires_at,
+        dwell,
+    })
+}
@@ -0,0 +1,124 @@
+//! Audit log of signing operations
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use sign_data_rust::audit::{verify_log, AuditError, AuditLog, Outcome, FLUSH_INTERVAL};
+use sign_data_rust::clock::MockClock;
+
+fn log_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("audit-{}-{}", std::process::id(), name))
+}
+
+fn write_entries(path: &Path, count: u8) -> [u8; 32] {
+    let log = AuditLog::open(path, Box::new(MockClock::new(1_000))).unwrap();
+    for byte in 0..count {
+        log.record("02ab", Some(&[byte; 32]), "cli:test", Outcome::Signed)
+            .unwrap();
+    }
+    log.record(
+        "02ab",
+        None,
+        "cli:test",
+        Outcome::Failed("key use limit".into()),
+    )
+    .unwrap();
+    log.head()
+}
+
+#[test]
+fn chain_verifies() {
+    let path = log_path("chain");
+    let _ = fs::remove_file(&path);
+    let head = write_entries(&path, 3);
+    assert_eq!(verify_log(&path, Some(&head)).unwrap(), head);
+
+    // reopening chains after the last line
+    let head = write_entries(&path, 2);
+    assert_eq!(verify_log(&path, Some(&head)).unwrap(), head);
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 7);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn edited_line_is_detected() {
+    let path = log_path("edited");
+    let _ = fs::remove_file(&path);
+    let head = write_entries(&path, 3);
+    let content = fs::read_to_string(&path).unwrap();
+
+    let edited = content.replacen("\"timestamp_ms\":1000", "\"timestamp_ms\":1001", 2);
+    fs::write(&path, edited).unwrap();
+    assert!(matches!(
+        verify_log(&path, None),
+        Err(AuditError::BrokenLink(2))
+    ));
+
+    // editing the last line is only caught by the head
+    let mut lines: Vec<&str> = content.lines().collect();
+    let last = lines[3].replace("key use limit", "no reason");
+    lines[3] = &last;
+    fs::write(&path, lines.join("\n") + "\n").unwrap();
+    assert!(verify_log(&path, None).is_ok());
+    assert!(matches!(
+        verify_log(&path, Some(&head)),
+        Err(AuditError::HeadMismatch)
+    ));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn partial_last_line_is_recovered() {
+    let path = log_path("partial");
+    let _ = fs::remove_file(&path);
+    write_entries(&path, 2);
+    let content = fs::read(&path).unwrap();
+    fs::write(&path, &content[..content.len() - 20]).unwrap();
+    assert!(matches!(
+        verify_log(&path, None),
+        Err(AuditError::Malformed(3))
+    ));
+
+    let head = write_entries(&path, 1);
+    assert_eq!(verify_log(&path, Some(&head)).unwrap(), head);
+    let content = fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert!(lines[3].contains("\"recovered\""));
+
+    // the recovery only stands for the line it hashes
+    let mut edited = lines.clone();
+    let partial = lines[2].replace("cli:test", "cli:tset");
+    edited[2] = &partial;
+    fs::write(&path, edited.join("\n") + "\n").unwrap();
+    assert!(matches!(
+        verify_log(&path, None),
+        Err(AuditError::Malformed(3))
+    ));
+
+    // a cut newline only, the last line being complete
+    let _ = fs::remove_file(&path);
+    write_entries(&path, 1);
+    let content = fs::read(&path).unwrap();
+    fs::write(&path, &content[..content.len() - 1]).unwrap();
+    let head = write_entries(&path, 1);
+    assert_eq!(verify_log(&path, Some(&head)).unwrap(), head);
+    assert!(!fs::read_to_string(&path).unwrap().contains("recovered"));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn buffered_lines_are_written_without_further_signatures() {
+    let path = log_path("buffered");
+    let _ = fs::remove_file(&path);
+    let log = AuditLog::open(&path, Box::new(MockClock::new(1_000))).unwrap();
+    log.record("02ab", Some(&[1; 32]), "cli:test", Outcome::Signed)
+        .unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"");
+
+    std::thread::sleep(FLUSH_INTERVAL + Duration::from_millis(500));
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    drop(log);
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,72 @@
+//! One signature over a burst of positions
+
+mod common;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::burst::{combined_digest, sign_burst, verify_burst, BurstError, SignedBurst};
+use sign_data_rust::VerifyError;
+
+fn burst() -> SignedBurst {
+    let positions = vec![
+        position(48.8566, 2.3522, 1_728_894_660),
+        position(48.8567, 2.3523, 1_728_894_661),
+        position(48.8568, 2.3524, 1_728_894_662),
+    ];
+    sign_burst(positions, &secret_key(1)).unwrap()
+}
+
+/// `verify_burst` of `burst` edited by `edit`, both as published and with the digest recomputed
+fn check_edit(edit: impl Fn(&mut SignedBurst)) {
+    let mut edited = burst();
+    edit(&mut edited);
+    assert_eq!(verify_burst(&edited), Err(BurstError::DigestMismatch));
+    edited.digest = hex::encode(combined_digest(&edited.positions).unwrap());
+    assert!(matches!(
+        verify_burst(&edited),
+        Err(BurstError::Verify(VerifyError::InvalidSignature { .. }))
+    ));
+}
+
+#[test]
+fn burst_verifies() {
+    assert_eq!(verify_burst(&burst()), Ok(()));
+    let positions = burst().positions;
+    assert_eq!(
+        verify_burst(&sign_burst(positions, &p256_key(2)).unwrap()),
+        Ok(())
+    );
+}
+
+#[test]
+fn changed_position_is_detected() {
+    check_edit(|burst| burst.positions[1].latitude += 1e-6);
+    check_edit(|burst| burst.positions[2].timestamp += 1);
+    check_edit(|burst| burst.positions[0].altitude = Some(35.0));
+}
+
+#[test]
+fn dropped_position_is_detected() {
+    check_edit(|burst| {
+        burst.positions.remove(1);
+    });
+    check_edit(|burst| {
+        burst.positions.pop();
+    });
+    let mut edited = burst();
+    edited.positions.clear();
+    assert_eq!(verify_burst(&edited), Err(BurstError::EmptyBurst));
+}
+
+#[test]
+fn reordered_positions_are_detected() {
+    check_edit(|burst| burst.positions.swap(0, 1));
+    check_edit(|burst| burst.positions.reverse());
+}
+
+#[test]
+fn added_position_is_detected() {
+    check_edit(|burst| {
+        let last = burst.positions[2].clone();
+        burst.positions.push(last);
+    });
+}
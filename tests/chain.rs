@@ -0,0 +1,87 @@
+//! Hash chains, and the diagnosis of a tampered log
+
+mod common;
+
+use common::{position, secret_key};
+use sign_data_rust::chain::{
+    link_hash, load_head, save_head, sign_linked, verify_chain, ChainBreak, GENESIS,
+};
+use sign_data_rust::{sign_position, SignedPosition};
+
+fn chain(head: &mut [u8; 32]) -> Vec<SignedPosition> {
+    (0..5)
+        .map(|index| {
+            let position = position(48.8566, 2.3522 + index as f64 * 1e-4, 1_728_894_600 + index);
+            sign_linked(position, head, &secret_key(1))
+        })
+        .collect()
+}
+
+#[test]
+fn chain_verifies() {
+    let mut head = GENESIS;
+    let records = chain(&mut head);
+    assert_eq!(records[0].position.prev_hash, Some(hex::encode(GENESIS)));
+    assert_eq!(link_hash(&records[4]), Some(head));
+    verify_chain(&records, Some(&GENESIS)).unwrap();
+    // any suffix verifies on its own, or following the record before it
+    verify_chain(&records[2..], None).unwrap();
+    verify_chain(&records[2..], Some(&link_hash(&records[1]).unwrap())).unwrap();
+
+    // signing resumes from the head
+    let next = chain(&mut head);
+    let records = [records, next].concat();
+    verify_chain(&records, Some(&GENESIS)).unwrap();
+}
+
+#[test]
+fn deleted_record() {
+    let mut records = chain(&mut GENESIS.clone());
+    records.remove(2);
+    assert_eq!(verify_chain(&records, None), Err(ChainBreak::Removed(2)));
+
+    let records = chain(&mut GENESIS.clone());
+    assert_eq!(
+        verify_chain(&records[1..], Some(&GENESIS)),
+        Err(ChainBreak::Removed(0))
+    );
+}
+
+#[test]
+fn altered_record() {
+    let mut records = chain(&mut GENESIS.clone());
+    records[3].position.latitude = 48.8567;
+    assert_eq!(verify_chain(&records, None), Err(ChainBreak::Altered(3)));
+
+    // re-signed after the change, it breaks the link of its successor instead
+    let mut records = chain(&mut GENESIS.clone());
+    let mut position = records[3].position.clone();
+    position.latitude = 48.8567;
+    records[3] = sign_position(position, &secret_key(1));
+    assert_eq!(verify_chain(&records, None), Err(ChainBreak::Removed(4)));
+}
+
+#[test]
+fn reordered_records() {
+    let mut records = chain(&mut GENESIS.clone());
+    records.swap(1, 3);
+    assert_eq!(verify_chain(&records, None), Err(ChainBreak::Reordered(1)));
+
+    let mut records = chain(&mut GENESIS.clone());
+    records[4].position.prev_hash = None;
+    records[4] = sign_position(records[4].position.clone(), &secret_key(1));
+    assert_eq!(verify_chain(&records, None), Err(ChainBreak::Unlinked(4)));
+}
+
+#[test]
+fn head_state_file() {
+    let path = std::env::temp_dir().join(format!("chain-head-{}", std::process::id()));
+    assert_eq!(load_head(&path).unwrap(), GENESIS);
+    let mut head = GENESIS;
+    chain(&mut head);
+    save_head(&path, &head).unwrap();
+    assert_eq!(load_head(&path).unwrap(), head);
+    std::fs::write(&path, "not hex\n").unwrap();
+    assert!(load_head(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,51 @@
+//! Sources of the current time
+
+mod common;
+
+use common::position;
+use sign_data_rust::clock::{Clock, GpsClock, MockClock, SystemClock};
+
+#[test]
+fn mock_clock_reads_what_it_was_set_to() {
+    let clock = MockClock::new(1_728_894_600_999);
+    assert_eq!(clock.now_unix_ms(), 1_728_894_600_999);
+    assert_eq!(clock.now_unix_secs(), 1_728_894_600);
+    // reading does not move it
+    assert_eq!(clock.now_unix_ms(), 1_728_894_600_999);
+
+    clock.advance(1);
+    assert_eq!(clock.now_unix_ms(), 1_728_894_601_000);
+    assert_eq!(clock.now_unix_secs(), 1_728_894_601);
+    clock.set(5_000);
+    assert_eq!(clock.now_unix_secs(), 5);
+    assert_eq!(MockClock::default().now_unix_ms(), 0);
+}
+
+#[test]
+fn system_clock_is_monotone() {
+    let mut last = SystemClock.now_unix_ms();
+    // after 2020-01-01
+    assert!(last > 1_577_836_800_000, "{}", last);
+    for _ in 0..1000 {
+        let now = SystemClock.now_unix_ms();
+        assert!(now >= last, "{} then {}", last, now);
+        last = now;
+    }
+    assert!(SystemClock.now_unix_secs() >= last / 1000);
+}
+
+#[test]
+fn gps_clock_follows_the_last_fix() {
+    let mut clock = GpsClock::new();
+    let system = SystemClock.now_unix_secs();
+    assert!(clock.now_unix_secs() >= system);
+
+    clock.observe(&position(48.8566, 2.3522, 1_700_000_000));
+    let now = clock.now_unix_ms();
+    assert!(
+        (1_700_000_000_000..1_700_000_060_000).contains(&now),
+        "{}",
+        now
+    );
+    assert!(clock.now_unix_ms() >= now);
+}
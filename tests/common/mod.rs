@@ -0,0 +1,26 @@
+//! Keys and positions shared by the integration tests
+
+#![allow(dead_code)]
+
+use sign_data_rust::Position;
+
+pub fn secret_key(byte: u8) -> secp256k1::SecretKey {
+    secp256k1::SecretKey::from_slice(&[byte; 32]).unwrap()
+}
+
+pub fn p256_key(byte: u8) -> p256::ecdsa::SigningKey {
+    p256::ecdsa::SigningKey::from_slice(&[byte; 32]).unwrap()
+}
+
+pub fn position(latitude: f64, longitude: f64, timestamp: u64) -> Position {
+    Position {
+        latitude,
+        longitude,
+        timestamp,
+        altitude: None,
+        prev_hash: None,
+        expires_at: None,
+        dwell_count: None,
+        last_seen: None,
+    }
+}
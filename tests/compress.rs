@@ -0,0 +1,93 @@
+//! Compressed and uncompressed artifacts on disk
+
+mod common;
+
+use std::io;
+use std::path::PathBuf;
+
+use common::{position, secret_key};
+use sign_data_rust::compress::{is_compressed, read_artifact, write_artifact};
+use sign_data_rust::{sign_position, track};
+
+fn artifact(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{}-{}", std::process::id(), name))
+}
+
+fn container() -> Vec<u8> {
+    let records: Vec<_> = (0..50)
+        .map(|index| {
+            sign_position(
+                position(48.8566, 2.3522, 1_728_894_600 + index),
+                &secret_key(1),
+            )
+        })
+        .collect();
+    track::pack(&records, None, &[7; 1000]).unwrap()
+}
+
+#[test]
+fn legacy_round_trip() {
+    let path = artifact("legacy.sgct");
+    let data = container();
+    write_artifact(&path, &data, None).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+    assert_eq!(read_artifact(&path).unwrap(), data);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn compressed_round_trip() {
+    let path = artifact("compressed.sgct");
+    let data = container();
+    write_artifact(&path, &data, Some(19)).unwrap();
+    let written = std::fs::read(&path).unwrap();
+    assert!(is_compressed(&written));
+    assert!(written.len() < data.len());
+    let read = read_artifact(&path).unwrap();
+    assert_eq!(read, data);
+    track::verify_track(&track::unpack(&read).unwrap()).unwrap();
+
+    assert_eq!(
+        write_artifact(&path, &data, Some(100)).unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn corrupted_stream() {
+    let path = artifact("corrupted.sgct");
+    write_artifact(&path, &container(), Some(3)).unwrap();
+    let mut written = std::fs::read(&path).unwrap();
+    let middle = written.len() / 2;
+    written[middle] ^= 0xff;
+    for corrupted in [&written[..], &written[..middle]] {
+        std::fs::write(&path, corrupted).unwrap();
+        let err = read_artifact(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", err);
+        assert!(err.to_string().starts_with("corrupted compressed data"));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(not(feature = "zstd"))]
+#[test]
+fn compression_unsupported() {
+    let path = artifact("unsupported.sgct");
+    assert_eq!(
+        write_artifact(&path, &container(), Some(3))
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::Unsupported
+    );
+    // a zstd frame is recognized, but cannot be read
+    std::fs::write(&path, [0x28, 0xb5, 0x2f, 0xfd, 0]).unwrap();
+    assert!(is_compressed(&std::fs::read(&path).unwrap()));
+    assert_eq!(
+        read_artifact(&path).unwrap_err().kind(),
+        io::ErrorKind::Unsupported
+    );
+    std::fs::remove_file(&path).unwrap();
+}
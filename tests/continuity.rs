@@ -0,0 +1,131 @@
+//! Plausibility of a submitted track as a whole
+
+mod common;
+
+use common::{position, secret_key};
+use sign_data_rust::continuity::{verify_track, ContinuityPolicy, Finding, Rule};
+use sign_data_rust::{sign_position, SignedPosition};
+
+/// Records a minute apart along a meridian, `step` degrees of latitude apart
+fn track(count: u64, step: f64) -> Vec<SignedPosition> {
+    (0..count)
+        .map(|index| {
+            let fix = position(48.0 + step * index as f64, 2.0, 1_000 + index * 60);
+            let mut record = sign_position(fix, &secret_key(1));
+            record.key_use = Some(index + 1);
+            record
+        })
+        .collect()
+}
+
+#[test]
+fn sequence_findings_are_advisory() {
+    let policy = ContinuityPolicy {
+        check_sequence: true,
+        ..ContinuityPolicy::default()
+    };
+    let mut records = track(4, 0.001);
+    let report = verify_track(&records, &policy);
+    assert!(report.is_clean());
+    assert!(report.advisory.is_empty());
+
+    // key_use is not signed, rewriting it leaves the signatures valid
+    records[2].key_use = Some(7);
+    records[3].key_use = None;
+    let report = verify_track(&records, &policy);
+    assert!(report.is_clean());
+    assert!(report.invalid.is_empty());
+    assert_eq!(
+        report.advisory,
+        [
+            Finding {
+                index: 2,
+                rule: Rule::Sequence,
+                measured: Some(5.0),
+            },
+            Finding {
+                index: 3,
+                rule: Rule::Sequence,
+                measured: None,
+            },
+        ]
+    );
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["findings"].as_array().unwrap().len(), 0);
+    assert_eq!(json["advisory"][0]["rule"], "sequence");
+
+    // not checked unless asked
+    let report = verify_track(&records, &ContinuityPolicy::default());
+    assert!(report.advisory.is_empty());
+}
+
+fn policy() -> ContinuityPolicy {
+    ContinuityPolicy {
+        max_gap: Some(std::time::Duration::from_secs(90)),
+        max_speed_mps: Some(50.0),
+        check_sequence: false,
+    }
+}
+
+#[test]
+fn clean_track() {
+    let report = verify_track(&track(10, 0.001), &policy());
+    assert!(report.is_clean());
+    assert_eq!(report.records, 10);
+}
+
+#[test]
+fn gap_is_found() {
+    let mut records = track(6, 0.001);
+    records.remove(3);
+    let report = verify_track(&records, &policy());
+    assert!(!report.is_clean());
+    assert!(report.invalid.is_empty());
+    assert_eq!(
+        report.findings,
+        [Finding {
+            index: 3,
+            rule: Rule::MaxGap,
+            measured: Some(120.0),
+        }]
+    );
+}
+
+#[test]
+fn teleport_is_found() {
+    let mut records = track(5, 0.001);
+    // a minute across a degree of latitude, then back
+    let mut fix = records[2].position.clone();
+    fix.latitude += 1.0;
+    records[2] = sign_position(fix, &secret_key(1));
+    let report = verify_track(&records, &policy());
+    assert!(report.invalid.is_empty());
+    let found: Vec<(usize, Rule)> = report
+        .findings
+        .iter()
+        .map(|finding| (finding.index, finding.rule))
+        .collect();
+    assert_eq!(found, [(2, Rule::MaxSpeed), (3, Rule::MaxSpeed)]);
+    let speed = report.findings[0].measured.unwrap();
+    assert!((speed - 1_853.0).abs() < 5.0, "{}", speed);
+
+    // records out of order, the pair going back in time not being checked further
+    let mut records = track(3, 0.001);
+    records.swap(1, 2);
+    let report = verify_track(&records, &policy());
+    assert_eq!(
+        report.findings,
+        [
+            Finding {
+                index: 1,
+                rule: Rule::MaxGap,
+                measured: Some(120.0),
+            },
+            Finding {
+                index: 2,
+                rule: Rule::IncreasingTimestamp,
+                measured: Some(-60.0),
+            }
+        ]
+    );
+}
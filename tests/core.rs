@@ -0,0 +1,137 @@
+//! Signing without the standard library, against the host path
+
+mod common;
+
+use common::{position, secret_key};
+use sign_data_rust::core::{self, Fix, OutOfRange};
+use sign_data_rust::scheme::Signer;
+use sign_data_rust::{sign_position, verify_signed_position, wire, Position, SignedPosition};
+
+/// The test vectors of `wire`
+fn vectors() -> Vec<(Position, &'static str, &'static str)> {
+    let paris = position(48.8566, 2.3522, 1_728_894_600);
+    let mut sydney = position(-33.8688197, 151.2092955, 1_700_000_000);
+    sydney.altitude = Some(58.25);
+    vec![
+        (
+            paris.clone(),
+            "02000000000b60148dc0000000008c33b94000000000670cd688",
+            "7e03307cedf2b2f6864276749fc5725abb824be1387e0533e5d9f92ea1f1e6a8",
+        ),
+        (
+            sydney,
+            "0201fffffff81d42d30c0000002334c6be8c000000006553f100000000000000e38a",
+            "f1d88302533e8297b0c47b0f710d2f2a8cc177e81d187b0a99c14633b7e3f948",
+        ),
+        (
+            Position {
+                expires_at: Some(1_728_895_200),
+                ..paris.clone()
+            },
+            "02040000000b60148dc0000000008c33b94000000000670cd68800000000670cd8e0",
+            "bdb9d8a56cdd7e9d474d463770c831492111ffe75b25f74b0223893f9c53c8b7",
+        ),
+        (
+            Position {
+                dwell_count: Some(12),
+                last_seen: Some(1_728_894_588),
+                ..paris
+            },
+            "02080000000b60148dc0000000008c33b94000000000670cd6880000000c00000000670cd67c",
+            "b1a3b57c457dbd4e36a5a950356877d1abf36f139ecc417ac19a227ecc3b5066",
+        ),
+    ]
+}
+
+#[test]
+fn host_bytes_identical_to_core() {
+    let mut chained = position(0.0, -180.0, u64::MAX);
+    chained.altitude = Some(-1e9);
+    chained.prev_hash = Some(hex::encode([0xab; 32]));
+    chained.expires_at = Some(0);
+    chained.dwell_count = Some(u32::MAX);
+    chained.last_seen = Some(1);
+
+    let mut positions: Vec<Position> = vectors()
+        .into_iter()
+        .map(|(position, ..)| position)
+        .collect();
+    positions.push(chained);
+    for position in positions {
+        let fix = wire::to_fix(&position).unwrap();
+        let encoded = core::encode(&fix).unwrap();
+        assert_eq!(encoded.as_bytes(), wire::encode(&position).unwrap());
+        assert_eq!(wire::decode(encoded.as_bytes()).unwrap(), position);
+        assert_eq!(
+            core::digest(&fix).unwrap(),
+            wire::digest(&position).unwrap()
+        );
+
+        let record = sign_position(position.clone(), &secret_key(1));
+        let signature = core::sign(&fix, &[1; 32]).unwrap();
+        assert_eq!(hex::encode(signature), record.signature);
+    }
+    let encoded = core::encode(&wire::to_fix(&position(1.0, 1.0, 1)).unwrap()).unwrap();
+    assert!(encoded.as_bytes().len() <= core::MAX_ENCODED_LENGTH);
+}
+
+#[test]
+fn wire_test_vectors() {
+    for (position, encoding, digest) in vectors() {
+        let fix = wire::to_fix(&position).unwrap();
+        assert_eq!(
+            hex::encode(core::encode(&fix).unwrap().as_bytes()),
+            encoding
+        );
+        assert_eq!(hex::encode(core::digest(&fix).unwrap()), digest);
+    }
+}
+
+#[test]
+fn core_signed_records_verify() {
+    let position = Position {
+        altitude: Some(35.0),
+        ..position(48.8566, 2.3522, 1_728_894_600)
+    };
+    let signature = core::sign(&wire::to_fix(&position).unwrap(), &[1; 32]).unwrap();
+    // the envelope a host builds around a signature made on a device
+    let record = SignedPosition {
+        signature: hex::encode(signature),
+        ..sign_position(position, &secret_key(2))
+    };
+    assert!(verify_signed_position(&record).is_err());
+    let record = SignedPosition {
+        public_key: Signer::public_key(&secret_key(1)),
+        ..record
+    };
+    verify_signed_position(&record).unwrap();
+}
+
+#[test]
+fn out_of_range() {
+    let fix = |latitude, longitude, altitude| Fix {
+        latitude,
+        longitude,
+        timestamp: 0,
+        altitude,
+        prev_hash: None,
+        expires_at: None,
+        dwell: None,
+    };
+    assert_eq!(
+        core::encode(&fix(90.5, 0.0, None)).unwrap_err(),
+        OutOfRange("latitude")
+    );
+    assert_eq!(
+        core::encode(&fix(0.0, f64::NAN, None)).unwrap_err(),
+        OutOfRange("longitude")
+    );
+    assert_eq!(
+        core::encode(&fix(0.0, 0.0, Some(1e10))).unwrap_err(),
+        OutOfRange("altitude")
+    );
+    assert_eq!(
+        core::sign(&fix(0.0, 0.0, None), &[0; 32]),
+        Err(core::Error::InvalidSecretKey)
+    );
+}
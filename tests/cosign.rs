@@ -0,0 +1,94 @@
+//! Co-signatures and threshold verification
+
+mod common;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::cosign::{co_sign, verify_threshold, CoSignError};
+use sign_data_rust::scheme::{Signer, VerifyingKey};
+use sign_data_rust::{sign_position, verify_signed_position, SignedPosition, VerifyError};
+
+fn trusted(signers: &[&dyn Signer]) -> Vec<VerifyingKey> {
+    signers
+        .iter()
+        .map(|signer| VerifyingKey::parse(signer.scheme(), &signer.public_key()).unwrap())
+        .collect()
+}
+
+fn co_signed() -> SignedPosition {
+    let mut record = sign_position(position(48.8566, 2.3522, 1_728_894_600), &secret_key(1));
+    co_sign(&mut record, &secret_key(2)).unwrap();
+    co_sign(&mut record, &p256_key(3)).unwrap();
+    record
+}
+
+#[test]
+fn threshold_met() {
+    let record = co_signed();
+    verify_signed_position(&record).unwrap();
+    let trusted = trusted(&[&secret_key(1), &secret_key(2), &p256_key(3)]);
+    assert_eq!(verify_threshold(&record, &trusted, 3), Ok(3));
+    assert_eq!(verify_threshold(&record, &trusted, 2), Ok(3));
+}
+
+#[test]
+fn threshold_not_met() {
+    let record = co_signed();
+    // the third signer is not trusted, so its signature does not count
+    let trusted = trusted(&[&secret_key(1), &secret_key(2), &secret_key(4)]);
+    assert_eq!(
+        verify_threshold(&record, &trusted, 3),
+        Err(VerifyError::ThresholdNotMet {
+            valid: 2,
+            required: 3
+        })
+    );
+}
+
+#[test]
+fn duplicate_key_rejected() {
+    let mut record = co_signed();
+    assert_eq!(
+        co_sign(&mut record, &secret_key(2)),
+        Err(CoSignError::AlreadySigned(Signer::public_key(&secret_key(
+            2
+        ))))
+    );
+
+    // a second signature of the same key appended by hand
+    let duplicate = record.co_signatures[0].clone();
+    record.co_signatures.push(duplicate);
+    let trusted = trusted(&[&secret_key(1), &secret_key(2), &p256_key(3)]);
+    assert!(matches!(
+        verify_threshold(&record, &trusted, 1),
+        Err(VerifyError::DuplicateKey(_))
+    ));
+    assert!(matches!(
+        verify_signed_position(&record),
+        Err(VerifyError::DuplicateKey(_))
+    ));
+}
+
+#[test]
+fn forged_co_signature_not_counted() {
+    let mut record = co_signed();
+    // the signature of the first co-signer moved over to another position
+    let mut moved = sign_position(position(48.8567, 2.3522, 1_728_894_600), &secret_key(1));
+    co_sign(&mut moved, &secret_key(2)).unwrap();
+    record.co_signatures[0].signature = moved.co_signatures[0].signature.clone();
+
+    assert_eq!(
+        verify_signed_position(&record),
+        Err(VerifyError::InvalidSignature {
+            public_key: Signer::public_key(&secret_key(2))
+        })
+    );
+    let trusted = trusted(&[&secret_key(1), &secret_key(2), &p256_key(3)]);
+    assert_eq!(verify_threshold(&record, &trusted, 2), Ok(2));
+    assert_eq!(
+        verify_threshold(&record, &trusted, 3),
+        Err(VerifyError::ThresholdNotMet {
+            valid: 2,
+            required: 3
+        })
+    );
+}
@@ -0,0 +1,98 @@
+//! Decimal string coordinates, which survive tools that reformat floats
+
+mod common;
+
+use common::{position, secret_key};
+use serde_json::Value;
+use sign_data_rust::decimal::{
+    format_coordinate, parse_coordinate, sign_decimal_position, verify_decimal_position,
+    DecimalError, DecimalPosition, SignedDecimalPosition,
+};
+use sign_data_rust::{try_sign_position, verify_signed_position, SignedPosition, VerifyError};
+
+/// Round every float of a JSON document to 15 significant digits, as a tool reading numbers as
+/// doubles and printing them shortest-but-15 would
+fn mangle(value: &mut Value) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            let rounded: f64 = format!("{:.14e}", number.as_f64().unwrap())
+                .parse()
+                .unwrap();
+            *value = Value::from(rounded);
+        }
+        Value::Array(values) => values.iter_mut().for_each(mangle),
+        Value::Object(map) => map.values_mut().for_each(mangle),
+        _ => {}
+    }
+}
+
+fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(record: &T) -> T {
+    let mut json = serde_json::to_value(record).unwrap();
+    mangle(&mut json);
+    serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn float_mangling_round_trip() {
+    // 0.1 + 0.2 needs 17 significant digits to come back
+    let position = position(48.8566, 0.1 + 0.2, 1_728_894_600);
+
+    let record = try_sign_position(position.clone(), 1, &secret_key(1)).unwrap();
+    let mangled: SignedPosition = round_trip(&record);
+    assert_ne!(mangled.position.longitude, 0.1 + 0.2);
+    assert!(matches!(
+        verify_signed_position(&mangled),
+        Err(VerifyError::InvalidSignature { .. })
+    ));
+
+    let decimal = DecimalPosition::from_position(&position, 7).unwrap();
+    assert_eq!(decimal.longitude, "0.3000000");
+    let record = sign_decimal_position(decimal, &secret_key(1)).unwrap();
+    let mangled: SignedDecimalPosition = round_trip(&record);
+    assert_eq!(mangled, record);
+    verify_decimal_position(&mangled).unwrap();
+}
+
+#[test]
+fn one_spelling_per_value() {
+    assert_eq!(
+        format_coordinate(-0.00000001, 7, 90.0).unwrap(),
+        "0.0000000"
+    );
+    assert_eq!(format_coordinate(-33.8688, 6, 90.0).unwrap(), "-33.868800");
+    assert_eq!(
+        format_coordinate(1.0, 8, 90.0),
+        Err(DecimalError::UnsupportedPrecision(8))
+    );
+    for malformed in [
+        "-0.000000",
+        "01.000000",
+        "1.00000",
+        "1.00000000",
+        "+1.000000",
+        "1e1",
+    ] {
+        assert_eq!(
+            parse_coordinate(malformed, 90.0),
+            Err(DecimalError::Malformed(malformed.to_string()))
+        );
+    }
+    assert_eq!(
+        parse_coordinate("90.0000001", 90.0),
+        Err(DecimalError::OutOfRange("90.0000001".to_string()))
+    );
+    assert_eq!(parse_coordinate("-180.000000", 180.0), Ok(-180.0));
+}
+
+#[test]
+fn altered_coordinate_string_fails() {
+    let decimal =
+        DecimalPosition::from_position(&position(48.8566, 2.3522, 1_728_894_600), 7).unwrap();
+    let mut record = sign_decimal_position(decimal, &secret_key(1)).unwrap();
+    // the same value, spelled with one decimal less
+    record.position.latitude = "48.856600".to_string();
+    assert!(matches!(
+        verify_decimal_position(&record),
+        Err(VerifyError::InvalidSignature { .. })
+    ));
+}
@@ -0,0 +1,61 @@
+//! Deduplication of stationary fixes before signing
+
+mod common;
+
+use common::position;
+use sign_data_rust::dedup::{Dedup, DedupPolicy};
+use sign_data_rust::source::{MemorySource, PositionSource};
+use sign_data_rust::Position;
+
+/// Degrees of latitude for `meters` north
+fn north(meters: f64) -> f64 {
+    meters / 111_195.0
+}
+
+#[test]
+fn stationary_then_moving() {
+    let mut fixes = Vec::new();
+    // parked for a minute, within a few meters
+    for second in 0..6 {
+        let jitter = north([0.0, 2.0, -1.5, 3.0, 0.5, -2.0][second as usize]);
+        fixes.push(position(48.0 + jitter, 2.0, 1_000 + second * 10));
+    }
+    // then driving north at 15 m/s
+    for second in 1..=4 {
+        fixes.push(position(
+            48.0 + north(150.0 * second as f64),
+            2.0,
+            1_050 + second * 10,
+        ));
+    }
+    let mut source = MemorySource::from(fixes.clone());
+    let mut dedup = Dedup::new(&mut source, DedupPolicy::default());
+    let out: Vec<Position> = std::iter::from_fn(|| dedup.next_fix()).collect();
+    assert_eq!(dedup.skipped(), 5);
+
+    // the first parked fix, then the first moving one carrying the dwell
+    let times: Vec<u64> = out.iter().map(|fix| fix.timestamp).collect();
+    assert_eq!(times, [1_000, 1_060, 1_070, 1_080, 1_090]);
+    assert_eq!(out[0].dwell_count, None);
+    assert_eq!(out[1].dwell_count, Some(5));
+    assert_eq!(out[1].last_seen, Some(1_050));
+    assert!(out[2..].iter().all(|fix| fix.dwell_count.is_none()));
+    assert_eq!(out[1].latitude, fixes[6].latitude);
+}
+
+#[test]
+fn parked_at_the_end_is_handed_out() {
+    let fixes: Vec<Position> = (0..4)
+        .map(|index| position(48.0, 2.0, 1_000 + index * 10))
+        .collect();
+    let mut source = MemorySource::from(fixes);
+    let mut dedup = Dedup::new(&mut source, DedupPolicy::default());
+    let out: Vec<Position> = std::iter::from_fn(|| dedup.next_fix()).collect();
+
+    // the last skipped fix, with the ones before it folded in
+    let times: Vec<u64> = out.iter().map(|fix| fix.timestamp).collect();
+    assert_eq!(times, [1_000, 1_030]);
+    assert_eq!(out[1].dwell_count, Some(2));
+    assert_eq!(out[1].last_seen, Some(1_020));
+    assert_eq!(dedup.skipped(), 3);
+}
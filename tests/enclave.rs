@@ -0,0 +1,93 @@
+//! Signers over a key backend, exercised through the software backend
+
+mod common;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::enclave::{
+    normalize, EnclaveError, EnclaveSigner, KeyBackend, SoftwareBackend, SoftwareKey,
+};
+use sign_data_rust::scheme::{KeyProvenance, Scheme, Signer};
+use sign_data_rust::{sign_position, verify_signed_position};
+
+fn backend(key: SoftwareKey) -> SoftwareBackend {
+    SoftwareBackend {
+        key,
+        provenance: KeyProvenance::AppleSecureEnclave,
+    }
+}
+
+#[test]
+fn records_verify_and_carry_provenance() {
+    for (key, public_key) in [
+        (
+            SoftwareKey::P256(p256_key(1)),
+            Signer::public_key(&p256_key(1)),
+        ),
+        (
+            SoftwareKey::Secp256k1(secret_key(1)),
+            Signer::public_key(&secret_key(1)),
+        ),
+    ] {
+        let signer = EnclaveSigner::new(backend(key)).unwrap();
+        // uncompressed keys from the backend are carried compressed
+        assert_eq!(signer.public_key(), public_key);
+        let record = sign_position(position(48.8566, 2.3522, 1_728_894_600), &signer);
+        assert_eq!(record.provenance, KeyProvenance::AppleSecureEnclave);
+        verify_signed_position(&record).unwrap();
+        assert!(serde_json::to_string(&record)
+            .unwrap()
+            .contains(r#""provenance":"apple_secure_enclave""#));
+    }
+}
+
+#[test]
+fn high_s_signatures_normalized() {
+    let signature: p256::ecdsa::Signature =
+        p256::ecdsa::signature::hazmat::PrehashSigner::sign_prehash(&p256_key(1), &[3; 32])
+            .unwrap();
+    let low = signature.normalize_s().unwrap_or(signature);
+    // s replaced by n - s, which verifies just the same under ECDSA
+    let (r, s) = low.split_scalars();
+    let high = p256::ecdsa::Signature::from_scalars(r, -*s).unwrap();
+    assert!(high.normalize_s().is_some());
+    let normalized = normalize(Scheme::P256, high.to_der().as_bytes()).unwrap();
+    assert_eq!(normalized, <[u8; 64]>::from(low.to_bytes()));
+    assert_eq!(
+        normalize(Scheme::P256, b"not der"),
+        Err(EnclaveError::MalformedSignature)
+    );
+}
+
+/// Backend returning the signatures of another key
+struct Faulty(SoftwareBackend, SoftwareBackend);
+
+impl KeyBackend for Faulty {
+    fn scheme(&self) -> Scheme {
+        self.0.scheme()
+    }
+
+    fn provenance(&self) -> KeyProvenance {
+        self.0.provenance()
+    }
+
+    fn public_key(&self) -> Vec<u8> {
+        self.0.public_key()
+    }
+
+    fn sign_prehash(&self, digest: &[u8; 32]) -> Result<Vec<u8>, EnclaveError> {
+        self.1.sign_prehash(digest)
+    }
+}
+
+#[test]
+fn bad_signatures_caught() {
+    let signer = EnclaveSigner::new(Faulty(
+        backend(SoftwareKey::P256(p256_key(1))),
+        backend(SoftwareKey::P256(p256_key(2))),
+    ))
+    .unwrap();
+    assert_eq!(
+        signer.try_sign_digest(&[3; 32]),
+        Err(EnclaveError::BadSignature)
+    );
+}
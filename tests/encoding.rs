@@ -0,0 +1,102 @@
+//! Hex and base64 encodings of public keys and signatures
+
+mod common;
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use common::{position, secret_key};
+use sign_data_rust::encoding::{self, Encoding};
+use sign_data_rust::{sign_position, verify_signed_position};
+
+/// 64 bytes whose base64 holds the characters told apart by the alphabets, `+/` or `-_`
+const BYTES: [u8; 64] = [0xfb; 64];
+
+#[test]
+fn every_alphabet_decodes() {
+    for (text, expected) in [
+        (hex::encode(BYTES), Encoding::Hex),
+        (STANDARD.encode(BYTES), Encoding::Base64),
+        (STANDARD_NO_PAD.encode(BYTES), Encoding::Base64Unpadded),
+        (URL_SAFE.encode(BYTES), Encoding::Base64Url),
+        (URL_SAFE_NO_PAD.encode(BYTES), Encoding::Base64UrlUnpadded),
+    ] {
+        assert_eq!(
+            encoding::decode(&text, &[64]),
+            Some((BYTES.to_vec(), expected)),
+            "{}",
+            text
+        );
+        assert_eq!(encoding::detect_signature(&text), Some(expected));
+    }
+    assert!(STANDARD.encode(BYTES).ends_with("+/v7+w=="));
+    assert!(URL_SAFE_NO_PAD.encode(BYTES).ends_with("-_v7-w"));
+}
+
+#[test]
+fn record_fields_verify_in_every_alphabet() {
+    let record = sign_position(position(48.8566, 2.3522, 1_728_894_600), &secret_key(1));
+    let public_key = hex::decode(&record.public_key).unwrap();
+    let signature = hex::decode(&record.signature).unwrap();
+    for engine in [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD] {
+        let mut record = record.clone();
+        record.public_key = engine.encode(&public_key);
+        record.signature = engine.encode(&signature);
+        assert_eq!(verify_signed_position(&record), Ok(()));
+    }
+    assert_eq!(
+        encoding::detect_public_key(&record.public_key),
+        Some(Encoding::Hex)
+    );
+    assert_eq!(
+        encoding::detect_public_key(&STANDARD.encode(&public_key)),
+        Some(Encoding::Base64)
+    );
+}
+
+#[test]
+fn wrong_length_is_not_decoded() {
+    let short = STANDARD.encode(&BYTES[..63]);
+    assert_eq!(Encoding::Base64.decode(&short), Some(BYTES[..63].to_vec()));
+    assert_eq!(encoding::decode(&short, &[64]), None);
+    assert_eq!(encoding::detect_signature(&short), None);
+    assert_eq!(encoding::detect_signature(&hex::encode(&BYTES[..63])), None);
+    // a signature is not a public key
+    assert_eq!(encoding::detect_public_key(&hex::encode(BYTES)), None);
+}
+
+#[test]
+fn malformed_text_is_not_decoded() {
+    let standard = STANDARD.encode(BYTES);
+    for text in [
+        String::new(),
+        "not an encoding!".to_string(),
+        // both alphabets at once
+        standard.replacen('+', "-", 1),
+        // padding in the middle
+        format!("{}==", &standard[..84]) + &standard[86..],
+        // an odd number of hex digits
+        hex::encode(BYTES)[1..].to_string(),
+    ] {
+        assert_eq!(encoding::decode(&text, &[63, 64, 65]), None, "{}", text);
+    }
+}
+
+#[test]
+fn hex_is_tried_first() {
+    // 8 hex digits are also 6 bytes of base64
+    assert_eq!(
+        encoding::decode("deadbeef", &[4, 6]),
+        Some((vec![0xde, 0xad, 0xbe, 0xef], Encoding::Hex))
+    );
+    assert_eq!(
+        encoding::decode("deadbeef", &[6]).map(|(_, encoding)| encoding),
+        Some(Encoding::Base64)
+    );
+    // 128 hex digits, a signature as hex, or 96 bytes as base64
+    let text = hex::encode(BYTES);
+    assert_eq!(STANDARD.decode(&text).unwrap().len(), 96);
+    assert_eq!(
+        encoding::decode(&text, &[64, 96]),
+        Some((BYTES.to_vec(), Encoding::Hex))
+    );
+}
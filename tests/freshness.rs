@@ -0,0 +1,116 @@
+//! Staleness checks of verified records
+
+mod common;
+
+use std::time::Duration;
+
+use common::{position, secret_key};
+use sign_data_rust::clock::MockClock;
+use sign_data_rust::freshness::{Freshness, FreshnessPolicy};
+use sign_data_rust::{sign_position, verify_signed_position};
+
+const TAKEN: u64 = 1_728_894_660;
+
+fn policy() -> FreshnessPolicy {
+    FreshnessPolicy {
+        max_age: Some(Duration::from_secs(300)),
+        max_future: Some(Duration::from_secs(30)),
+    }
+}
+
+#[test]
+fn fixes_at_the_bounds_are_fresh() {
+    let fix = position(48.8566, 2.3522, TAKEN);
+    let clock = MockClock::new(TAKEN * 1000);
+    assert_eq!(policy().check(&fix, &clock), Freshness::Fresh);
+
+    clock.set((TAKEN + 300) * 1000);
+    assert_eq!(policy().check(&fix, &clock), Freshness::Fresh);
+    clock.set((TAKEN - 30) * 1000);
+    assert_eq!(policy().check(&fix, &clock), Freshness::Fresh);
+
+    // no bound, any age
+    clock.set(0);
+    assert_eq!(
+        FreshnessPolicy::default().check(&fix, &clock),
+        Freshness::Fresh
+    );
+}
+
+#[test]
+fn old_fixes_are_stale() {
+    let fix = position(48.8566, 2.3522, TAKEN);
+    let clock = MockClock::new((TAKEN + 300) * 1000 + 1);
+    assert_eq!(
+        policy().check(&fix, &clock),
+        Freshness::Stale {
+            age: Duration::from_millis(300_001)
+        }
+    );
+    clock.advance(3_599_999);
+    let verdict = policy().check(&fix, &clock);
+    assert!(!verdict.is_fresh());
+    assert_eq!(verdict.to_string(), "stale, taken 3900s ago");
+}
+
+#[test]
+fn fixes_ahead_of_the_clock_are_from_the_future() {
+    let fix = position(48.8566, 2.3522, TAKEN);
+    let clock = MockClock::new((TAKEN - 30) * 1000 - 1);
+    assert_eq!(
+        policy().check(&fix, &clock),
+        Freshness::FromTheFuture {
+            ahead: Duration::from_millis(30_001)
+        }
+    );
+    clock.set((TAKEN - 3600) * 1000);
+    assert_eq!(
+        policy().check(&fix, &clock).to_string(),
+        "from the future, 3600s ahead"
+    );
+}
+
+#[test]
+fn records_are_honored_up_to_their_expiry() {
+    let mut fix = position(48.8566, 2.3522, TAKEN);
+    fix.expires_at = Some(TAKEN + 60);
+    let clock = MockClock::new((TAKEN + 60) * 1000);
+    // not yet expired, and expiring at that very second
+    assert_eq!(
+        FreshnessPolicy::default().check(&fix, &clock),
+        Freshness::Fresh
+    );
+
+    clock.advance(1);
+    assert_eq!(
+        FreshnessPolicy::default().check(&fix, &clock),
+        Freshness::Expired {
+            since: Duration::from_millis(1)
+        }
+    );
+    // expired whatever the policy, even when fresh enough
+    clock.advance(9_999);
+    let verdict = policy().check(&fix, &clock);
+    assert_eq!(verdict.to_string(), "expired 10s ago");
+
+    // without an expiry, only the policy applies
+    fix.expires_at = None;
+    assert_eq!(policy().check(&fix, &clock), Freshness::Fresh);
+    clock.set((TAKEN + 301) * 1000);
+    assert!(matches!(
+        policy().check(&fix, &clock),
+        Freshness::Stale { .. }
+    ));
+}
+
+#[test]
+fn expiry_is_signed() {
+    let mut fix = position(48.8566, 2.3522, TAKEN);
+    fix.expires_at = Some(TAKEN + 60);
+    let mut record = sign_position(fix, &secret_key(1));
+    assert!(verify_signed_position(&record).is_ok());
+    record.position.expires_at = Some(TAKEN + 3600);
+    assert!(verify_signed_position(&record).is_err());
+    record.position.expires_at = None;
+    assert!(verify_signed_position(&record).is_err());
+}
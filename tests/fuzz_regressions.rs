@@ -0,0 +1,96 @@
+//! Replay of the inputs kept in `fuzz/regressions/<target>/` through the entry points of the fuzz
+//! targets, so that `cargo test` catches a crash coming back without a nightly toolchain
+
+use std::fs;
+use std::path::Path;
+
+use sign_data_rust::cosign::verify_threshold;
+use sign_data_rust::freshness::Freshness;
+use sign_data_rust::scheme::{Scheme, Signer, VerifyingKey};
+use sign_data_rust::{
+    encoding, gpx, kml, track, tsa, verify_signed_position, wire, SignedPosition,
+};
+
+/// Feed every input of the regressions of `target` to `run`, none of them may panic
+fn replay(target: &str, run: fn(&[u8])) {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz/regressions")
+        .join(target);
+    let Ok(entries) = fs::read_dir(&directory) else {
+        return;
+    };
+    for entry in entries {
+        let path = entry.unwrap().path();
+        let data = fs::read(&path).unwrap();
+        let result = std::panic::catch_unwind(|| run(&data));
+        assert!(result.is_ok(), "{} panicked", path.display());
+    }
+}
+
+#[test]
+fn gpx_import() {
+    replay("gpx_import", |data| {
+        let Ok(document) = std::str::from_utf8(data) else {
+            return;
+        };
+        if let Ok(points) = gpx::read_gpx(document) {
+            for point in points {
+                let _ = wire::encode(&point.to_position(0));
+            }
+        }
+    });
+}
+
+#[test]
+fn signature_decoding() {
+    replay("signature_decoding", |data| {
+        let Some((&split, rest)) = data.split_first() else {
+            return;
+        };
+        let (digest, text) = rest.split_at((split as usize).min(rest.len()));
+        let Ok(text) = std::str::from_utf8(text) else {
+            return;
+        };
+
+        let _ = encoding::decode(text, &[33, 64, 65]);
+        let _ = encoding::detect_public_key(text);
+        let _ = encoding::detect_signature(text);
+
+        let secret_key = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let signing_key = p256::ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+        for (scheme, public_key) in [
+            (Scheme::Secp256k1, Signer::public_key(&secret_key)),
+            (Scheme::P256, Signer::public_key(&signing_key)),
+        ] {
+            let _ = VerifyingKey::parse(scheme, text);
+            let key = VerifyingKey::parse(scheme, &public_key).unwrap();
+            let _ = key.verify(digest, text);
+        }
+    });
+}
+
+#[test]
+fn signed_position() {
+    replay("signed_position", |data| {
+        for record in serde_json::Deserializer::from_slice(data).into_iter::<SignedPosition>() {
+            let Ok(record) = record else { break };
+            let result = verify_signed_position(&record);
+            let _ = verify_threshold(&record, &[], 1);
+            let _ = tsa::verify_position_timestamp(&record, 60);
+
+            let _ = gpx::write_gpx(&mut std::io::sink(), &[&record], 0);
+            let mut writer = kml::KmlWriter::new(std::io::sink(), "fuzz").unwrap();
+            let _ = writer.add(&record, &result, &Freshness::Fresh);
+            let _ = writer.finish();
+        }
+    });
+}
+
+#[test]
+fn track_container() {
+    replay("track_container", |data| {
+        if let Ok(track) = track::unpack(data) {
+            let _ = track::verify_track(&track);
+        }
+    });
+}
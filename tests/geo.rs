@@ -0,0 +1,58 @@
+//! Great-circle helpers against reference geodesics
+
+mod common;
+
+use common::position;
+use sign_data_rust::geo::{bearing_deg, destination, distance_m, normalize_longitude};
+
+/// Latitudes and longitudes of two cities, with their distance in meters
+type CityPair = ((f64, f64), (f64, f64), f64);
+
+/// WGS-84 ellipsoidal distances, from Vincenty's inverse formula
+const CITY_PAIRS: [CityPair; 5] = [
+    ((48.8566, 2.3522), (51.5074, -0.1278), 343_923.0),
+    ((40.7128, -74.0060), (34.0522, -118.2437), 3_944_422.0),
+    ((-33.8688, 151.2093), (35.6762, 139.6503), 7_792_175.0),
+    ((-22.9068, -43.1729), (-33.9249, 18.4241), 6_069_412.0),
+    ((64.1466, -21.9426), (-36.8485, 174.7633), 16_769_129.0),
+];
+
+#[test]
+fn city_pairs_within_half_a_percent() {
+    for ((lat1, lon1), (lat2, lon2), expected) in CITY_PAIRS {
+        let (a, b) = (position(lat1, lon1, 0), position(lat2, lon2, 0));
+        for distance in [distance_m(&a, &b), distance_m(&b, &a)] {
+            let error = (distance - expected).abs() / expected;
+            assert!(error < 0.005, "{:?} {:?}: {} m", a, b, distance);
+        }
+    }
+}
+
+#[test]
+fn destination_inverts_distance_and_bearing() {
+    for ((lat1, lon1), (lat2, lon2), _) in CITY_PAIRS {
+        let (a, b) = (position(lat1, lon1, 7), position(lat2, lon2, 0));
+        let reached = destination(&a, bearing_deg(&a, &b), distance_m(&a, &b));
+        assert!(distance_m(&reached, &b) < 1.0, "{:?}", reached);
+        assert_eq!(reached.timestamp, 7);
+    }
+}
+
+#[test]
+fn bearings() {
+    let origin = position(0.0, 0.0, 0);
+    assert_eq!(bearing_deg(&origin, &origin), 0.0);
+    assert!((bearing_deg(&origin, &position(0.0, 1.0, 0)) - 90.0).abs() < 1e-9);
+    assert!((bearing_deg(&origin, &position(-1.0, 0.0, 0)) - 180.0).abs() < 1e-9);
+    assert!((bearing_deg(&origin, &position(0.0, -1.0, 0)) - 270.0).abs() < 1e-9);
+}
+
+#[test]
+fn antimeridian() {
+    let (a, b) = (position(0.0, 179.5, 0), position(0.0, -179.5, 0));
+    assert!((distance_m(&a, &b) - 111_195.0).abs() < 1.0);
+    let reached = destination(&a, 90.0, distance_m(&a, &b));
+    assert!((reached.longitude + 179.5).abs() < 1e-9);
+    assert_eq!(normalize_longitude(180.0), -180.0);
+    assert_eq!(normalize_longitude(-540.0), -180.0);
+}
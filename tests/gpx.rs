@@ -0,0 +1,95 @@
+//! GPX export and import
+
+mod common;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::gpx::{read_gpx, write_gpx, GpxError, GpxPoint};
+use sign_data_rust::{sign_position, wire, Position};
+
+#[test]
+fn gpx_round_trip() {
+    let mut positions = [
+        position(48.85661234567891, 2.352212345678912, 1_728_894_660),
+        position(-33.8688, 151.2093, 1_728_894_600),
+        position(0.1 + 0.2, -179.99999999999997, 1_728_894_720),
+    ];
+    positions[0].altitude = Some(35.25);
+    let records = [
+        sign_position(positions[0].clone(), &secret_key(1)),
+        sign_position(positions[1].clone(), &p256_key(2)),
+        sign_position(positions[2].clone(), &secret_key(1)),
+    ];
+
+    let mut out = Vec::new();
+    write_gpx(&mut out, &records.iter().collect::<Vec<_>>(), 4).unwrap();
+    let document = String::from_utf8(out).unwrap();
+    assert!(document.contains(r#"<sgc:verification verified="3" excluded="4"/>"#));
+    assert_eq!(document.matches("<sgc:public_key ").count(), 2);
+
+    let read: Vec<Position> = read_gpx(&document)
+        .unwrap()
+        .iter()
+        .map(|point| point.to_position(0))
+        .collect();
+    // written in timestamp order, every coordinate reading back to the same f64
+    let expected = [&positions[1], &positions[0], &positions[2]];
+    assert_eq!(read.iter().collect::<Vec<_>>(), expected);
+    for (read, original) in read.iter().zip(expected) {
+        assert_eq!(wire::digest(read), wire::digest(original));
+    }
+}
+
+#[test]
+fn routes_and_waypoints() {
+    let document = r#"<?xml version="1.0"?>
+<gpx version="1.1" creator="other">
+  <wpt lat="1.5" lon="2.5"><name>start</name></wpt>
+  <rte><rtept lon='-3' lat='4'><ele>-12.5</ele></rtept></rte>
+  <trk><trkseg><trkpt lat="5" lon="6"><time>2024-10-14T08:30:00Z</time></trkpt></trkseg></trk>
+</gpx>"#;
+    let points = read_gpx(document).unwrap();
+    assert_eq!(
+        points,
+        [
+            GpxPoint {
+                latitude: 1.5,
+                longitude: 2.5,
+                altitude: None,
+                time: None
+            },
+            GpxPoint {
+                latitude: 4.0,
+                longitude: -3.0,
+                altitude: Some(-12.5),
+                time: None
+            },
+            GpxPoint {
+                latitude: 5.0,
+                longitude: 6.0,
+                altitude: None,
+                time: Some(1_728_894_600)
+            },
+        ]
+    );
+    assert_eq!(points[0].to_position(7).timestamp, 7);
+}
+
+#[test]
+fn malformed_points() {
+    let point = |element: &str| format!("<gpx><trk><trkseg>{}</trkseg></trk></gpx>", element);
+    for (element, err) in [
+        (r#"<trkpt lat="1"/>"#, GpxError::BadCoordinates(0)),
+        (r#"<trkpt lat="1" lon="x"/>"#, GpxError::BadCoordinates(0)),
+        (
+            r#"<trkpt lat="1" lon="2"><ele>high</ele></trkpt>"#,
+            GpxError::BadElevation(0),
+        ),
+        (
+            r#"<trkpt lat="1" lon="2"><time>yesterday</time></trkpt>"#,
+            GpxError::BadTime(0),
+        ),
+        (r#"<trkpt lat="1" lon="2">"#, GpxError::Unterminated(0)),
+    ] {
+        assert_eq!(read_gpx(&point(element)), Err(err), "{}", element);
+    }
+}
@@ -0,0 +1,55 @@
+//! KML export
+
+mod common;
+
+use std::time::Duration;
+
+use common::{position, secret_key};
+use sign_data_rust::freshness::Freshness;
+use sign_data_rust::kml::KmlWriter;
+use sign_data_rust::{sign_position, VerifyError};
+
+#[test]
+fn placemarks_show_freshness() {
+    let record = sign_position(position(48.8566, 2.3522, 1_728_894_660), &secret_key(1));
+    let mut writer = KmlWriter::new(Vec::new(), "track").unwrap();
+    let age = Duration::from_secs(600);
+    for freshness in [
+        Freshness::Fresh,
+        Freshness::Stale { age },
+        Freshness::FromTheFuture { ahead: age },
+        Freshness::Expired { since: age },
+    ] {
+        writer.add(&record, &Ok(()), &freshness).unwrap();
+    }
+    // an invalid record is shown as such whatever its age
+    writer
+        .add(
+            &record,
+            &Err(VerifyError::UnsupportedVersion(1)),
+            &Freshness::Stale { age },
+        )
+        .unwrap();
+    let document = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+    let styles: Vec<&str> = document
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("<styleUrl>#"))
+        .map(|line| line.trim_end_matches("</styleUrl>"))
+        .collect();
+    assert_eq!(
+        styles,
+        ["valid", "stale", "future", "expired", "invalid", "track"]
+    );
+    for status in [
+        "Status:&lt;/b&gt; valid&lt;",
+        "Status:&lt;/b&gt; valid but stale, taken 600s ago",
+        "Status:&lt;/b&gt; valid but from the future, 600s ahead",
+        "Status:&lt;/b&gt; valid but expired 600s ago",
+    ] {
+        assert!(document.contains(status), "{}", status);
+    }
+    // only the current valid record joins the track
+    let track = document.split("<LineString>").nth(1).unwrap();
+    assert_eq!(track.matches("2.3522,48.8566").count(), 1);
+}
@@ -0,0 +1,146 @@
+//! LoRaWAN uplink frames of signed fixes
+
+mod common;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::geo::distance_m;
+use sign_data_rust::lora::{
+    pack, LoraError, Reassembler, FIX_FRAME_LENGTH, MAX_PENDING, SIGNATURE_FRAME_LENGTH,
+};
+use sign_data_rust::scheme::{Signer, VerifyingKey};
+use sign_data_rust::{verify_signed_position, Position};
+
+const EPOCH: u64 = 1_728_000_000;
+
+fn reassembler(signer: &dyn Signer) -> Reassembler {
+    Reassembler::new(
+        EPOCH,
+        VerifyingKey::parse(signer.scheme(), &signer.public_key()).unwrap(),
+    )
+}
+
+fn round_trip(position: &Position, signer: &dyn Signer) -> Position {
+    let [fix, signature] = pack(position, EPOCH, 7, signer).unwrap();
+    assert_eq!(fix.len(), FIX_FRAME_LENGTH);
+    assert_eq!(signature.len(), SIGNATURE_FRAME_LENGTH);
+    let mut reassembler = reassembler(signer);
+    assert_eq!(reassembler.push(&fix).unwrap(), None);
+    let record = reassembler.push(&signature).unwrap().unwrap();
+    verify_signed_position(&record).unwrap();
+    record.position
+}
+
+#[test]
+fn boundary_coordinates() {
+    for (latitude, longitude) in [
+        (90.0, 180.0),
+        (-90.0, -180.0),
+        (0.0, 0.0),
+        (90.0, -180.0),
+        (-89.9999999, 179.9999999),
+        (48.8566, 2.3522),
+    ] {
+        for signer in [&secret_key(1) as &dyn Signer, &p256_key(2)] {
+            let original = position(latitude, longitude, EPOCH + 42);
+            let received = round_trip(&original, signer);
+            assert_eq!(received.timestamp, EPOCH + 42);
+            assert!(received.latitude.abs() <= 90.0 && received.longitude.abs() <= 180.0);
+            // within the 1.2 by 2.4 meters of the fixed point grid
+            assert!(distance_m(&original, &received) < 1.4, "{:?}", received);
+        }
+    }
+    let time = |timestamp| pack(&position(0.0, 0.0, timestamp), EPOCH, 0, &secret_key(1));
+    assert_eq!(time(EPOCH - 1).unwrap_err(), LoraError::OutsideSession);
+    assert_eq!(
+        time(EPOCH + (1 << 32)).unwrap_err(),
+        LoraError::OutsideSession
+    );
+    assert!(time(EPOCH + u32::MAX as u64).is_ok());
+    assert!(pack(&position(90.5, 0.0, EPOCH), EPOCH, 0, &secret_key(1)).is_err());
+}
+
+#[test]
+fn reordered_frames() {
+    let frames: Vec<[Vec<u8>; 2]> = (0..3)
+        .map(|sequence| {
+            let position = position(48.8566, 2.3522, EPOCH + sequence as u64);
+            pack(&position, EPOCH, sequence, &secret_key(1)).unwrap()
+        })
+        .collect();
+    let mut reassembler = reassembler(&secret_key(1));
+    // signatures first, then fixes in reverse order
+    for [_, signature] in &frames {
+        assert_eq!(reassembler.push(signature).unwrap(), None);
+    }
+    for (sequence, [fix, _]) in frames.iter().enumerate().rev() {
+        let record = reassembler.push(fix).unwrap().unwrap();
+        assert_eq!(record.position.timestamp, EPOCH + sequence as u64);
+        verify_signed_position(&record).unwrap();
+    }
+    assert_eq!(reassembler.dropped(), 0);
+}
+
+#[test]
+fn duplicated_frames() {
+    let [fix, signature] = pack(&position(1.0, 1.0, EPOCH), EPOCH, 3, &secret_key(1)).unwrap();
+    let mut reassembler = reassembler(&secret_key(1));
+    assert_eq!(reassembler.push(&fix).unwrap(), None);
+    assert_eq!(reassembler.push(&fix).unwrap(), None);
+    let record = reassembler.push(&signature).unwrap().unwrap();
+    verify_signed_position(&record).unwrap();
+    // a frame arriving again after its fix completed starts a new pending fix
+    assert_eq!(reassembler.push(&signature).unwrap(), None);
+
+    // a replaced frame of another fix under the same sequence number fails verification
+    let [other, _] = pack(&position(2.0, 2.0, EPOCH), EPOCH, 3, &secret_key(1)).unwrap();
+    let record = reassembler.push(&other).unwrap().unwrap();
+    assert!(verify_signed_position(&record).is_err());
+}
+
+#[test]
+fn incomplete_fixes_dropped() {
+    let mut reassembler = reassembler(&secret_key(1));
+    let frames: Vec<[Vec<u8>; 2]> = (0..=MAX_PENDING as u16)
+        .map(|sequence| pack(&position(1.0, 1.0, EPOCH), EPOCH, sequence, &secret_key(1)).unwrap())
+        .collect();
+    for [fix, _] in &frames {
+        assert_eq!(reassembler.push(fix).unwrap(), None);
+    }
+    assert_eq!(reassembler.dropped(), 1);
+    // the oldest was dropped, its signature now waits for a fix of its own
+    assert_eq!(reassembler.push(&frames[0][1]).unwrap(), None);
+    assert!(reassembler.push(&frames[MAX_PENDING][1]).unwrap().is_some());
+}
+
+#[test]
+fn malformed_frames() {
+    let [fix, signature] = pack(&position(1.0, 1.0, EPOCH), EPOCH, 0, &secret_key(1)).unwrap();
+    let mut reassembler = reassembler(&secret_key(1));
+    assert_eq!(
+        reassembler.push(&fix[..45]).unwrap_err(),
+        LoraError::BadLength {
+            frame: 0,
+            length: 45
+        }
+    );
+    assert_eq!(
+        reassembler.push(&[0x21; 35]).unwrap_err(),
+        LoraError::UnknownFormat(0x21)
+    );
+    let mut flagged = fix.clone();
+    flagged[13] = 2;
+    assert_eq!(
+        reassembler.push(&flagged).unwrap_err(),
+        LoraError::UnknownFlags(2)
+    );
+    assert_eq!(
+        Reassembler::new(
+            EPOCH,
+            VerifyingKey::parse(p256_key(2).scheme(), &p256_key(2).public_key()).unwrap()
+        )
+        .push(&fix)
+        .unwrap_err(),
+        LoraError::SchemeMismatch
+    );
+    assert!(reassembler.push(&signature).unwrap().is_none());
+}
@@ -0,0 +1,176 @@
+//! Multibase and multicodec vectors, as encoded by the `multibase` and `unsigned-varint` crates
+
+mod common;
+
+use common::{p256_key, secret_key};
+use sha2::{Digest, Sha256};
+use sign_data_rust::multiformats::{
+    self, MultiformatError, ES256, ES256K, P256_PUB, SECP256K1_PUB, SHA2_256,
+};
+use sign_data_rust::scheme::{Scheme, Signer, VerifyingKey};
+
+const SECP256K1_KEY: &str = "zQ3shgVXZLaMzm5S5x7XzGUG6YFHFLtoEMiv9ao2Bqa7hGyg2";
+const P256_KEY: &str = "zDnaeXxvmFHMHjqgQTbadpWG7gPHwnga1i7SMwxrV2BSdUjAD";
+const ES256K_ZEROS: &str =
+    "z7sD8GHfobrcbUBMhSm7LkLaHJmfSAxhnWN8CAMayiqsFmHJeycuN6FAdgQv87aHs2Txyt3pEX3pJAvA1kbhfdsbRYMzo";
+const ES256_SEVENS: &str =
+    "zHq9vjFFJqhMPoYixGVWKhMYu5KWZNWuHziso8nUVa1kk3ueEgRRueYxGD3AF6Gt1AnQNNNzNxQcjAug3hSmSWvtLKDVzE";
+const EMPTY_DIGEST: &str = "zQmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n";
+
+fn key(scheme: Scheme, signer: &dyn Signer) -> VerifyingKey {
+    VerifyingKey::parse(scheme, &signer.public_key()).unwrap()
+}
+
+#[test]
+fn encoded_as_the_reference() {
+    assert_eq!(
+        multiformats::encode_key(key(Scheme::Secp256k1, &secret_key(1))),
+        SECP256K1_KEY
+    );
+    assert_eq!(
+        multiformats::encode_key(key(Scheme::P256, &p256_key(1))),
+        P256_KEY
+    );
+    assert_eq!(
+        multiformats::encode_signature(Scheme::Secp256k1, &[0; 64]),
+        ES256K_ZEROS
+    );
+    assert_eq!(
+        multiformats::encode_signature(Scheme::P256, &[7; 64]),
+        ES256_SEVENS
+    );
+    assert_eq!(
+        multiformats::encode_digest(&Sha256::digest(b"")),
+        EMPTY_DIGEST
+    );
+
+    // the multicodec varints, in base16
+    for (codec, varint) in [
+        (SECP256K1_PUB, "e701"),
+        (P256_PUB, "8024"),
+        (ES256K, "e7a103"),
+        (ES256, "80a4c006"),
+        (SHA2_256, "12"),
+    ] {
+        assert_eq!(
+            multiformats::decode(&format!("f{}", varint)).unwrap(),
+            (codec, vec![])
+        );
+    }
+}
+
+#[test]
+fn every_base_decodes_to_the_same_bytes() {
+    for strings in [
+        [
+            SECP256K1_KEY,
+            "fe701031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f",
+            "m5wEDG4TFVnsSZECZXT7VqroFZdceGDRgSBn/nBf16dXdB48",
+            "u5wEDG4TFVnsSZECZXT7VqroFZdceGDRgSBn_nBf16dXdB48",
+        ],
+        [
+            P256_KEY,
+            "f8024026ff03b949241ce1dadd43519e6960e0a85b41a69a05c328103aa2bce1594ca16",
+            "mgCQCb/A7lJJBzh2t1DUZ5pYOCoW0GmmgXDKBA6orzhWUyhY",
+            "ugCQCb_A7lJJBzh2t1DUZ5pYOCoW0GmmgXDKBA6orzhWUyhY",
+        ],
+        [
+            EMPTY_DIGEST,
+            "f1220e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "mEiDjsMRCmPwcFJr79MiZb7kkJ65B5GSbk0yklZkbeFK4VQ",
+            "uEiDjsMRCmPwcFJr79MiZb7kkJ65B5GSbk0yklZkbeFK4VQ",
+        ],
+    ] {
+        let expected = multiformats::decode(strings[0]).unwrap();
+        for text in &strings[1..] {
+            assert_eq!(multiformats::decode(text).unwrap(), expected, "{}", text);
+        }
+    }
+    assert_eq!(
+        multiformats::decode_key(SECP256K1_KEY).unwrap(),
+        key(Scheme::Secp256k1, &secret_key(1))
+    );
+    assert_eq!(
+        multiformats::decode_key("ugCQCb_A7lJJBzh2t1DUZ5pYOCoW0GmmgXDKBA6orzhWUyhY").unwrap(),
+        key(Scheme::P256, &p256_key(1))
+    );
+    assert_eq!(
+        multiformats::decode_signature(ES256_SEVENS).unwrap(),
+        (Scheme::P256, vec![7; 64])
+    );
+    assert_eq!(
+        multiformats::decode_digest(EMPTY_DIGEST).unwrap(),
+        Sha256::digest(b"").to_vec()
+    );
+}
+
+#[test]
+fn unsupported_codec_is_rejected() {
+    // an ed25519-pub key
+    let ed25519 = "z6MkeXBLjYiSvqnhFb6D7sHm8yKm4jV45wwBFRaatf1cfZ76";
+    assert_eq!(
+        multiformats::decode_key(ed25519),
+        Err(MultiformatError::UnsupportedCodec(0xed))
+    );
+    assert_eq!(
+        multiformats::decode_signature(ed25519),
+        Err(MultiformatError::UnsupportedCodec(0xed))
+    );
+    assert_eq!(
+        multiformats::decode_digest(ed25519),
+        Err(MultiformatError::UnsupportedCodec(0xed))
+    );
+
+    // a codec of another kind
+    assert_eq!(
+        multiformats::decode_key(ES256K_ZEROS),
+        Err(MultiformatError::UnexpectedCodec(ES256K))
+    );
+    assert_eq!(
+        multiformats::decode_signature(SECP256K1_KEY),
+        Err(MultiformatError::UnexpectedCodec(SECP256K1_PUB))
+    );
+    assert_eq!(
+        multiformats::decode_digest(P256_KEY),
+        Err(MultiformatError::UnexpectedCodec(P256_PUB))
+    );
+}
+
+#[test]
+fn unsupported_multibase_is_rejected() {
+    // base32, base16 upper case, base10 and base58flickr
+    for text in [
+        "b44aqc",
+        "FE70101",
+        "9123",
+        "Z6MkeXBLjYiSvqnhFb6D7sHm8yKm4jV45wwBFRaatf1cfZ76",
+    ] {
+        let prefix = text.chars().next().unwrap();
+        assert_eq!(
+            multiformats::decode(text),
+            Err(MultiformatError::UnsupportedBase(prefix))
+        );
+        assert_eq!(
+            multiformats::decode_key(text),
+            Err(MultiformatError::UnsupportedBase(prefix))
+        );
+    }
+    // no prefix, or digits outside the alphabet of the base
+    for text in ["", "z0OIl", "fe7g", "m5wED*"] {
+        assert_eq!(
+            multiformats::decode(text),
+            Err(MultiformatError::MalformedBase),
+            "{}",
+            text
+        );
+    }
+    // varints unsigned-varint refuses as not minimal, or cut short
+    for text in ["fe78100", "f8000", "fe7", "f"] {
+        assert_eq!(
+            multiformats::decode(text),
+            Err(MultiformatError::MalformedVarint),
+            "{}",
+            text
+        );
+    }
+}
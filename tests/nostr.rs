@@ -0,0 +1,129 @@
+//! NIP-01 events carrying signed positions
+
+mod common;
+
+use common::{position, secret_key};
+use secp256k1::{Keypair, Message, Secp256k1};
+use sha2::{Digest, Sha256};
+use sign_data_rust::nostr::{self, Event, NostrError, LOCATION_KIND};
+use sign_data_rust::sign_position;
+
+/// The event of the vector in the `nostr` module documentation
+fn event() -> Event {
+    let record = sign_position(position(48.85, 2.35, 1_700_000_000), &secret_key(1));
+    nostr::to_event(&record, &secret_key(1), 1_700_000_100).unwrap()
+}
+
+/// Sign `event` again after an edit, so that only the edit is checked
+fn resign(event: &mut Event) {
+    let id = nostr::event_id(
+        &event.pubkey,
+        event.created_at,
+        event.kind,
+        &event.tags,
+        &event.content,
+    );
+    let secp = Secp256k1::new();
+    let keypair = Keypair::from_secret_key(&secp, &secret_key(1));
+    let sig = secp.sign_schnorr_no_aux_rand(&Message::from_digest(id), &keypair);
+    event.id = hex::encode(id);
+    event.sig = hex::encode(sig.serialize());
+}
+
+/// Flip the last hex digit of a field
+fn flip(field: &mut String) {
+    let last = field.pop().unwrap();
+    field.push(if last == '0' { '1' } else { '0' });
+}
+
+#[test]
+fn event_matches_vector() {
+    let event = event();
+    let content = concat!(
+        r#"{"version":2,"position":{"latitude":48.85,"longitude":2.35,"timestamp":1700000000},"#,
+        r#""signature":"c2f876efe4d3d07c7aa3e2fe85cea4d94771f4c64d00191e3fdcb2aae2816d23"#,
+        r#"5e098a149c9b2cf1ed653810c8548aabb17ca9a725a0b78d1f05207f731f904c","#,
+        r#""public_key":"031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f","#,
+        r#""scheme":"secp256k1"}"#
+    );
+    assert_eq!(event.content, content);
+    let preimage = format!(
+        concat!(
+            r#"[0,"1b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f","#,
+            r#"1700000100,7400,[["g","u09tvkz5y"],["timestamp","1700000000"],"#,
+            r#"["alt","GPS location attestation"]],{}]"#
+        ),
+        serde_json::to_string(content).unwrap()
+    );
+    let id = "3d2d887de08a5c268436bc0a55de79dce235e8c9b634a5ced986d3539829cbde";
+    assert_eq!(hex::encode(Sha256::digest(preimage.as_bytes())), id);
+    assert_eq!(event.id, id);
+    assert_eq!(
+        event.sig,
+        concat!(
+            "7a14b935d8490d030693177b32a2b5aade22d03407144faee92b22361c3ff3be",
+            "10366bc237880275b71946c6b6278ae75f4acd385f782e9118c8d884046ca3e7"
+        )
+    );
+    assert_eq!(
+        nostr::verify_event(&event).unwrap().position.latitude,
+        48.85
+    );
+}
+
+#[test]
+fn tampered_event_is_rejected() {
+    let mut tampered = event();
+    flip(&mut tampered.id);
+    assert!(matches!(
+        nostr::verify_event(&tampered),
+        Err(NostrError::IdMismatch)
+    ));
+
+    let mut tampered = event();
+    flip(&mut tampered.sig);
+    assert!(matches!(
+        nostr::verify_event(&tampered),
+        Err(NostrError::BadSignature)
+    ));
+
+    // another cell, unsigned then signed again
+    let mut tampered = event();
+    tampered.tags[0][1] = "u09tvkz5z".to_string();
+    assert!(matches!(
+        nostr::verify_event(&tampered),
+        Err(NostrError::IdMismatch)
+    ));
+    resign(&mut tampered);
+    assert!(matches!(
+        nostr::verify_event(&tampered),
+        Err(NostrError::Tag("g"))
+    ));
+
+    let mut tampered = event();
+    tampered.created_at += 1;
+    assert!(matches!(
+        nostr::verify_event(&tampered),
+        Err(NostrError::IdMismatch)
+    ));
+    let mut tampered = event();
+    tampered.tags[1][1] = "1700000001".to_string();
+    resign(&mut tampered);
+    assert!(matches!(
+        nostr::verify_event(&tampered),
+        Err(NostrError::Tag("timestamp"))
+    ));
+
+    let mut tampered = event();
+    tampered.kind = 1;
+    assert!(matches!(
+        nostr::verify_event(&tampered),
+        Err(NostrError::WrongKind(1))
+    ));
+    resign(&mut tampered);
+    assert!(matches!(
+        nostr::verify_event(&tampered),
+        Err(NostrError::WrongKind(1))
+    ));
+    assert_eq!(event().kind, LOCATION_KIND);
+}
@@ -0,0 +1,194 @@
+//! OpenTimestamps anchoring against calendars misbehaving
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+
+use sign_data_rust::ots::{self, Attestation, DetachedTimestamp, OtsError, Timestamp};
+use sign_data_rust::transport::{Transport, TransportError};
+
+/// Calendar answering a submission with a pending attestation, unless `garbled`, then an upgrade
+/// with `upgrade`
+struct MockCalendar {
+    uri: &'static str,
+    garbled: bool,
+    upgrade: fn(&[u8]) -> Result<Vec<u8>, TransportError>,
+}
+
+/// Calendars by uri, every request being logged
+struct MockCalendars {
+    calendars: Vec<MockCalendar>,
+    requests: RefCell<Vec<String>>,
+}
+
+impl MockCalendars {
+    fn calendar(&self, url: &str) -> &MockCalendar {
+        self.requests.borrow_mut().push(url.to_string());
+        self.calendars
+            .iter()
+            .find(|calendar| url.starts_with(calendar.uri))
+            .unwrap()
+    }
+}
+
+impl Transport for MockCalendars {
+    fn post(&self, url: &str, _content_type: &str, body: &[u8]) -> Result<Vec<u8>, TransportError> {
+        let calendar = self.calendar(url);
+        if calendar.garbled {
+            return Ok(b"not a proof".to_vec());
+        }
+        let mut timestamp = Timestamp::new(body.to_vec());
+        timestamp.attestations.push(Attestation::Pending {
+            uri: calendar.uri.to_string(),
+        });
+        let mut response = Vec::new();
+        timestamp.serialize(&mut response);
+        Ok(response)
+    }
+
+    fn get(&self, url: &str) -> Result<Vec<u8>, TransportError> {
+        let calendar = self.calendar(url);
+        let msg = hex::decode(url.rsplit('/').next().unwrap()).unwrap();
+        (calendar.upgrade)(&msg)
+    }
+}
+
+fn anchored(msg: &[u8]) -> Result<Vec<u8>, TransportError> {
+    let mut timestamp = Timestamp::new(msg.to_vec());
+    timestamp
+        .attestations
+        .push(Attestation::Bitcoin { height: 860_000 });
+    let mut response = Vec::new();
+    timestamp.serialize(&mut response);
+    Ok(response)
+}
+
+fn batch(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("ots-{}-{}.jsonl", std::process::id(), name));
+    fs::write(&path, b"{\"record\":1}\n").unwrap();
+    path
+}
+
+#[test]
+fn malformed_calendar_answer_is_skipped() {
+    let calendars = MockCalendars {
+        calendars: vec![
+            MockCalendar {
+                uri: "http://garbage.example",
+                garbled: false,
+                upgrade: |_| Ok(b"<html>502 Bad Gateway</html>".to_vec()),
+            },
+            MockCalendar {
+                uri: "http://down.example",
+                garbled: false,
+                upgrade: |_| Err(TransportError::Status(404)),
+            },
+            MockCalendar {
+                uri: "http://anchored.example",
+                garbled: false,
+                upgrade: anchored,
+            },
+        ],
+        requests: RefCell::new(Vec::new()),
+    };
+    let uris: Vec<&str> = calendars
+        .calendars
+        .iter()
+        .map(|calendar| calendar.uri)
+        .collect();
+    let path = batch("upgrade");
+    ots::stamp(&calendars, &uris, &path).unwrap();
+
+    let upgrade = ots::upgrade(&calendars, &path).unwrap();
+    assert!(upgrade.complete);
+    let rejected: Vec<&str> = upgrade
+        .rejected
+        .iter()
+        .map(|(uri, _)| uri.as_str())
+        .collect();
+    assert_eq!(rejected, ["http://garbage.example"]);
+    assert!(matches!(upgrade.rejected[0].1, OtsError::UnknownOp(b'<')));
+    // every calendar was asked, the garbage answer did not stop the upgrade
+    assert_eq!(calendars.requests.borrow().len(), 6);
+
+    // the pending attestations of the other calendars are kept
+    let proof = ots::read_proof(&path).unwrap();
+    let pending = proof
+        .timestamp
+        .all_attestations()
+        .iter()
+        .filter(|(_, attestation)| matches!(attestation, Attestation::Pending { .. }))
+        .count();
+    assert_eq!(pending, 2);
+    fs::remove_file(ots::proof_path(&path)).unwrap();
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn malformed_submission_answer_is_skipped() {
+    let calendars = MockCalendars {
+        calendars: vec![
+            MockCalendar {
+                uri: "http://garbage.example",
+                garbled: true,
+                upgrade: anchored,
+            },
+            MockCalendar {
+                uri: "http://anchored.example",
+                garbled: false,
+                upgrade: anchored,
+            },
+        ],
+        requests: RefCell::new(Vec::new()),
+    };
+    let path = batch("stamp");
+    let uris = ["http://garbage.example", "http://anchored.example"];
+    let proof = ots::stamp(&calendars, &uris, &path).unwrap();
+    assert_eq!(proof.timestamp.all_attestations().len(), 1);
+    assert!(matches!(
+        ots::stamp(&calendars, &uris[..1], &path),
+        Err(OtsError::NoCalendarResponded)
+    ));
+    fs::remove_file(ots::proof_path(&path)).unwrap();
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn overlong_varuint_is_rejected() {
+    let mut timestamp = Timestamp::new(vec![0; 32]);
+    timestamp
+        .attestations
+        .push(Attestation::Bitcoin { height: 860_000 });
+    let proof = DetachedTimestamp { timestamp }.serialize();
+    // the major version, 1, follows the 31 bytes of the magic
+    assert_eq!(proof[31], 0x01);
+    let with_version = |version: &[u8]| {
+        let mut data = proof[..31].to_vec();
+        data.extend_from_slice(version);
+        data.extend_from_slice(&proof[32..]);
+        DetachedTimestamp::deserialize(&data)
+    };
+    assert!(with_version(&[0x01]).is_ok());
+
+    // 1 plus 2^64, read as 1 when the bits above 63 were dropped
+    let mut wrapped = vec![0x81];
+    wrapped.extend([0x80; 8]);
+    wrapped.push(0x02);
+    // 1 plus 2^63, the largest tenth byte
+    let mut largest = wrapped.clone();
+    *largest.last_mut().unwrap() = 0x01;
+    // an eleventh byte
+    let mut eleven = wrapped.clone();
+    *eleven.last_mut().unwrap() = 0x80;
+    eleven.push(0x00);
+    assert!(matches!(
+        with_version(&largest),
+        Err(OtsError::UnsupportedVersion(version)) if version == (1 << 63) + 1
+    ));
+    for varuint in [wrapped, eleven] {
+        assert!(matches!(
+            with_version(&varuint),
+            Err(OtsError::Malformed("varuint overflow"))
+        ));
+    }
+}
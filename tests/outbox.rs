@@ -0,0 +1,230 @@
+//! Durable outbox of signed records, against an endpoint going down
+
+mod common;
+
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::outbox::{self, OutboxError, Overflow, Spool};
+use sign_data_rust::track;
+use sign_data_rust::transport::{Transport, TransportError};
+use sign_data_rust::{sign_position, SignedPosition};
+
+/// Endpoint keeping the bodies it accepted, failing every request while down
+#[derive(Default)]
+struct MockEndpoint {
+    received: RefCell<Vec<Vec<u8>>>,
+    /// Requests still accepted before going down, any when `None`
+    up_for: Cell<Option<usize>>,
+    status: Cell<Option<u16>>,
+}
+
+impl MockEndpoint {
+    fn go_down_after(&self, requests: usize, status: Option<u16>) {
+        self.up_for.set(Some(requests));
+        self.status.set(status);
+    }
+
+    fn come_back(&self) {
+        self.up_for.set(None);
+    }
+}
+
+impl Transport for MockEndpoint {
+    fn post(
+        &self,
+        _url: &str,
+        _content_type: &str,
+        body: &[u8],
+    ) -> Result<Vec<u8>, TransportError> {
+        match self.up_for.get() {
+            Some(0) => {
+                return Err(match self.status.get() {
+                    Some(status) => TransportError::Status(status),
+                    None => TransportError::Io(std::io::ErrorKind::ConnectionRefused.into()),
+                })
+            }
+            Some(left) => self.up_for.set(Some(left - 1)),
+            None => {}
+        }
+        self.received.borrow_mut().push(body.to_vec());
+        Ok(Vec::new())
+    }
+
+    fn get(&self, _url: &str) -> Result<Vec<u8>, TransportError> {
+        // records are only ever posted
+        Err(TransportError::Status(405))
+    }
+}
+
+fn spool_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("outbox-{}-{}", std::process::id(), name));
+    remove(&path);
+    path
+}
+
+fn remove(path: &Path) {
+    let mut cursor = path.as_os_str().to_owned();
+    cursor.push(".sent");
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(cursor);
+}
+
+fn records(count: u64) -> Vec<SignedPosition> {
+    (0..count)
+        .map(|index| {
+            let fix = position(48.8566, 2.3522, 1_728_894_660 + index);
+            // the device key changes half way
+            if index < count / 2 {
+                sign_position(fix, &secret_key(1))
+            } else {
+                sign_position(fix, &p256_key(2))
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn outage_loses_nothing() {
+    let path = spool_path("outage");
+    let endpoint = MockEndpoint::default();
+    let mut spool = Spool::open(&path, None, Overflow::RejectNewest).unwrap();
+    for index in 0..5 {
+        spool.push(&format!("{{\"record\":{}}}", index)).unwrap();
+    }
+
+    endpoint.go_down_after(2, None);
+    let Err(OutboxError::Delivery { sent, err }) = outbox::deliver(&path, &endpoint, "") else {
+        panic!("delivered during the outage");
+    };
+    assert_eq!(sent, 2);
+    assert!(!err.is_permanent());
+    // records keep being spooled while the endpoint is down, and across a restart of the spool
+    spool.push("{\"record\":5}").unwrap();
+    drop(spool);
+    let mut spool = Spool::open(&path, None, Overflow::RejectNewest).unwrap();
+    spool.push("{\"record\":6}").unwrap();
+    assert!(outbox::deliver(&path, &endpoint, "").is_err());
+    assert_eq!(outbox::pending(&path).unwrap().len(), 5);
+
+    endpoint.come_back();
+    assert_eq!(outbox::deliver(&path, &endpoint, "").unwrap(), 5);
+    assert_eq!(outbox::deliver(&path, &endpoint, "").unwrap(), 0);
+    let received: Vec<String> = endpoint
+        .received
+        .borrow()
+        .iter()
+        .map(|body| String::from_utf8(body.clone()).unwrap())
+        .collect();
+    let expected: Vec<String> = (0..7)
+        .map(|index| format!("{{\"record\":{}}}", index))
+        .collect();
+    assert_eq!(received, expected);
+    remove(&path);
+}
+
+#[test]
+fn refused_record_is_permanent() {
+    let path = spool_path("refused");
+    let endpoint = MockEndpoint::default();
+    let mut spool = Spool::open(&path, None, Overflow::RejectNewest).unwrap();
+    spool.push("{}").unwrap();
+    for (status, permanent) in [
+        (400, true),
+        (422, true),
+        (408, false),
+        (429, false),
+        (503, false),
+    ] {
+        endpoint.go_down_after(0, Some(status));
+        let Err(OutboxError::Delivery { err, .. }) = outbox::deliver(&path, &endpoint, "") else {
+            panic!("delivered a refused record");
+        };
+        assert_eq!(err.is_permanent(), permanent, "{}", status);
+    }
+    // the record stays spooled
+    assert_eq!(outbox::pending(&path).unwrap().len(), 1);
+    remove(&path);
+}
+
+#[test]
+fn sync_is_idempotent() {
+    let path = spool_path("sync");
+    let endpoint = MockEndpoint::default();
+    let records = records(6);
+    let mut spool = Spool::open(&path, None, Overflow::RejectNewest).unwrap();
+    for record in &records {
+        spool.push(&serde_json::to_string(record).unwrap()).unwrap();
+    }
+
+    // containers of 2 records, the outage cutting the second one
+    endpoint.go_down_after(1, None);
+    let Err(OutboxError::Delivery { sent, .. }) = outbox::sync(&path, &endpoint, "", 2) else {
+        panic!("synced during the outage");
+    };
+    assert_eq!(sent, 2);
+    endpoint.come_back();
+    assert_eq!(outbox::sync(&path, &endpoint, "", 2).unwrap(), 4);
+    // nothing left, running it again sends nothing
+    assert_eq!(outbox::sync(&path, &endpoint, "", 2).unwrap(), 0);
+
+    let containers: Vec<track::Track> = endpoint
+        .received
+        .borrow()
+        .iter()
+        .map(|container| track::unpack(container).unwrap())
+        .collect();
+    // the key changing after the third record ends a container early
+    let lengths: Vec<usize> = containers.iter().map(|track| track.records.len()).collect();
+    assert_eq!(lengths, [2, 1, 2, 1]);
+    let synced: Vec<SignedPosition> = containers
+        .into_iter()
+        .flat_map(|track| track.records)
+        .collect();
+    assert_eq!(synced, records);
+    remove(&path);
+}
+
+/// Sinks shared by the threads of a broadcast, the ones named `down` refusing connections
+#[derive(Default)]
+struct MockSinks {
+    received: Mutex<Vec<String>>,
+}
+
+impl Transport for MockSinks {
+    fn post(
+        &self,
+        url: &str,
+        _content_type: &str,
+        _body: &[u8],
+    ) -> Result<Vec<u8>, TransportError> {
+        if url.contains("down") {
+            return Err(TransportError::Io(
+                std::io::ErrorKind::ConnectionRefused.into(),
+            ));
+        }
+        self.received.lock().unwrap().push(url.to_string());
+        Ok(Vec::new())
+    }
+
+    fn get(&self, _url: &str) -> Result<Vec<u8>, TransportError> {
+        // records are only ever posted
+        Err(TransportError::Status(405))
+    }
+}
+
+#[test]
+fn broadcast_reports_failed_sinks() {
+    let sinks = MockSinks::default();
+    let urls = ["http://a/", "http://down-1/", "http://b/", "http://down-2/"];
+    let failed = outbox::broadcast("{}", &sinks, &urls);
+    let failed: Vec<&str> = failed.iter().map(|(sink, _)| *sink).collect();
+    assert_eq!(failed, ["http://down-1/", "http://down-2/"]);
+    let mut received = sinks.received.into_inner().unwrap();
+    received.sort();
+    assert_eq!(received, ["http://a/", "http://b/"]);
+    assert!(outbox::broadcast("{}", &MockSinks::default(), &[]).is_empty());
+}
@@ -0,0 +1,148 @@
+//! Signatures over payloads taken at a position
+
+mod common;
+
+use common::{position, secret_key};
+use sha2::{Digest, Sha256};
+use sign_data_rust::payload::{
+    payload_digest, sign_payload_at, try_sign_payload_at, verify_payload, SignedPayload,
+};
+use sign_data_rust::{
+    hash_position, sign_position, try_sign_position, verify_signed_position, wire, SignedPosition,
+    VerifyError,
+};
+
+/// Record signed before payloads had a version, of `b"21.5 C"` read in Berlin
+const LEGACY_RECORD: &str = concat!(
+    r#"{"position":{"latitude":52.52,"longitude":13.405,"timestamp":1728894600},"#,
+    r#""payload_sha256":"5887cf3fc7a4c429c2040c23d3ce2236231f3fde8713b5fa79d01cfce0f03e27","#,
+    r#""signature":"6d6c2328ee326faebd22a654b696993db1bbd2d1ed7e5850c1d2e79954191c61"#,
+    r#"7df1dfbfe13ff0ad59b3786f137f101df42cc9f333e6fc1591f1578102e6d181","#,
+    r#""public_key":"031b84c5567b126440995d3ed5aaba0565d71e1834604819ff9c17f5e9d5dd078f","#,
+    r#""scheme":"secp256k1"}"#
+);
+
+#[test]
+fn payload_round_trip() {
+    let record = sign_payload_at(
+        position(52.52, 13.405, 1_728_894_600),
+        b"21.5 C",
+        &secret_key(1),
+    );
+    verify_payload(&record, b"21.5 C").unwrap();
+    assert_eq!(
+        verify_payload(&record, b"21.6 C"),
+        Err(VerifyError::PayloadMismatch)
+    );
+}
+
+#[test]
+fn digest_is_length_prefixed() {
+    let position = position(52.52, 13.405, 1_728_894_600);
+    let payload_sha256 = Sha256::digest(b"21.5 C");
+    let json = serde_json::to_vec(&position).unwrap();
+    let wire = wire::encode(&position).unwrap();
+
+    for (version, tag, serialized) in [
+        (1, &b"sign_GPS_coords/payload"[..], json),
+        (2, &b"sign_GPS_coords/payload/v2"[..], wire),
+    ] {
+        let mut expected = Vec::new();
+        for field in [tag, &serialized, &payload_sha256] {
+            expected.extend((field.len() as u32).to_be_bytes());
+            expected.extend(field);
+        }
+        assert_eq!(
+            payload_digest(&position, &payload_sha256, version).unwrap(),
+            <[u8; 32]>::from(Sha256::digest(&expected))
+        );
+
+        // bytes moved from the end of one field to the start of the next hash differently
+        assert_ne!(
+            payload_digest(&position, &payload_sha256[..31], version).unwrap(),
+            payload_digest(&position, &payload_sha256, version).unwrap()
+        );
+    }
+    assert_eq!(
+        payload_digest(&position, &payload_sha256, 3),
+        Err(VerifyError::UnsupportedVersion(3))
+    );
+}
+
+#[test]
+fn both_versions_verify() {
+    let legacy: SignedPayload = serde_json::from_str(LEGACY_RECORD).unwrap();
+    assert_eq!(legacy.version, 1);
+    verify_payload(&legacy, b"21.5 C").unwrap();
+
+    let position = position(52.52, 13.405, 1_728_894_600);
+    let record = sign_payload_at(position.clone(), b"21.5 C", &secret_key(1));
+    assert_eq!(record.version, 2);
+    verify_payload(&record, b"21.5 C").unwrap();
+    let json = serde_json::to_string(&record).unwrap();
+    assert!(json.starts_with(r#"{"version":2,"#), "{}", json);
+    assert_eq!(
+        serde_json::from_str::<SignedPayload>(&json).unwrap(),
+        record
+    );
+
+    // a version 1 signature read under version 2, and the other way round
+    let version_1 = try_sign_payload_at(position, b"21.5 C", 1, &secret_key(1)).unwrap();
+    assert_eq!(version_1, legacy);
+    for (mut forged, version) in [(version_1, 2), (record, 1)] {
+        forged.version = version;
+        assert!(matches!(
+            verify_payload(&forged, b"21.5 C"),
+            Err(VerifyError::InvalidSignature { .. })
+        ));
+    }
+
+    // digits a JSON serializer could format differently are not signed from version 2 on
+    let mut reformatted = sign_payload_at(
+        common::position(52.52, 13.405, 1_728_894_600),
+        b"21.5 C",
+        &secret_key(1),
+    );
+    reformatted.position.latitude = 52.520_000_000_000_01;
+    verify_payload(&reformatted, b"21.5 C").unwrap();
+    assert!(try_sign_payload_at(common::position(91.0, 0.0, 0), b"", 2, &secret_key(1)).is_err());
+}
+
+#[test]
+fn domain_separated_from_positions() {
+    let position = position(52.52, 13.405, 1_728_894_600);
+    let payload = sign_payload_at(position.clone(), b"", &secret_key(1));
+    let record = sign_position(position.clone(), &secret_key(1));
+    assert_ne!(
+        payload_digest(&position, &Sha256::digest(b""), 1)
+            .unwrap()
+            .as_slice(),
+        &*hash_position(&position)
+    );
+    assert_ne!(
+        payload_digest(&position, &Sha256::digest(b""), 2)
+            .unwrap()
+            .as_slice(),
+        &*record.digest().unwrap()
+    );
+
+    // the payload signature presented as a position signature
+    let forged = SignedPosition {
+        version: 1,
+        signature: payload.signature.clone(),
+        ..record.clone()
+    };
+    assert!(matches!(
+        verify_signed_position(&forged),
+        Err(VerifyError::InvalidSignature { .. })
+    ));
+
+    // the position signature presented as a payload signature
+    let mut forged = payload;
+    let version_1 = try_sign_position(position, 1, &secret_key(1)).unwrap();
+    forged.signature = version_1.signature;
+    assert!(matches!(
+        verify_payload(&forged, b""),
+        Err(VerifyError::InvalidSignature { .. })
+    ));
+}
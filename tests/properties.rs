@@ -0,0 +1,156 @@
+//! Properties of signing, verification and serialization over generated positions and keys
+
+use proptest::prelude::*;
+
+use sign_data_rust::multiformats::MultiformatRecord;
+use sign_data_rust::scheme::Signer;
+use sign_data_rust::{
+    track, try_sign_position, verify_signed_position, wire, Position, SignedPosition,
+};
+
+fn position() -> impl Strategy<Value = Position> {
+    (
+        -90.0..=90.0f64,
+        -180.0..=180.0f64,
+        0..4_102_444_800u64,
+        proptest::option::of(-1e6..1e6f64),
+        proptest::option::of(any::<[u8; 32]>()),
+        proptest::option::of(0..4_102_444_800u64),
+    )
+        .prop_map(
+            |(latitude, longitude, timestamp, altitude, prev_hash, expires_at)| Position {
+                latitude,
+                longitude,
+                timestamp,
+                altitude,
+                prev_hash: prev_hash.map(hex::encode),
+                expires_at,
+                dwell_count: None,
+                last_seen: None,
+            },
+        )
+}
+
+/// secp256k1 or P-256 secret key, kept as bytes so that failures print it
+#[derive(Debug, Clone)]
+struct Key {
+    p256: bool,
+    secret: [u8; 32],
+}
+
+impl Key {
+    fn signer(&self) -> Box<dyn Signer> {
+        match self.p256 {
+            true => Box::new(p256::ecdsa::SigningKey::from_slice(&self.secret).unwrap()),
+            false => Box::new(secp256k1::SecretKey::from_slice(&self.secret).unwrap()),
+        }
+    }
+}
+
+fn key() -> impl Strategy<Value = Key> {
+    (any::<bool>(), any::<[u8; 32]>())
+        .prop_filter("invalid secret key", |(p256, secret)| match p256 {
+            true => p256::ecdsa::SigningKey::from_slice(secret).is_ok(),
+            false => secp256k1::SecretKey::from_slice(secret).is_ok(),
+        })
+        .prop_map(|(p256, secret)| Key { p256, secret })
+}
+
+fn signed_position() -> impl Strategy<Value = SignedPosition> {
+    (position(), prop_oneof![Just(1u8), Just(2u8)], key()).prop_map(|(position, version, key)| {
+        try_sign_position(position, version, key.signer().as_ref()).expect("position in range")
+    })
+}
+
+/// Move a position by at least the resolution of every encoding
+fn shift(value: f64, bound: f64) -> f64 {
+    match value + 1e-6 <= bound {
+        true => value + 1e-6,
+        false => value - 1e-6,
+    }
+}
+
+proptest! {
+    #[test]
+    fn signed_positions_verify(record in signed_position()) {
+        prop_assert_eq!(verify_signed_position(&record), Ok(()));
+    }
+
+    #[test]
+    fn json_round_trip_verifies(record in signed_position()) {
+        let json = serde_json::to_string(&record).unwrap();
+        let parsed: SignedPosition = serde_json::from_str(&json).unwrap();
+        prop_assert_eq!(parsed.digest().unwrap(), record.digest().unwrap());
+        prop_assert_eq!(verify_signed_position(&parsed), Ok(()));
+        prop_assert_eq!(parsed, record);
+    }
+
+    #[test]
+    fn multiformats_round_trip_verifies(record in signed_position()) {
+        let multiformat = MultiformatRecord::from_signed_position(&record).unwrap();
+        let json = serde_json::to_string(&multiformat).unwrap();
+        let parsed: MultiformatRecord = serde_json::from_str(&json).unwrap();
+        let parsed = parsed.to_signed_position().unwrap();
+        prop_assert_eq!(parsed.digest().unwrap(), record.digest().unwrap());
+        prop_assert_eq!(verify_signed_position(&parsed), Ok(()));
+    }
+
+    #[test]
+    fn binary_round_trips_verify(position in position(), key in key()) {
+        let record = try_sign_position(position, 2, key.signer().as_ref()).unwrap();
+        let decoded = wire::decode(&wire::encode(&record.position).unwrap()).unwrap();
+        prop_assert_eq!(wire::digest(&decoded).unwrap(), wire::digest(&record.position).unwrap());
+
+        let track = track::unpack(&track::pack(std::slice::from_ref(&record), None, &[]).unwrap()).unwrap();
+        prop_assert_eq!(track.records.len(), 1);
+        prop_assert_eq!(track.records[0].digest().unwrap(), record.digest().unwrap());
+        prop_assert_eq!(verify_signed_position(&track.records[0]), Ok(()));
+    }
+
+    #[test]
+    fn mutations_fail_verification(record in signed_position(), field in 0..6usize) {
+        let mut mutated = record.clone();
+        let position = &mut mutated.position;
+        match field {
+            0 => position.latitude = shift(position.latitude, 90.0),
+            1 => position.longitude = shift(position.longitude, 180.0),
+            2 => position.timestamp ^= 1,
+            3 => position.altitude = match position.altitude {
+                Some(_) => None,
+                None => Some(1.0),
+            },
+            4 => position.expires_at = Some(position.expires_at.map_or(0, |expiry| expiry ^ 1)),
+            _ => {
+                let mut signature = hex::decode(&mutated.signature).unwrap();
+                signature[10] ^= 1;
+                mutated.signature = hex::encode(signature);
+            }
+        }
+        prop_assert!(verify_signed_position(&mutated).is_err());
+    }
+
+    #[test]
+    fn try_sign_position_never_panics(
+        latitude in any::<f64>(),
+        longitude in any::<f64>(),
+        timestamp in any::<u64>(),
+        altitude in proptest::option::of(any::<f64>()),
+        prev_hash in proptest::option::of(".{0,70}"),
+        version in any::<u8>(),
+        key in key(),
+    ) {
+        let position = Position {
+            latitude,
+            longitude,
+            timestamp,
+            altitude,
+            prev_hash,
+            expires_at: None,
+            dwell_count: None,
+            last_seen: None,
+        };
+        if let Ok(record) = try_sign_position(position, version, key.signer().as_ref()) {
+            prop_assert_eq!(verify_signed_position(&record), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Revocation lists of compromised device keys
+
+mod common;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::cosign::co_sign;
+use sign_data_rust::revocation::{Revocation, RevocationError, RevocationList};
+use sign_data_rust::scheme::{Scheme, Signer, VerifyingKey};
+use sign_data_rust::{sign_position, VerifyError};
+
+const REVOKED_AT: u64 = 1_728_894_660;
+
+fn authority_key() -> VerifyingKey {
+    VerifyingKey::parse(Scheme::P256, &Signer::public_key(&p256_key(9))).unwrap()
+}
+
+fn list() -> RevocationList {
+    let revocations = vec![Revocation {
+        public_key: Signer::public_key(&secret_key(1)),
+        scheme: Scheme::Secp256k1,
+        revoked_at: REVOKED_AT,
+    }];
+    RevocationList::issue(REVOKED_AT + 60, revocations, &p256_key(9)).unwrap()
+}
+
+#[test]
+fn records_before_revocation_stay_valid() {
+    let list = list();
+    list.verify(&authority_key()).unwrap();
+    let record = sign_position(position(48.8566, 2.3522, REVOKED_AT - 1), &secret_key(1));
+    assert_eq!(list.check(&record), Ok(()));
+    // other keys are not affected
+    let record = sign_position(position(48.8566, 2.3522, REVOKED_AT + 1), &secret_key(2));
+    assert_eq!(list.check(&record), Ok(()));
+}
+
+#[test]
+fn records_after_revocation_are_rejected() {
+    let list = list();
+    for timestamp in [REVOKED_AT, REVOKED_AT + 1] {
+        let record = sign_position(position(48.8566, 2.3522, timestamp), &secret_key(1));
+        assert_eq!(
+            list.check(&record),
+            Err(VerifyError::Revoked {
+                public_key: Signer::public_key(&secret_key(1)),
+                revoked_at: REVOKED_AT,
+            })
+        );
+    }
+
+    // a revoked key co-signing is rejected as well
+    let mut record = sign_position(position(48.8566, 2.3522, REVOKED_AT), &secret_key(2));
+    co_sign(&mut record, &secret_key(1)).unwrap();
+    assert!(matches!(
+        list.check(&record),
+        Err(VerifyError::Revoked { .. })
+    ));
+}
+
+#[test]
+fn bad_authority_signature_is_refused() {
+    // an entry lifted after issuance
+    let mut list = list();
+    list.revocations[0].revoked_at += 3600;
+    assert_eq!(
+        list.verify(&authority_key()),
+        Err(RevocationError::BadSignature)
+    );
+
+    // an entry dropped
+    let mut list = self::list();
+    list.revocations.clear();
+    assert_eq!(
+        list.verify(&authority_key()),
+        Err(RevocationError::BadSignature)
+    );
+
+    // a list signed by another key claiming to be the authority
+    let mut list = self::list();
+    let forged =
+        RevocationList::issue(list.issued_at, list.revocations.clone(), &p256_key(8)).unwrap();
+    list.signature = forged.signature.clone();
+    assert_eq!(
+        list.verify(&authority_key()),
+        Err(RevocationError::BadSignature)
+    );
+    assert_eq!(
+        forged.verify(&authority_key()),
+        Err(RevocationError::UntrustedIssuer)
+    );
+}
@@ -0,0 +1,57 @@
+//! Signing and verification under each scheme
+
+mod common;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::scheme::{Scheme, Signer, VerifyingKey};
+use sign_data_rust::{sign_position, verify_signed_position, VerifyError};
+
+#[test]
+fn p256_round_trip() {
+    let key = p256_key(7);
+    let record = sign_position(position(35.6762, 139.6503, 1_728_894_600), &key);
+    assert_eq!(record.scheme, Scheme::P256);
+    assert_eq!(record.public_key, Signer::public_key(&key));
+    verify_signed_position(&record).unwrap();
+
+    let json = serde_json::to_string(&record).unwrap();
+    assert!(json.contains(r#""scheme":"p256""#));
+    verify_signed_position(&serde_json::from_str(&json).unwrap()).unwrap();
+}
+
+#[test]
+fn secp256k1_is_the_default_scheme() {
+    let record = sign_position(position(35.6762, 139.6503, 1_728_894_600), &secret_key(7));
+    let mut json = serde_json::to_value(&record).unwrap();
+    json.as_object_mut().unwrap().remove("scheme");
+    let record = serde_json::from_value(json).unwrap();
+    verify_signed_position(&record).unwrap();
+}
+
+#[test]
+fn cross_scheme_verification_fails() {
+    let sign = |signer: &dyn Signer, scheme| {
+        let mut record = sign_position(position(35.6762, 139.6503, 1_728_894_600), signer);
+        record.scheme = scheme;
+        verify_signed_position(&record)
+    };
+    for result in [
+        sign(&p256_key(7), Scheme::Secp256k1),
+        sign(&secret_key(7), Scheme::P256),
+    ] {
+        assert!(
+            matches!(
+                result,
+                Err(VerifyError::MalformedPublicKey(_) | VerifyError::InvalidSignature { .. })
+            ),
+            "{:?}",
+            result
+        );
+    }
+
+    // a P-256 signature checked with a secp256k1 key
+    let digest = [5; 32];
+    let signature = p256_key(7).sign_digest(&digest);
+    let key = VerifyingKey::parse(Scheme::Secp256k1, &Signer::public_key(&secret_key(7))).unwrap();
+    assert_eq!(key.verify(&digest, &signature), Ok(false));
+}
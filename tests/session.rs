@@ -0,0 +1,131 @@
+//! Ephemeral session keys endorsed by the long-term device key
+
+mod common;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::cosign::{co_sign, verify_threshold, CoSignError};
+use sign_data_rust::scheme::{Scheme, Signer, VerifyingKey};
+use sign_data_rust::session::{self, Session, SessionError};
+use sign_data_rust::{
+    sign_position, verify_signed_position, CoSignature, SignedPosition, VerifyError,
+};
+
+const NOT_BEFORE: u64 = 1_728_894_600;
+const NOT_AFTER: u64 = 1_728_898_200;
+
+fn device_key() -> VerifyingKey {
+    VerifyingKey::parse(Scheme::Secp256k1, &Signer::public_key(&secret_key(1))).unwrap()
+}
+
+fn session() -> Session {
+    Session::start(&secret_key(1), "tracker-7", NOT_BEFORE, NOT_AFTER).unwrap()
+}
+
+fn record(session: &Session, timestamp: u64) -> SignedPosition {
+    let mut record = sign_position(position(48.8566, 2.3522, timestamp), session);
+    record.endorsement = Some(session.endorsement().clone());
+    record
+}
+
+#[test]
+fn endorsed_record_verifies() {
+    let session = session();
+    for timestamp in [NOT_BEFORE, NOT_BEFORE + 60, NOT_AFTER] {
+        let record = record(&session, timestamp);
+        assert_eq!(verify_signed_position(&record), Ok(()));
+        assert_eq!(session::device_key(&record), Ok(device_key()));
+    }
+    assert!(matches!(
+        Session::start(&secret_key(1), "tracker-7", NOT_AFTER, NOT_BEFORE),
+        Err(SessionError::EmptyWindow)
+    ));
+}
+
+#[test]
+fn forged_endorsement_is_rejected() {
+    let session = session();
+    let invalid = Err(VerifyError::InvalidEndorsement {
+        device_key: Signer::public_key(&secret_key(1)),
+    });
+
+    // an endorsement made by another key naming the device key
+    let mut record = record(&session, NOT_BEFORE + 60);
+    let forged = Session::start(&secret_key(2), "tracker-7", NOT_BEFORE, NOT_AFTER).unwrap();
+    let mut endorsement = session.endorsement().clone();
+    endorsement.signature = forged.endorsement().signature.clone();
+    record.endorsement = Some(endorsement);
+    assert_eq!(verify_signed_position(&record), invalid);
+
+    // a window or identity edited after endorsement
+    for edit in [
+        |endorsement: &mut session::Endorsement| endorsement.not_after += 3600,
+        |endorsement: &mut session::Endorsement| endorsement.not_before -= 3600,
+        |endorsement: &mut session::Endorsement| endorsement.device_id.push('8'),
+    ] {
+        let mut record = self::record(&session, NOT_BEFORE + 60);
+        edit(record.endorsement.as_mut().unwrap());
+        assert_eq!(verify_signed_position(&record), invalid);
+    }
+
+    // the endorsement of another session key
+    let mut record = self::record(&forged, NOT_BEFORE + 60);
+    record.endorsement = Some(session.endorsement().clone());
+    assert!(matches!(
+        verify_signed_position(&record),
+        Err(VerifyError::NotEndorsed { .. })
+    ));
+}
+
+#[test]
+fn expired_session_is_rejected() {
+    let session = session();
+    for timestamp in [NOT_BEFORE - 1, NOT_AFTER + 1] {
+        assert_eq!(
+            verify_signed_position(&record(&session, timestamp)),
+            Err(VerifyError::OutsideSession {
+                timestamp,
+                not_before: NOT_BEFORE,
+                not_after: NOT_AFTER,
+            })
+        );
+    }
+}
+
+#[test]
+fn device_key_cannot_co_sign_its_own_session() {
+    let session = session();
+    let trusted = [
+        device_key(),
+        VerifyingKey::parse(Scheme::P256, &Signer::public_key(&p256_key(2))).unwrap(),
+    ];
+    let mut record = record(&session, NOT_BEFORE + 60);
+    assert!(matches!(
+        co_sign(&mut record, &secret_key(1)),
+        Err(CoSignError::AlreadySigned(_))
+    ));
+    assert_eq!(verify_threshold(&record, &trusted, 1), Ok(1));
+    assert!(matches!(
+        verify_threshold(&record, &trusted, 2),
+        Err(VerifyError::ThresholdNotMet {
+            valid: 1,
+            required: 2
+        })
+    ));
+
+    // a co-signature added by hand does not count the device twice
+    let digest = record.digest().unwrap();
+    record.co_signatures.push(CoSignature {
+        public_key: Signer::public_key(&secret_key(1)),
+        signature: secret_key(1).sign_digest(&digest),
+        scheme: Scheme::Secp256k1,
+    });
+    assert!(matches!(
+        verify_threshold(&record, &trusted, 2),
+        Err(VerifyError::DuplicateKey(_))
+    ));
+
+    // another trusted key does count
+    record.co_signatures.clear();
+    co_sign(&mut record, &p256_key(2)).unwrap();
+    assert_eq!(verify_threshold(&record, &trusted, 2), Ok(2));
+}
@@ -0,0 +1,190 @@
+//! Sources of fixes and the signing pipeline
+
+mod common;
+
+use std::io::{self, Read};
+
+use common::{position, secret_key};
+use sign_data_rust::chain::{self, GENESIS};
+use sign_data_rust::clock::MockClock;
+use sign_data_rust::gpx::GpxPoint;
+use sign_data_rust::source::{
+    sign_stream, JsonLinesSource, MemorySource, PositionSource, RoutePlayback, SourceError, Speed,
+};
+use sign_data_rust::{sign_position, verify_signed_position, Position};
+
+fn ride() -> Vec<Position> {
+    (0..4)
+        .map(|index| position(48.8566 + index as f64 * 1e-4, 2.3522, 1_728_894_600 + index))
+        .collect()
+}
+
+#[test]
+fn memory_source_drives_the_pipeline() {
+    let mut signed = Vec::new();
+    let count = sign_stream(
+        &mut MemorySource::from(ride()),
+        &secret_key(1),
+        None,
+        |record, head| {
+            assert!(head.is_none());
+            signed.push(record.clone());
+            Ok(())
+        },
+    )
+    .unwrap();
+    assert_eq!(count, 4);
+    // deterministic signatures, the same records as signing one by one
+    let expected: Vec<_> = ride()
+        .into_iter()
+        .map(|position| sign_position(position, &secret_key(1)))
+        .collect();
+    assert_eq!(signed, expected);
+}
+
+#[test]
+fn chained_pipeline() {
+    let mut head = GENESIS;
+    let mut signed = Vec::new();
+    sign_stream(
+        &mut MemorySource::from(ride()),
+        &secret_key(1),
+        Some(&mut head),
+        |record, head| {
+            assert_eq!(head, chain::link_hash(record).as_ref());
+            signed.push(record.clone());
+            Ok(())
+        },
+    )
+    .unwrap();
+    chain::verify_chain(&signed, Some(&GENESIS)).unwrap();
+    assert_eq!(chain::link_hash(&signed[3]), Some(head));
+}
+
+#[test]
+fn pipeline_stops_on_unsignable_fix() {
+    let mut fixes = ride();
+    fixes[2].latitude = 91.0;
+    let mut emitted = 0;
+    let result = sign_stream(
+        &mut MemorySource::from(fixes),
+        &secret_key(1),
+        None,
+        |record, _| {
+            verify_signed_position(record).unwrap();
+            emitted += 1;
+            Ok(())
+        },
+    );
+    assert!(matches!(result, Err(SourceError::Fix { index: 2, .. })));
+    assert_eq!(emitted, 2);
+
+    let result = sign_stream(
+        &mut MemorySource::from(ride()),
+        &secret_key(1),
+        None,
+        |_, _| Err(io::Error::other("disk full")),
+    );
+    assert!(matches!(result, Err(SourceError::Io(_))));
+}
+
+#[test]
+fn json_lines() {
+    let input = "{\"latitude\":1.5,\"longitude\":2.5,\"timestamp\":100}\n\nnot json\n{\"timestamp\":3}\n{\"latitude\":1.5,\"longitude\":2.5,\"timestamp\":101}\n";
+    let mut source = JsonLinesSource::new(input.as_bytes());
+    assert_eq!(source.next_fix(), Some(position(1.5, 2.5, 100)));
+    assert_eq!(source.next_fix(), Some(position(1.5, 2.5, 101)));
+    assert_eq!(source.next_fix(), None);
+    assert_eq!(source.skipped(), 2);
+    assert!(source.take_error().is_none());
+}
+
+#[test]
+fn read_errors_kept() {
+    let input = b"{\"latitude\":1.5,\"longitude\":2.5,\"timestamp\":100}\n\xff\xfe\n{\"latitude\":1.5,\"longitude\":2.5,\"timestamp\":101}\n";
+    let mut source = JsonLinesSource::new(&input[..]);
+    assert!(source.next_fix().is_some());
+    assert_eq!(source.next_fix(), None);
+    assert_eq!(
+        source.take_error().unwrap().kind(),
+        io::ErrorKind::InvalidData
+    );
+
+    // a reader failing after its first line
+    let line = &b"{\"latitude\":1.5,\"longitude\":2.5,\"timestamp\":100}\n"[..];
+    let failing = io::BufReader::new(line.chain(Failing));
+    let mut source = JsonLinesSource::new(failing);
+    assert!(source.next_fix().is_some());
+    assert_eq!(source.next_fix(), None);
+    assert_eq!(source.take_error().unwrap().to_string(), "connection reset");
+}
+
+struct Failing;
+
+impl Read for Failing {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::ConnectionReset,
+            "connection reset",
+        ))
+    }
+}
+
+#[test]
+fn gpsd_reports() {
+    let input = r#"{"class":"VERSION","release":"3.25","proto_major":3}
+{"class":"DEVICES","devices":[{"path":"/dev/ttyACM0"}]}
+{"class":"TPV","device":"/dev/ttyACM0","mode":1}
+{"class":"TPV","mode":3,"lat":48.8566,"lon":2.3522,"altHAE":80.5,"altMSL":35.2,"time":"2024-10-14T08:30:00.000Z"}
+{"class":"SKY","satellites":[]}
+{"class":"TPV","mode":2,"lat":-33.8688,"lon":151.2093,"time":"2024-10-14T08:30:01.000Z"}
+{"class":"TPV","mode":3,"lat":48.8566,"lon":2.3522}
+"#;
+    let mut source = JsonLinesSource::gpsd(input.as_bytes());
+    let mut expected = position(48.8566, 2.3522, 1_728_894_600);
+    expected.altitude = Some(80.5);
+    assert_eq!(source.next_fix(), Some(expected));
+    assert_eq!(
+        source.next_fix(),
+        Some(position(-33.8688, 151.2093, 1_728_894_601))
+    );
+    assert_eq!(source.next_fix(), None);
+    // the reports without a fix or a time, other classes are not counted
+    assert_eq!(source.skipped(), 2);
+}
+
+#[test]
+fn route_playback_interpolates() {
+    let point = |time| GpxPoint {
+        latitude: 1.0,
+        longitude: 1.0,
+        altitude: None,
+        time,
+    };
+    let clock = MockClock::new(5_000_000);
+    let points = [
+        point(None),
+        point(Some(100)),
+        point(None),
+        point(None),
+        point(Some(106)),
+        point(None),
+    ];
+    let mut playback = RoutePlayback::new(&points, Speed::AsFastAsPossible, &clock).unwrap();
+    let times: Vec<u64> = std::iter::from_fn(|| playback.next_fix())
+        .map(|fix| fix.timestamp)
+        .collect();
+    assert_eq!(times, [99, 100, 102, 104, 106, 107]);
+
+    // without any time, one second apart from the clock
+    let mut playback =
+        RoutePlayback::new(&[point(None), point(None)], Speed::AsFastAsPossible, &clock).unwrap();
+    assert_eq!(playback.next_fix().unwrap().timestamp, 5_000);
+    assert_eq!(playback.next_fix().unwrap().timestamp, 5_001);
+    assert!(matches!(
+        RoutePlayback::new(&[], Speed::Factor(1.0), &clock),
+        Err(SourceError::EmptyRoute)
+    ));
+    assert_eq!("max".parse::<Speed>(), Ok(Speed::AsFastAsPossible));
+    assert!("0".parse::<Speed>().is_err());
+}
@@ -0,0 +1,119 @@
+//! Synthetic tracks
+
+mod common;
+
+use common::position;
+use sign_data_rust::geo::distance_m;
+use sign_data_rust::synthetic::{generate, Route, SyntheticError, TrackOptions};
+use sign_data_rust::Position;
+
+fn max_speed(track: &[Position]) -> f64 {
+    track
+        .windows(2)
+        .map(|pair| distance_m(&pair[0], &pair[1]) / (pair[1].timestamp - pair[0].timestamp) as f64)
+        .fold(0.0, f64::max)
+}
+
+#[test]
+fn point_count_and_times() {
+    let options = TrackOptions {
+        count: 500,
+        interval: 5,
+        dropout: 0.2,
+        seed: 7,
+        start_time: 1_728_894_600,
+        ..TrackOptions::default()
+    };
+    let track = generate(&options).unwrap();
+    assert_eq!(track.len(), 500);
+    assert_eq!(track[0].timestamp, 1_728_894_600);
+    assert!(track
+        .windows(2)
+        .all(|pair| pair[1].timestamp > pair[0].timestamp
+            && (pair[1].timestamp - pair[0].timestamp) % 5 == 0));
+    // some fixes were dropped, leaving gaps
+    assert!(track.last().unwrap().timestamp > 1_728_894_600 + 499 * 5);
+
+    assert_eq!(generate(&options).unwrap(), track);
+    let other = generate(&TrackOptions { seed: 8, ..options }).unwrap();
+    assert_ne!(other, track);
+}
+
+#[test]
+fn great_circle_endpoints() {
+    let options = TrackOptions {
+        count: 101,
+        route: Route::GreatCircle {
+            from: (48.8566, 2.3522),
+            to: (51.5074, -0.1278),
+        },
+        interval: 10,
+        ..TrackOptions::default()
+    };
+    let track = generate(&options).unwrap();
+    assert_eq!(track.len(), 101);
+    let (first, last) = (&track[0], &track[100]);
+    assert!((first.latitude - 48.8566).abs() < 1e-9 && (first.longitude - 2.3522).abs() < 1e-9);
+    assert!(distance_m(last, &position(51.5074, -0.1278, 0)) < 1e-3);
+    // a constant speed over the track
+    let speed = distance_m(first, last) / 1000.0;
+    assert!((max_speed(&track) - speed).abs() < 1e-3 * speed);
+}
+
+#[test]
+fn maximum_speed() {
+    for (noise_m, dropout) in [(0.0, 0.0), (0.0, 0.5), (5.0, 0.0)] {
+        let options = TrackOptions {
+            count: 1000,
+            interval: 2,
+            noise_m,
+            dropout,
+            seed: 3,
+            ..TrackOptions::default()
+        };
+        let track = generate(&options).unwrap();
+        // 10 m/s by default, noise moving each end of a leg by up to noise_m
+        let bound = 10.0 + 2.0 * noise_m / 2.0;
+        assert!(max_speed(&track) <= bound + 1e-6, "{}", max_speed(&track));
+        if noise_m == 0.0 && dropout == 0.0 {
+            assert!(max_speed(&track) > 10.0 - 1e-6);
+        }
+    }
+}
+
+#[test]
+fn invalid_options() {
+    let invalid = |options: TrackOptions| generate(&options).unwrap_err();
+    assert_eq!(
+        invalid(TrackOptions {
+            interval: 0,
+            ..TrackOptions::default()
+        }),
+        SyntheticError::Invalid("interval", "at least 1 second")
+    );
+    assert_eq!(
+        invalid(TrackOptions {
+            dropout: 1.0,
+            ..TrackOptions::default()
+        }),
+        SyntheticError::Invalid("dropout", "a probability in [0, 1)")
+    );
+    assert_eq!(
+        invalid(TrackOptions {
+            route: Route::GreatCircle {
+                from: (91.0, 0.0),
+                to: (0.0, 0.0)
+            },
+            ..TrackOptions::default()
+        }),
+        SyntheticError::Invalid("start and end", "valid latitudes and longitudes")
+    );
+    assert!(matches!(
+        invalid(TrackOptions {
+            count: 10,
+            start_time: u64::MAX - 5,
+            ..TrackOptions::default()
+        }),
+        SyntheticError::Invalid("count", _)
+    ));
+}
@@ -0,0 +1,93 @@
+//! Import of Google Takeout location history
+
+use std::fs;
+use std::path::PathBuf;
+
+use sign_data_rust::takeout::{read_takeout, TakeoutError, TakeoutFilter, TakeoutStats};
+use sign_data_rust::Position;
+
+/// `Records.json` cut short in the middle of its last entry
+fn fixture() -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/takeout-truncated.json");
+    fs::read(path).unwrap()
+}
+
+/// The fixture without its partial entry, closed
+fn completed() -> Vec<u8> {
+    let mut history = fixture();
+    let end = history
+        .windows(2)
+        .rposition(|window| window == b"},")
+        .unwrap();
+    history.truncate(end + 1);
+    history.extend_from_slice(b"]}");
+    history
+}
+
+fn import(
+    history: &[u8],
+    filter: &TakeoutFilter,
+) -> (Vec<Position>, Result<TakeoutStats, TakeoutError>) {
+    let mut positions = Vec::new();
+    let result = read_takeout(history, filter, |position| {
+        positions.push(position);
+        Ok(())
+    });
+    (positions, result)
+}
+
+#[test]
+fn truncated_history_keeps_entries_before_the_cut() {
+    let (positions, result) = import(&fixture(), &TakeoutFilter::default());
+    assert!(matches!(result, Err(TakeoutError::Json(_))));
+    let times: Vec<u64> = positions
+        .iter()
+        .map(|position| position.timestamp)
+        .collect();
+    assert_eq!(times, [1_728_894_600, 1_728_894_660, 1_728_894_780]);
+}
+
+#[test]
+fn bad_entries_are_skipped() {
+    let (positions, result) = import(&completed(), &TakeoutFilter::default());
+    // a wrongly typed accuracy and a missing latitude
+    assert_eq!(
+        result.unwrap(),
+        TakeoutStats {
+            kept: 3,
+            filtered: 0,
+            malformed: 2,
+        }
+    );
+    assert_eq!(positions[0].latitude, 48.8566);
+    assert_eq!(positions[0].altitude, Some(35.0));
+    // the unsigned complement of a negative longitude
+    assert_eq!(positions[2].latitude, -33.7);
+    assert_eq!(positions[2].longitude, -3.8905);
+}
+
+#[test]
+fn entries_without_accuracy_are_filtered_out() {
+    let filter = TakeoutFilter {
+        min_accuracy: Some(15),
+        ..TakeoutFilter::default()
+    };
+    let (positions, result) = import(&completed(), &filter);
+    assert_eq!(
+        result.unwrap(),
+        TakeoutStats {
+            kept: 1,
+            filtered: 2,
+            malformed: 2,
+        }
+    );
+    assert_eq!(positions[0].timestamp, 1_728_894_780);
+
+    let filter = TakeoutFilter {
+        since: Some(1_728_894_660),
+        until: Some(1_728_894_660),
+        min_accuracy: None,
+    };
+    let (positions, _) = import(&completed(), &filter);
+    assert_eq!(positions.len(), 1);
+}
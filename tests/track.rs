@@ -0,0 +1,120 @@
+//! Track containers against golden files
+//!
+//! Signatures are deterministic (RFC 6979), so packing the same records gives the same bytes.
+//! Run with `UPDATE_GOLDEN=1` to rewrite the files after a deliberate format change.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{p256_key, position, secret_key};
+use sign_data_rust::cosign::co_sign;
+use sign_data_rust::track::{self, TrackError};
+use sign_data_rust::{sign_position, try_sign_position, SignedPosition};
+
+fn golden(name: &str, packed: &[u8]) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, packed).unwrap();
+    }
+    let golden = fs::read(&path).unwrap();
+    assert!(golden == packed, "{} differs from the packed track", name);
+    golden
+}
+
+fn ride() -> Vec<SignedPosition> {
+    (0..3)
+        .map(|index| {
+            let position = position(
+                48.8566 + index as f64 * 0.001,
+                2.3522,
+                1_728_894_600 + index * 60,
+            );
+            sign_position(position, &secret_key(1))
+        })
+        .collect()
+}
+
+fn co_signed_legacy() -> Vec<SignedPosition> {
+    let mut position = position(-33.8688, 151.2093, 1_728_894_600);
+    position.altitude = Some(58.5);
+    position.expires_at = Some(1_728_898_200);
+    let mut record = try_sign_position(position, 1, &p256_key(2)).unwrap();
+    co_sign(&mut record, &secret_key(3)).unwrap();
+    vec![record]
+}
+
+#[test]
+fn golden_containers() {
+    for (name, records, engine_id, proof) in [
+        ("ride.sgct", ride(), Some("zkengine"), &b"opaque proof"[..]),
+        ("co-signed-legacy.sgct", co_signed_legacy(), None, &[][..]),
+    ] {
+        let packed = track::pack(&records, engine_id, proof).unwrap();
+        let track = track::unpack(&golden(name, &packed)).unwrap();
+        track::verify_track(&track).unwrap();
+        assert_eq!(track.records, records);
+        assert_eq!(track.header.engine_id.as_deref(), engine_id);
+        assert_eq!(track.proof, proof);
+        assert_eq!(
+            track.manifest,
+            records
+                .iter()
+                .map(|record| <[u8; 32]>::try_from(&*record.digest().unwrap()).unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn corrupted_containers() {
+    let data = track::pack(&ride(), Some("zkengine"), b"opaque proof").unwrap();
+    // the header section starts after the magic and format version
+    let header_length = u32::from_be_bytes(data[6..10].try_into().unwrap()) as usize;
+    let positions = 10 + header_length + 32;
+
+    let mut flipped = data.clone();
+    flipped[positions + 10] ^= 1;
+    let mut reordered = data[..5].to_vec();
+    reordered.extend(&data[positions..]);
+    let mut format = data.clone();
+    format[4] = 2;
+    let mut trailing = data.clone();
+    trailing.push(0);
+
+    for (corrupted, expected) in [
+        (&data[..data.len() - 1], "truncated container"),
+        (&flipped[..], "checksum mismatch in section 2"),
+        (&reordered[..], "expected section 1, found section 2"),
+        (&format[..], "unsupported container format 2"),
+        (&trailing[..], "trailing data after the last section"),
+        (&b"GPX!"[..], "not a signed track container"),
+    ] {
+        let err = track::unpack(corrupted).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+#[test]
+fn single_device() {
+    let mut records = ride();
+    records.push(sign_position(
+        position(48.86, 2.3522, 1_728_894_780),
+        &secret_key(2),
+    ));
+    assert!(matches!(
+        track::pack(&records, None, &[]),
+        Err(TrackError::MixedDevices)
+    ));
+
+    // a record altered after packing fails against the manifest
+    let mut track = track::unpack(&track::pack(&ride(), None, &[]).unwrap()).unwrap();
+    track.records[1].position.latitude += 0.001;
+    assert!(matches!(
+        track::verify_track(&track),
+        Err(TrackError::ManifestMismatch(1))
+    ));
+}
@@ -0,0 +1,291 @@
+//! RFC 3161 timestamping against canned TSA answers
+
+mod common;
+
+use common::{position, secret_key};
+use sign_data_rust::transport::{Transport, TransportError};
+use sign_data_rust::tsa::{self, TsaError};
+use sign_data_rust::{sign_position, SignedPosition};
+
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const OID_TST_INFO: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04,
+];
+
+/// 2024-10-14 08:30:00 UTC
+const GEN_TIME: &str = "20241014083000Z";
+const GEN_UNIX: u64 = 1_728_894_600;
+
+const URL: &str = "http://tsa.example/";
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match content.len() {
+        len @ 0..=0x7f => out.push(len as u8),
+        len @ 0x80..=0xff => out.extend_from_slice(&[0x81, len as u8]),
+        len => out.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]),
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn integer(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(7);
+    let mut content = bytes[skip..].to_vec();
+    if content[0] & 0x80 != 0 {
+        content.insert(0, 0);
+    }
+    tlv(0x02, &content)
+}
+
+/// TimeStampToken over `digest`, made at `gen_time`, carrying `nonce`
+fn token(digest: &[u8], gen_time: &str, nonce: Option<u64>) -> Vec<u8> {
+    let algorithm = tlv(0x30, &[tlv(0x06, OID_SHA256), tlv(0x05, &[])].concat());
+    let imprint = tlv(0x30, &[algorithm.clone(), tlv(0x04, digest)].concat());
+    let mut tst_info = [
+        integer(1),
+        tlv(0x06, &[0x2a, 0x03, 0x04]),
+        imprint,
+        integer(7),
+        tlv(0x18, gen_time.as_bytes()),
+    ]
+    .concat();
+    if let Some(nonce) = nonce {
+        tst_info.extend(integer(nonce));
+    }
+    let encap = tlv(
+        0x30,
+        &[
+            tlv(0x06, OID_TST_INFO),
+            tlv(0xa0, &tlv(0x04, &tlv(0x30, &tst_info))),
+        ]
+        .concat(),
+    );
+    let signed_data = tlv(
+        0x30,
+        &[integer(3), tlv(0x31, &algorithm), encap, tlv(0x31, &[])].concat(),
+    );
+    tlv(
+        0x30,
+        &[tlv(0x06, OID_SIGNED_DATA), tlv(0xa0, &signed_data)].concat(),
+    )
+}
+
+/// TimeStampResp of PKIStatus `status`, with a token when granted
+fn response(status: u64, token: Option<&[u8]>) -> Vec<u8> {
+    let status_info = match status {
+        0 | 1 => tlv(0x30, &integer(status)),
+        _ => tlv(
+            0x30,
+            &[
+                integer(status),
+                tlv(0x30, &tlv(0x0c, b"bad message digest")),
+            ]
+            .concat(),
+        ),
+    };
+    tlv(
+        0x30,
+        &[status_info, token.unwrap_or_default().to_vec()].concat(),
+    )
+}
+
+/// Digest and nonce of a TimeStampReq, laid out by `tsa::build_request`
+fn request_fields(request: &[u8]) -> (Vec<u8>, u64) {
+    // header, version, then the imprint ending with the 32 bytes digest
+    let digest = request[24..56].to_vec();
+    assert_eq!(request[56], 0x02);
+    let len = request[57] as usize;
+    let nonce = request[58..58 + len]
+        .iter()
+        .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+    (digest, nonce)
+}
+
+/// TSA building its answer from the request it was sent
+struct CannedTsa(fn(Vec<u8>, u64) -> Vec<u8>);
+
+impl Transport for CannedTsa {
+    fn post(&self, _url: &str, content_type: &str, body: &[u8]) -> Result<Vec<u8>, TransportError> {
+        assert_eq!(content_type, "application/timestamp-query");
+        let (digest, nonce) = request_fields(body);
+        Ok((self.0)(digest, nonce))
+    }
+
+    fn get(&self, _url: &str) -> Result<Vec<u8>, TransportError> {
+        // timestamps are only ever requested by POST
+        Err(TransportError::Status(405))
+    }
+}
+
+/// Message of the DER error of a result, the DER reader being private to the crate
+fn der_error<T: std::fmt::Debug>(result: Result<T, TsaError>) -> String {
+    match result {
+        Err(err @ TsaError::Der(_)) => err.to_string(),
+        other => panic!("{:?}", other),
+    }
+}
+
+fn record() -> SignedPosition {
+    sign_position(position(48.8566, 2.3522, GEN_UNIX - 2), &secret_key(1))
+}
+
+#[test]
+fn granted_token_verifies() {
+    let tsa = CannedTsa(|digest, nonce| response(0, Some(&token(&digest, GEN_TIME, Some(nonce)))));
+    let mut record = record();
+    tsa::timestamp_position(&tsa, URL, &mut record).unwrap();
+    assert_eq!(
+        tsa::verify_position_timestamp(&record, 5).unwrap(),
+        GEN_UNIX
+    );
+
+    // granted with modifications, without a nonce
+    let tsa = CannedTsa(|digest, _| response(1, Some(&token(&digest, GEN_TIME, None))));
+    let digest = record.digest().unwrap();
+    let token = tsa::request_timestamp(&tsa, URL, &digest).unwrap();
+    let info = tsa::verify_token(&token, &digest, GEN_UNIX, 0).unwrap();
+    assert_eq!((info.gen_time, info.nonce), (GEN_UNIX, None));
+}
+
+#[test]
+fn rejected_status_is_reported() {
+    let tsa = CannedTsa(|_, _| response(2, None));
+    let digest = record().digest().unwrap();
+    match tsa::request_timestamp(&tsa, URL, &digest) {
+        Err(TsaError::Rejected { status, text }) => {
+            assert_eq!((status, text.as_deref()), (2, Some("bad message digest")))
+        }
+        other => panic!("{:?}", other),
+    }
+    // granted, but without a token
+    let tsa = CannedTsa(|_, _| response(0, None));
+    assert!(matches!(
+        tsa::request_timestamp(&tsa, URL, &digest),
+        Err(TsaError::MissingToken)
+    ));
+}
+
+#[test]
+fn imprint_mismatch_is_rejected() {
+    let tsa = CannedTsa(|_, nonce| response(0, Some(&token(&[7; 32], GEN_TIME, Some(nonce)))));
+    let digest = record().digest().unwrap();
+    assert!(matches!(
+        tsa::request_timestamp(&tsa, URL, &digest),
+        Err(TsaError::ImprintMismatch)
+    ));
+
+    // a token of another record
+    let token = token(&[7; 32], GEN_TIME, None);
+    assert!(matches!(
+        tsa::verify_token(&token, &digest, GEN_UNIX, 5),
+        Err(TsaError::ImprintMismatch)
+    ));
+    let mut record = record();
+    record.timestamp_token = Some(hex::encode(&token));
+    assert!(matches!(
+        tsa::verify_position_timestamp(&record, 5),
+        Err(TsaError::ImprintMismatch)
+    ));
+}
+
+#[test]
+fn nonce_mismatch_is_rejected() {
+    let tsa =
+        CannedTsa(|digest, nonce| response(0, Some(&token(&digest, GEN_TIME, Some(nonce ^ 1)))));
+    let digest = record().digest().unwrap();
+    assert!(matches!(
+        tsa::request_timestamp(&tsa, URL, &digest),
+        Err(TsaError::NonceMismatch)
+    ));
+}
+
+#[test]
+fn gen_time_outside_tolerance_is_rejected() {
+    let record = record();
+    let digest = record.digest().unwrap();
+    let token = token(&digest, GEN_TIME, None);
+    assert_eq!(
+        tsa::verify_token(&token, &digest, GEN_UNIX + 60, 60)
+            .unwrap()
+            .gen_time,
+        GEN_UNIX
+    );
+    for claimed in [GEN_UNIX + 61, GEN_UNIX - 61] {
+        match tsa::verify_token(&token, &digest, claimed, 60) {
+            Err(TsaError::TimeOutOfTolerance {
+                claimed: reported,
+                tsa_time,
+            }) => assert_eq!((reported, tsa_time), (claimed, GEN_UNIX)),
+            other => panic!("{:?}", other),
+        }
+    }
+    // the record itself is 2 seconds older than the token
+    let mut record = record;
+    record.timestamp_token = Some(hex::encode(&token));
+    assert!(matches!(
+        tsa::verify_position_timestamp(&record, 1),
+        Err(TsaError::TimeOutOfTolerance { .. })
+    ));
+}
+
+#[test]
+fn malformed_der_is_rejected() {
+    let digest = record().digest().unwrap();
+    let response = response(0, Some(&token(&digest, GEN_TIME, None)));
+    // cut short, by a byte or in the middle of a long form length
+    for len in [response.len() - 1, 1, 3] {
+        assert_eq!(
+            der_error(tsa::parse_response(&response[..len])),
+            "truncated DER input",
+            "{}",
+            len
+        );
+    }
+    // a length longer than the content left
+    let mut overlong = response.clone();
+    overlong[2] += 1;
+    assert_eq!(
+        der_error(tsa::parse_response(&overlong)),
+        "truncated DER input"
+    );
+    // lengths of more than 4 bytes, or of the indefinite form
+    for header in [[0x30, 0x85, 0, 0, 0, 0, 0x10], [0x30, 0x80, 0, 0, 0, 0, 0]] {
+        assert_eq!(
+            der_error(tsa::parse_response(&header)),
+            "unsupported DER length encoding"
+        );
+    }
+    assert_eq!(
+        der_error(tsa::verify_token(&[0x30, 0x05, 0x06], &digest, GEN_UNIX, 5)),
+        "truncated DER input"
+    );
+}
+
+#[test]
+fn gen_time_fields_are_bounded() {
+    let digest = record().digest().unwrap();
+    assert_eq!(
+        tsa::parse_token(&token(&digest, "20241014083059.25Z", None))
+            .unwrap()
+            .gen_time,
+        GEN_UNIX + 59
+    );
+    for gen_time in [
+        "20241014083060Z",
+        "20241014083099Z",
+        "20241014086000Z",
+        "20241014243000Z",
+        "20241314083000Z",
+        "20241014083000",
+    ] {
+        assert_eq!(
+            der_error(tsa::parse_token(&token(&digest, gen_time, None))),
+            "malformed GeneralizedTime",
+            "{}",
+            gen_time
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Signature counters of device keys
+
+use std::fs;
+use std::path::PathBuf;
+
+use sign_data_rust::usage::{KeyUsage, UsageError, UsageState};
+
+fn state_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("key-state-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn restarts_around_the_limit() {
+    let path = state_path("limit");
+    let _ = fs::remove_file(&path);
+    let mut state = UsageState::load(&path).unwrap();
+    state.set_limit("02ab", Some(3)).unwrap();
+    assert_eq!(state.reserve("02ab", false).unwrap(), 1);
+    assert_eq!(state.reserve("02ab", false).unwrap(), 2);
+
+    // every count is on disk before the signature, a restart goes on from it
+    let mut state = UsageState::load(&path).unwrap();
+    assert_eq!(
+        state.usage("02ab"),
+        KeyUsage {
+            count: 2,
+            limit: Some(3)
+        }
+    );
+    assert_eq!(state.reserve("02ab", false).unwrap(), 3);
+    assert_eq!(state.usage("02ab").remaining(), Some(0));
+
+    // the limit holds across restarts, unless forced
+    let mut state = UsageState::load(&path).unwrap();
+    assert!(matches!(
+        state.reserve("02ab", false),
+        Err(UsageError::KeyExhausted { limit: 3, .. })
+    ));
+    assert_eq!(state.reserve("02ab", true).unwrap(), 4);
+    let mut state = UsageState::load(&path).unwrap();
+    assert!(matches!(
+        state.reserve("02ab", false),
+        Err(UsageError::KeyExhausted { .. })
+    ));
+    assert_eq!(state.usage("02ab").count, 4);
+
+    // other keys are counted apart, without a limit
+    assert_eq!(state.reserve("03cd", false).unwrap(), 1);
+    assert_eq!(UsageState::load(&path).unwrap().keys().count(), 2);
+
+    // raising the limit lets the key sign again
+    state.set_limit("02ab", Some(5)).unwrap();
+    let mut state = UsageState::load(&path).unwrap();
+    assert_eq!(state.reserve("02ab", false).unwrap(), 5);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn malformed_state_is_refused() {
+    let path = state_path("malformed");
+    fs::write(&path, "02ab 2 3\n03cd two\n").unwrap();
+    assert!(matches!(
+        UsageState::load(&path),
+        Err(UsageError::Malformed(2))
+    ));
+    fs::remove_file(&path).unwrap();
+}
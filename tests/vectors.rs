@@ -0,0 +1,56 @@
+//! Interoperability vectors, generated then checked
+
+use sign_data_rust::vectors::{self, VectorSet, VECTORS_FORMAT};
+
+#[test]
+fn generated_vectors_check() {
+    let set = vectors::generate(b"my-seed");
+    assert_eq!(set.format, VECTORS_FORMAT);
+    assert_eq!(set.seed, hex::encode(b"my-seed"));
+    // every position, under both schemes, 2 keys each, and both versions
+    assert_eq!(set.vectors.len() % 8, 0);
+    assert!(vectors::check(&set).is_empty());
+
+    let json = serde_json::to_string_pretty(&set).unwrap();
+    let read: VectorSet = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, set);
+    assert!(vectors::check(&read).is_empty());
+}
+
+#[test]
+fn deterministic() {
+    assert_eq!(vectors::generate(b"my-seed"), vectors::generate(b"my-seed"));
+    let (one, other) = (vectors::generate(b"one"), vectors::generate(b"other"));
+    assert_ne!(one.vectors[0].secret_key, other.vectors[0].secret_key);
+    assert_ne!(one.vectors.last(), other.vectors.last());
+}
+
+#[test]
+fn differences_reported() {
+    let set = vectors::generate(b"my-seed");
+    let mut altered = set.clone();
+    altered.vectors[3].signature_der = "3000".to_string();
+    altered.vectors[5].position.latitude = 1.0;
+    altered.vectors[6].signature = altered.vectors[7].signature.clone();
+    let differences = vectors::check(&altered);
+    assert_eq!(
+        differences[0],
+        format!(
+            "vector 3: signature_der is 3000, expected {}",
+            set.vectors[3].signature_der
+        )
+    );
+    // the position is not signed by the vector any more
+    let moved: Vec<_> = differences
+        .iter()
+        .filter(|difference| difference.starts_with("vector 5: "))
+        .collect();
+    assert!(moved.len() >= 3, "{:?}", moved);
+    assert!(differences.contains(&"vector 6: signature does not verify".to_string()));
+
+    altered.format = VECTORS_FORMAT + 1;
+    assert_eq!(
+        vectors::check(&altered),
+        [format!("unsupported vectors format {}", VECTORS_FORMAT + 1)]
+    );
+}
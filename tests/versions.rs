@@ -0,0 +1,55 @@
+//! Records signed before the protocol had versions
+
+use std::fs;
+use std::path::PathBuf;
+
+use secp256k1::SecretKey;
+use sign_data_rust::scheme::Scheme;
+use sign_data_rust::{
+    hash_position, try_sign_position, verify_signed_position, SignedPosition, VerifyError,
+};
+
+/// Record printed by the first release, signed with its built-in demo key
+fn fixture() -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/legacy-v1.json");
+    fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn frozen_v1_record_verifies() {
+    let json = fixture();
+    assert!(!json.contains("version"));
+    let record: SignedPosition = serde_json::from_str(&json).unwrap();
+    assert_eq!(record.version, 1);
+    assert_eq!(record.scheme, Scheme::Secp256k1);
+    assert_eq!(verify_signed_position(&record), Ok(()));
+    assert_eq!(record.digest().unwrap(), hash_position(&record.position));
+
+    // signing it again under version 1 gives the very same record
+    let key = SecretKey::from_slice(b"12345678901234567890123456789011").unwrap();
+    let resigned = try_sign_position(record.position.clone(), 1, &key).unwrap();
+    assert_eq!(resigned, record);
+
+    // written back with its version, it still verifies
+    let rewritten = serde_json::to_string(&record).unwrap();
+    assert!(rewritten.starts_with(r#"{"version":1,"#), "{}", rewritten);
+    let record: SignedPosition = serde_json::from_str(&rewritten).unwrap();
+    assert_eq!(verify_signed_position(&record), Ok(()));
+}
+
+#[test]
+fn frozen_v1_record_is_not_read_as_v2() {
+    let mut record: SignedPosition = serde_json::from_str(&fixture()).unwrap();
+    record.version = 2;
+    assert!(matches!(
+        verify_signed_position(&record),
+        Err(VerifyError::InvalidSignature { .. })
+    ));
+
+    let mut record: SignedPosition = serde_json::from_str(&fixture()).unwrap();
+    record.position.latitude += 0.0001;
+    assert!(matches!(
+        verify_signed_position(&record),
+        Err(VerifyError::InvalidSignature { .. })
+    ));
+}